@@ -0,0 +1,73 @@
+/* C interface to the pngme chunk library. Link against libpngme.a or
+ * libpngme.so; the static library also needs -lpthread -ldl -lm. */
+#ifndef PNGME_H
+#define PNGME_H
+
+#include <stddef.h>
+#include <stdint.h>
+
+#ifdef __cplusplus
+extern "C" {
+#endif
+
+/* An owned PNG, MNG or JNG file. */
+typedef struct PngmePng PngmePng;
+
+/* A single chunk, either owned or borrowed from a PngmePng. */
+typedef struct PngmeChunk PngmeChunk;
+
+/* Result of every fallible function. On anything but PNGME_OK,
+ * pngme_last_error() describes what went wrong. PNGME_INTERNAL means pngme
+ * panicked, which is a bug. */
+typedef enum {
+    PNGME_OK = 0,
+    PNGME_NULL_POINTER = 1,
+    PNGME_INVALID_PNG = 2,
+    PNGME_INVALID_CHUNK_TYPE = 3,
+    PNGME_NOT_FOUND = 4,
+    PNGME_OUT_OF_RANGE = 5,
+    PNGME_INTERNAL = 6,
+} PngmeStatus;
+
+/* Description of the last error on this thread, or NULL. Valid until the
+ * next failing call on this thread. */
+const char *pngme_last_error(void);
+
+/* Parses len bytes at data. Release *out with pngme_png_free. */
+PngmeStatus pngme_png_from_bytes(const uint8_t *data, size_t len, PngmePng **out);
+void pngme_png_free(PngmePng *png);
+
+size_t pngme_png_chunk_count(const PngmePng *png);
+
+/* Borrows the chunk at index, valid until png is changed or freed. */
+PngmeStatus pngme_png_chunk_at(const PngmePng *png, size_t index, const PngmeChunk **out);
+
+/* Moves chunk into png where the PNG spec requires it. On success png owns
+ * the chunk and it must not be freed. */
+PngmeStatus pngme_png_add_chunk(PngmePng *png, PngmeChunk *chunk);
+
+/* Removes the first chunk of the given type. */
+PngmeStatus pngme_png_remove_chunk(PngmePng *png, const char *chunk_type);
+
+/* Serializes png. Release *out with pngme_bytes_free(*out, *len). */
+PngmeStatus pngme_png_to_bytes(const PngmePng *png, uint8_t **out, size_t *len);
+void pngme_bytes_free(uint8_t *data, size_t len);
+
+/* Makes a chunk holding a copy of len bytes at data, which may be NULL if
+ * len is 0. len must be at most 2^31-1. Release *out with pngme_chunk_free
+ * unless it is added to a png. */
+PngmeStatus pngme_chunk_new(const char *chunk_type, const uint8_t *data, size_t len,
+                            PngmeChunk **out);
+void pngme_chunk_free(PngmeChunk *chunk);
+
+/* Writes the chunk type and a NUL terminator to out, which needs 5 bytes. */
+PngmeStatus pngme_chunk_type(const PngmeChunk *chunk, char *out);
+
+/* Returns the data of chunk and stores its length in *len. */
+const uint8_t *pngme_chunk_data(const PngmeChunk *chunk, size_t *len);
+
+#ifdef __cplusplus
+}
+#endif
+
+#endif
@@ -0,0 +1,63 @@
+/* Exercises pngme.h from C. Built and run by the ffi tests in src/ffi.rs. */
+#include <stdio.h>
+#include <string.h>
+
+#include "pngme.h"
+
+#define CHECK(x)                                                                  \
+    do {                                                                          \
+        if (!(x)) {                                                               \
+            fprintf(stderr, "%s:%d: %s failed: %s\n", __FILE__, __LINE__, #x,     \
+                    pngme_last_error() ? pngme_last_error() : "no error");        \
+            return 1;                                                             \
+        }                                                                         \
+    } while (0)
+
+/* A 1x1 grayscale image with only IHDR and IEND. */
+static const uint8_t PNG[] = {
+    0x89, 'P', 'N', 'G', '\r', '\n', 0x1a, '\n',
+    0, 0, 0, 13, 'I', 'H', 'D', 'R', 0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0,
+    0x3a, 0x7e, 0x9b, 0x55,
+    0, 0, 0, 0, 'I', 'E', 'N', 'D', 0xae, 0x42, 0x60, 0x82,
+};
+
+int main(void) {
+    PngmePng *png = NULL;
+    CHECK(pngme_png_from_bytes(PNG, sizeof PNG, &png) == PNGME_OK);
+    CHECK(pngme_png_chunk_count(png) == 2);
+
+    PngmeChunk *chunk = NULL;
+    const char *message = "hello from C";
+    CHECK(pngme_chunk_new("ruSt", (const uint8_t *)message, strlen(message), &chunk) == PNGME_OK);
+    CHECK(pngme_png_add_chunk(png, chunk) == PNGME_OK);
+    CHECK(pngme_chunk_new("ruSt", (const uint8_t *)message, (size_t)1 << 31, &chunk) ==
+          PNGME_OUT_OF_RANGE);
+    CHECK(pngme_png_chunk_count(png) == 3);
+
+    const PngmeChunk *added = NULL;
+    char type[5];
+    size_t len = 0;
+    CHECK(pngme_png_chunk_at(png, 1, &added) == PNGME_OK);
+    CHECK(pngme_chunk_type(added, type) == PNGME_OK);
+    CHECK(strcmp(type, "ruSt") == 0);
+    const uint8_t *data = pngme_chunk_data(added, &len);
+    CHECK(len == strlen(message) && memcmp(data, message, len) == 0);
+
+    uint8_t *bytes = NULL;
+    CHECK(pngme_png_to_bytes(png, &bytes, &len) == PNGME_OK);
+    CHECK(len == sizeof PNG + 12 + strlen(message));
+    PngmePng *reread = NULL;
+    CHECK(pngme_png_from_bytes(bytes, len, &reread) == PNGME_OK);
+    CHECK(pngme_png_chunk_count(reread) == 3);
+    pngme_png_free(reread);
+    pngme_bytes_free(bytes, len);
+
+    CHECK(pngme_png_remove_chunk(png, "ruSt") == PNGME_OK);
+    CHECK(pngme_png_remove_chunk(png, "ruSt") == PNGME_NOT_FOUND);
+    CHECK(pngme_last_error() != NULL);
+    CHECK(pngme_png_remove_chunk(png, "bad") == PNGME_INVALID_CHUNK_TYPE);
+    CHECK(pngme_png_chunk_at(png, 7, &added) == PNGME_OUT_OF_RANGE);
+    CHECK(pngme_png_from_bytes(PNG, 4, &reread) == PNGME_INVALID_PNG);
+    pngme_png_free(png);
+    return 0;
+}
@@ -0,0 +1,12 @@
+#![no_main]
+
+use std::str::FromStr;
+
+use libfuzzer_sys::fuzz_target;
+use pngme::chunk_type::ChunkType;
+
+fuzz_target!(|data: &str| {
+    if let Ok(chunk_type) = ChunkType::from_str(data) {
+        assert_eq!(chunk_type.to_string(), data);
+    }
+});
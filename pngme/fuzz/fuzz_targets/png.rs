@@ -0,0 +1,18 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pngme::png::{Png, PngRef};
+
+fuzz_target!(|data: &[u8]| {
+    let borrowed = PngRef::new(data).and_then(|x| x.to_png());
+    match Png::try_from(data) {
+        Ok(png) => {
+            // Whatever parses must serialize back to the same bytes, minus any
+            // trailing bytes too short to hold a chunk length.
+            let bytes = png.as_bytes();
+            assert_eq!(&data[..bytes.len()], &bytes[..]);
+            assert_eq!(borrowed.unwrap().as_bytes(), bytes);
+        }
+        Err(_) => assert!(borrowed.is_err()),
+    }
+});
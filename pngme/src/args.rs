@@ -1,32 +1,68 @@
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::fs;
+use std::io::{self, Read, Write};
+use std::path::Path;
 use std::str::FromStr;
 
-use crate::chunk::Chunk;
-use crate::chunk_type::ChunkType;
-use crate::png::Png;
-use clap::{app_from_crate, App, AppSettings, Arg, ArgMatches};
+use clap::{app_from_crate, App, AppSettings, Arg, ArgGroup, ArgMatches};
+use pngme::carrier::{self, Carrier};
+use pngme::chunk::Chunk;
+use pngme::chunk_type::ChunkType;
+use pngme::color::{self, ColorInfo, RenderingIntent};
+use pngme::ecc::{self, Ecc};
+use pngme::exif::Group;
+use pngme::hex;
+use pngme::known::KnownChunk;
+use pngme::manifest::Manifest;
+use pngme::message::{self, Message};
+use pngme::optimize;
+use pngme::png::Png;
+use pngme::provenance;
+use pngme::recipient::{self, Identity, Recipient};
+use pngme::scan;
+use pngme::schema::Schema;
+use pngme::shamir::{self, Share};
+#[cfg(target_os = "linux")]
+use pngme::watch::{Debouncer, Pipeline, Seen};
+use pngme::Result;
+
+/// Chunk type `encode --shares` stores shares in unless `--type` is given.
+const SHARE_CHUNK_TYPE: &str = "shRe";
+
+/// The `--provenance` flag of every subcommand that edits files.
+fn provenance_arg() -> Arg<'static> {
+    Arg::new("provenance")
+        .long("provenance")
+        .help("record changes in the history chunk of the file, see `history`")
+}
 
 pub fn get_matches() -> ArgMatches {
-    app_from_crate!()
+    app().get_matches()
+}
+
+fn app() -> App<'static> {
+    let app = app_from_crate!()
         .global_setting(AppSettings::PropagateVersion)
         .global_setting(AppSettings::UseLongFormatForHelpSubcommand)
         .subcommand(
             App::new("encode")
-                .about("encode message into a png file")
+                .about("encode message into a png or webp file")
                 .arg(
                     Arg::new("file_path")
                         .required(true)
                         .value_name("FILE")
-                        .help("path to the PNG file"),
+                        .help("path to the PNG, MNG, JNG or WebP file, or - for stdin"),
                 )
                 .arg(
                     Arg::new("chunk_type")
-                        .required(true)
+                        .required_unless_present_any(["schema", "shares"])
                         .value_name("CHUNK_TYPE")
-                        .help("PNG Chunk Type"),
+                        .help("PNG chunk type or RIFF four character code, ignored with --mode trailer"),
                 )
                 .arg(
                     Arg::new("message")
-                        .required(true)
+                        .required_unless_present_any(["schema", "shares"])
                         .value_name("MESSAGE")
                         .help("message to encode"),
                 )
@@ -34,84 +70,1777 @@ pub fn get_matches() -> ArgMatches {
                     Arg::new("outupt_file")
                         .required(false)
                         .value_name("OUTPUT_FILE")
-                        .help("output to <OUTPUT_FILE> instead of overwriting"),
-                ),
+                        .help("output to <OUTPUT_FILE> instead of overwriting, or - for stdout"),
+                )
+                .arg(
+                    Arg::new("more_files")
+                        .multiple_values(true)
+                        .requires("shares")
+                        .value_name("FILE")
+                        .help("with --shares, every positional argument is an image to store a share in"),
+                )
+                .arg(
+                    Arg::new("label")
+                        .long("label")
+                        .takes_value(true)
+                        .value_name("LABEL")
+                        .help("store the message in a labelled envelope"),
+                )
+                .arg(
+                    Arg::new("content_type")
+                        .long("content-type")
+                        .takes_value(true)
+                        .requires("label")
+                        .default_value("text/plain")
+                        .value_name("MIME_TYPE")
+                        .help("content type of a labelled message"),
+                )
+                .arg(
+                    Arg::new("expires_in")
+                        .long("expires-in")
+                        .takes_value(true)
+                        .requires("label")
+                        .value_name("DURATION")
+                        .help(
+                            "expire a labelled message after <DURATION>, e.g. 90s, 30m, 12h or 7d",
+                        ),
+                )
+                .arg(
+                    Arg::new("recipient")
+                        .long("recipient")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .value_name("KEY_FILE")
+                        .help("encrypt the message for the public key in <KEY_FILE>"),
+                )
+                .arg(
+                    Arg::new("ecc")
+                        .long("ecc")
+                        .takes_value(true)
+                        .value_name("PERCENT")
+                        .help("add Reed-Solomon parity making up <PERCENT> of the data, e.g. 20%"),
+                )
+                .arg(
+                    Arg::new("shares")
+                        .long("shares")
+                        .takes_value(true)
+                        .requires("threshold")
+                        .value_name("COUNT")
+                        .help("split the message into <COUNT> secret shares, one per image, overwriting them"),
+                )
+                .arg(
+                    Arg::new("threshold")
+                        .long("threshold")
+                        .takes_value(true)
+                        .requires("shares")
+                        .value_name("COUNT")
+                        .help("number of shares needed to rebuild the message"),
+                )
+                .arg(
+                    Arg::new("share_message")
+                        .long("message")
+                        .takes_value(true)
+                        .requires("shares")
+                        .value_name("MESSAGE")
+                        .help("message to split with --shares, read from stdin if not given"),
+                )
+                .arg(
+                    Arg::new("mode")
+                        .long("mode")
+                        .takes_value(true)
+                        .possible_values(["chunk", "trailer"])
+                        .default_value("chunk")
+                        .value_name("MODE")
+                        .help("store the message in a new chunk, or append it after IEND"),
+                )
+                .arg(
+                    Arg::new("schema")
+                        .long("schema")
+                        .takes_value(true)
+                        .requires("schema_type")
+                        .conflicts_with_all(&["chunk_type", "label", "recipient", "ecc", "shares"])
+                        .value_name("SCHEMA_FILE")
+                        .help("build a chunk from the fields given with --set, as laid out in <SCHEMA_FILE>"),
+                )
+                .arg(
+                    Arg::new("schema_type")
+                        .long("type")
+                        .takes_value(true)
+                        .value_name("CHUNK_TYPE")
+                        .help("chunk type to build with --schema, or to store shares in with --shares (default shRe)"),
+                )
+                .arg(
+                    Arg::new("set")
+                        .long("set")
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .requires("schema")
+                        .value_name("FIELD=VALUE")
+                        .help("set a field of the chunk built with --schema"),
+                )
+                .arg(
+                    Arg::new("schema_output")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .requires("schema")
+                        .value_name("OUTPUT_FILE")
+                        .help("with --schema, output to <OUTPUT_FILE> instead of overwriting, or - for stdout"),
+                )
+                .arg(provenance_arg()),
         )
         .subcommand(
             App::new("decode")
-                .about("decode message from a png file")
+                .about("decode message from a png or webp file")
                 .arg(
                     Arg::new("file_path")
                         .required(true)
+                        .multiple_values(true)
                         .value_name("FILE")
-                        .help("path to the PNG file"),
+                        .help("paths to the PNG, MNG, JNG or WebP files, or - for stdin. \
+                               A single file may be followed by the CHUNK_TYPE to search for. \
+                               Without a chunk type or --label, secret shares are combined"),
                 )
                 .arg(
                     Arg::new("chunk_type")
-                        .required(true)
+                        .long("chunk-type")
+                        .takes_value(true)
                         .value_name("CHUNK_TYPE")
-                        .help("type of PNG chunk to search for"),
+                        .help("type of chunk to search for, needed when several files are given"),
+                )
+                .arg(
+                    Arg::new("trailer")
+                        .long("trailer")
+                        .help("decode the data after IEND instead of chunks"),
+                )
+                .arg(
+                    Arg::new("label")
+                        .long("label")
+                        .takes_value(true)
+                        .value_name("LABEL")
+                        .help("only decode messages labelled <LABEL>"),
+                )
+                .arg(
+                    Arg::new("include_expired")
+                        .long("include-expired")
+                        .help("also decode messages that have expired"),
+                )
+                .arg(
+                    Arg::new("identity")
+                        .long("identity")
+                        .takes_value(true)
+                        .value_name("KEY_FILE")
+                        .help("decrypt messages with the private key in <KEY_FILE>"),
+                )
+                .arg(
+                    Arg::new("lenient")
+                        .long("lenient")
+                        .help("read chunks with a bad CRC instead of rejecting the file"),
+                )
+                ,
+        )
+        .subcommand(
+            App::new("keygen")
+                .about("generate a key pair for encrypted messages")
+                .arg(
+                    Arg::new("output_file")
+                        .required(true)
+                        .value_name("OUTPUT_FILE")
+                        .help("write the private key to <OUTPUT_FILE> and the public key to <OUTPUT_FILE>.pub"),
+                ),
+        )
+        .subcommand(
+            App::new("list")
+                .about("list the labelled messages in a png or webp file")
+                .arg(
+                    Arg::new("file_path")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("path to the PNG, MNG, JNG or WebP file, or - for stdin"),
                 ),
         )
         .subcommand(
             App::new("remove")
-                .about("remove chunk from a png file")
+                .about("remove chunk from a png or webp file")
+                .arg(
+                    Arg::new("file_path")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("path to the PNG, MNG, JNG or WebP file, or - for stdin"),
+                )
+                .arg(
+                    Arg::new("chunk_type")
+                        .required(true)
+                        .value_name("CHUNK_TYPE")
+                        .help("type of chunk to remove"),
+                )
+                .arg(
+                    Arg::new("output_file")
+                        .required(false)
+                        .value_name("OUTPUT_FILE")
+                        .help("output to <OUTPUT_FILE> instead of overwriting, or - for stdout"),
+                )
+                .arg(provenance_arg()),
+        )
+        .subcommand(
+            App::new("print")
+                .about("print the png file")
+                .arg(
+                    Arg::new("file_path")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("path to the PNG file, or - for stdin"),
+                )
+                .arg(
+                    Arg::new("schema")
+                        .long("schema")
+                        .takes_value(true)
+                        .value_name("SCHEMA_FILE")
+                        .help("decode the custom chunks described in <SCHEMA_FILE>"),
+                ),
+        )
+        .subcommand(
+            App::new("scan")
+                .about("look for data hidden in png files")
                 .arg(
                     Arg::new("file_path")
                         .required(true)
+                        .multiple_values(true)
                         .value_name("FILE")
-                        .help("path to the PNG file"),
+                        .help("paths to the PNG, MNG or JNG files"),
+                )
+                .arg(
+                    Arg::new("strip_trailing")
+                        .long("strip-trailing")
+                        .help("remove data after IEND, overwriting the files"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(["text", "json"])
+                        .default_value("text")
+                        .help("report format"),
+                )
+                .arg(provenance_arg().requires("strip_trailing")),
+        )
+        .subcommand(
+            App::new("history")
+                .about("print the changes recorded with --provenance and check their hash links")
+                .arg(
+                    Arg::new("file_path")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("path to the PNG, MNG or JNG file, or - for stdin"),
+                ),
+        )
+        .subcommand(
+            App::new("optimize")
+                .about("losslessly recompress the image data of png files")
+                .arg(
+                    Arg::new("file_path")
+                        .required(true)
+                        .multiple_values(true)
+                        .value_name("FILE")
+                        .help("paths to the PNG files"),
+                )
+                .arg(
+                    Arg::new("strip")
+                        .long("strip")
+                        .help("also remove ancillary chunks that don't affect rendering"),
+                )
+                .arg(provenance_arg()),
+        )
+        .subcommand(
+            App::new("reidat")
+                .about("merge or split the IDAT chunks of a png file")
+                .arg(
+                    Arg::new("file_path")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("path to the PNG file, or - for stdin"),
+                )
+                .arg(
+                    Arg::new("max_chunk_size")
+                        .long("max-chunk-size")
+                        .takes_value(true)
+                        .value_name("BYTES")
+                        .help("split the image data into IDAT chunks of at most <BYTES> bytes"),
+                )
+                .arg(
+                    Arg::new("output_file")
+                        .required(false)
+                        .value_name("OUTPUT_FILE")
+                        .help("output to <OUTPUT_FILE> instead of overwriting, or - for stdout"),
+                )
+                .arg(provenance_arg()),
+        )
+        .subcommand(
+            App::new("inspect")
+                .about("show a hex dump and decoded contents of png chunks")
+                .arg(
+                    Arg::new("file_path")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("path to the PNG file, or - for stdin"),
                 )
                 .arg(
                     Arg::new("chunk_type")
+                        .long("chunk")
+                        .takes_value(true)
+                        .value_name("CHUNK_TYPE")
+                        .help("inspect every chunk of type <CHUNK_TYPE>"),
+                )
+                .arg(
+                    Arg::new("index")
+                        .long("index")
+                        .takes_value(true)
+                        .value_name("N")
+                        .help("inspect the chunk at position <N>"),
+                )
+                .group(
+                    ArgGroup::new("selector")
+                        .args(&["chunk_type", "index"])
+                        .required(true),
+                ),
+        )
+        .subcommand(
+            App::new("export")
+                .about("write chunks of a png file to standalone .chunk files")
+                .arg(
+                    Arg::new("file_path")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("path to the PNG file, or - for stdin"),
+                )
+                .arg(
+                    Arg::new("chunk_type")
+                        .long("type")
+                        .required(true)
+                        .takes_value(true)
+                        .multiple_occurrences(true)
+                        .value_name("CHUNK_TYPE")
+                        .help("PNG chunk type to export"),
+                )
+                .arg(
+                    Arg::new("output_dir")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .default_value(".")
+                        .value_name("DIR")
+                        .help("directory to write the .chunk files to"),
+                ),
+        )
+        .subcommand(
+            App::new("import")
+                .about("insert chunks from standalone .chunk files into a png file")
+                .arg(
+                    Arg::new("file_path")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("path to the PNG file, or - for stdin"),
+                )
+                .arg(
+                    Arg::new("chunk_files")
+                        .required(true)
+                        .multiple_values(true)
+                        .value_name("CHUNK_FILE")
+                        .help("paths to the .chunk files"),
+                )
+                .arg(
+                    Arg::new("output_file")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("OUTPUT_FILE")
+                        .help("output to <OUTPUT_FILE> instead of overwriting, or - for stdout"),
+                )
+                .arg(provenance_arg()),
+        )
+        .subcommand(
+            App::new("transplant")
+                .about("copy chunks from one png file into another")
+                .arg(
+                    Arg::new("source")
+                        .required(true)
+                        .value_name("SRC")
+                        .help("path to the PNG file to copy chunks from, or - for stdin"),
+                )
+                .arg(
+                    Arg::new("destination")
+                        .required(true)
+                        .value_name("DST")
+                        .help("path to the PNG file to copy chunks into, or - for stdin"),
+                )
+                .arg(
+                    Arg::new("chunk_types")
+                        .long("types")
                         .required(true)
+                        .takes_value(true)
+                        .use_value_delimiter(true)
+                        .multiple_values(true)
                         .value_name("CHUNK_TYPE")
-                        .help("PNG chunk type to remove"),
+                        .help("comma separated PNG chunk types to copy"),
+                )
+                .arg(
+                    Arg::new("output_file")
+                        .short('o')
+                        .long("output")
+                        .takes_value(true)
+                        .value_name("OUTPUT_FILE")
+                        .help("output to <OUTPUT_FILE> instead of overwriting <DST>, or - for stdout"),
+                )
+                .arg(provenance_arg()),
+        )
+        .subcommand(
+            App::new("dump")
+                .about("print a manifest describing every chunk of a png file")
+                .arg(
+                    Arg::new("file_path")
+                        .required(true)
+                        .value_name("FILE")
+                        .help("path to the PNG file, or - for stdin"),
+                )
+                .arg(
+                    Arg::new("format")
+                        .long("format")
+                        .takes_value(true)
+                        .possible_values(["json", "yaml"])
+                        .default_value("json")
+                        .help("manifest format"),
                 ),
         )
         .subcommand(
-            App::new("print").about("print the png file").arg(
+            App::new("build")
+                .about("build a png file from a manifest")
+                .arg(
+                    Arg::new("manifest")
+                        .required(true)
+                        .value_name("MANIFEST")
+                        .help("path to the JSON or YAML manifest"),
+                )
+                .arg(
+                    Arg::new("output_file")
+                        .short('o')
+                        .long("output")
+                        .required(true)
+                        .takes_value(true)
+                        .value_name("OUTPUT_FILE")
+                        .help("path to write the PNG file to, or - for stdout"),
+                ),
+        )
+        .subcommand(
+            App::new("exif")
+                .about("read and edit the EXIF data of a png file")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    App::new("get")
+                        .about("print EXIF tags")
+                        .arg(
+                            Arg::new("file_path")
+                                .required(true)
+                                .value_name("FILE")
+                                .help("path to the PNG file, or - for stdin"),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .value_name("TAG")
+                                .help("only print the tag named <TAG>, e.g. Orientation"),
+                        ),
+                )
+                .subcommand(
+                    App::new("set")
+                        .about("set an EXIF tag, adding an eXIf chunk if needed")
+                        .arg(
+                            Arg::new("file_path")
+                                .required(true)
+                                .value_name("FILE")
+                                .help("path to the PNG file, or - for stdin"),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .required(true)
+                                .value_name("TAG")
+                                .help("name of the tag, e.g. Orientation"),
+                        )
+                        .arg(
+                            Arg::new("value")
+                                .required(true)
+                                .value_name("VALUE")
+                                .help("new value, with list items separated by commas"),
+                        )
+                        .arg(
+                            Arg::new("output_file")
+                                .required(false)
+                                .value_name("OUTPUT_FILE")
+                                .help("output to <OUTPUT_FILE> instead of overwriting, or - for stdout"),
+                        )
+                        .arg(provenance_arg()),
+                )
+                .subcommand(
+                    App::new("strip")
+                        .about("remove EXIF tags, or the whole eXIf chunk if none are given")
+                        .arg(
+                            Arg::new("file_path")
+                                .required(true)
+                                .value_name("FILE")
+                                .help("path to the PNG file, or - for stdin"),
+                        )
+                        .arg(
+                            Arg::new("tag")
+                                .multiple_occurrences(true)
+                                .value_name("TAG")
+                                .help("names of the tags to remove"),
+                        )
+                        .arg(Arg::new("gps").long("gps").help("remove all GPS tags"))
+                        .arg(
+                            Arg::new("thumbnail")
+                                .long("thumbnail")
+                                .help("remove the thumbnail and its tags"),
+                        )
+                        .arg(provenance_arg()),
+                ),
+        )
+        .subcommand(
+            App::new("color")
+                .about("inspect and normalize the color space of a png file")
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    App::new("show")
+                        .about("print the effective color space and any rule violations")
+                        .arg(
+                            Arg::new("file_path")
+                                .required(true)
+                                .value_name("FILE")
+                                .help("path to the PNG file, or - for stdin"),
+                        ),
+                )
+                .subcommand(
+                    App::new("set-srgb")
+                        .about("replace the color space chunks with a plain sRGB chunk")
+                        .arg(
+                            Arg::new("file_path")
+                                .required(true)
+                                .value_name("FILE")
+                                .help("path to the PNG file, or - for stdin"),
+                        )
+                        .arg(
+                            Arg::new("output_file")
+                                .required(false)
+                                .value_name("OUTPUT_FILE")
+                                .help("output to <OUTPUT_FILE> instead of overwriting, or - for stdout"),
+                        )
+                        .arg(
+                            Arg::new("intent")
+                                .long("intent")
+                                .takes_value(true)
+                                .possible_values([
+                                    "perceptual",
+                                    "relative",
+                                    "saturation",
+                                    "absolute",
+                                ])
+                                .default_value("perceptual")
+                                .help("rendering intent"),
+                        )
+                        .arg(provenance_arg()),
+                )
+                .subcommand(
+                    App::new("extract-icc")
+                        .about("save the embedded ICC profile to a file")
+                        .arg(
+                            Arg::new("file_path")
+                                .required(true)
+                                .value_name("FILE")
+                                .help("path to the PNG file, or - for stdin"),
+                        )
+                        .arg(
+                            Arg::new("output_file")
+                                .required(true)
+                                .value_name("ICC_FILE")
+                                .help("path to write the profile to, or - for stdout"),
+                        ),
+                ),
+        );
+    #[cfg(target_os = "linux")]
+    let app = app.subcommand(
+        App::new("watch")
+            .about("run pngme commands on every png file written to a directory")
+            .arg(
+                Arg::new("dir")
+                    .required(true)
+                    .value_name("DIR")
+                    .help("directory to watch"),
+            )
+            .arg(
+                Arg::new("exec")
+                    .long("exec")
+                    .required(true)
+                    .takes_value(true)
+                    .value_name("PIPELINE")
+                    .help("pngme commands separated by ; with {} for the file, e.g. \"exif strip {} ; encode {} trCk id\""),
+            )
+            .arg(
+                Arg::new("debounce")
+                    .long("debounce")
+                    .takes_value(true)
+                    .default_value("500")
+                    .value_name("MILLISECONDS")
+                    .help("wait until a file has not been written to for <MILLISECONDS>"),
+            )
+            .arg(
+                Arg::new("log")
+                    .long("log")
+                    .takes_value(true)
+                    .value_name("LOG_FILE")
+                    .help("append processed files to <LOG_FILE> instead of DIR/pngme-watch.log"),
+            )
+            .arg(provenance_arg().help("pass --provenance to the commands that edit files")),
+    );
+    #[cfg(feature = "tui")]
+    let app = app.subcommand(
+        App::new("edit")
+            .about("edit the chunks of a png file in a terminal user interface")
+            .arg(
                 Arg::new("file_path")
                     .required(true)
                     .value_name("FILE")
                     .help("path to the PNG file"),
-            ),
-        )
-        .get_matches()
+            )
+            .arg(provenance_arg()),
+    );
+    app
 }
 
 pub fn handle_encode(args: &ArgMatches) {
+    if let Some(schema_path) = args.value_of("schema") {
+        encode_with_schema(args, schema_path);
+        return;
+    }
+    if args.is_present("shares") {
+        encode_shares(args);
+        return;
+    }
+    if args.is_present("schema_type") {
+        fail("--type needs --schema or --shares");
+    }
     let file_path = args.value_of("file_path").unwrap();
-    let chunk_type = ChunkType::from_str(args.value_of("chunk_type").unwrap()).unwrap();
-    let message = args.value_of("message").unwrap();
+    let chunk_type = args.value_of("chunk_type").unwrap();
     let output_path = args.value_of("outupt_file").unwrap_or(file_path);
-    let mut png = Png::from_file(file_path).unwrap();
-    let chunk = Chunk::new(chunk_type, message.as_bytes().to_vec());
-    png.append_chunk(chunk);
-    png.to_file(output_path).unwrap();
+    let data = envelope_data(args, args.value_of("message").unwrap().as_bytes());
+    let bytes = read_input(file_path).unwrap();
+    let mut carrier = carrier::open(&bytes).unwrap();
+    let touched = store_data(args, carrier.as_mut(), chunk_type, data);
+    write_edited(
+        args,
+        output_path,
+        &bytes,
+        carrier.as_bytes(),
+        "encode",
+        &touched,
+    )
+    .unwrap();
+}
+
+/// Splits the message into secret shares and stores one in each image given
+/// as a positional argument.
+fn encode_shares(args: &ArgMatches) {
+    let paths: Vec<&str> = [
+        "file_path",
+        "chunk_type",
+        "message",
+        "outupt_file",
+        "more_files",
+    ]
+    .iter()
+    .flat_map(|x| args.values_of(x).unwrap_or_default())
+    .collect();
+    let chunk_type = args.value_of("schema_type").unwrap_or(SHARE_CHUNK_TYPE);
+    let count = args.value_of("shares").unwrap();
+    let count: u8 = match count.parse() {
+        Ok(count) => count,
+        Err(_) => fail(&format!(
+            "--shares {} must be a number from 2 to 255",
+            count
+        )),
+    };
+    if paths.len() != count as usize {
+        fail(&format!(
+            "--shares {} needs {} images, got {}",
+            count,
+            count,
+            paths.len()
+        ));
+    }
+    let threshold = args.value_of("threshold").unwrap();
+    let threshold: u8 = match threshold.parse() {
+        Ok(threshold) => threshold,
+        Err(_) => fail(&format!(
+            "--threshold {} must be a number from 2 to 255",
+            threshold
+        )),
+    };
+    let message = match args.value_of("share_message") {
+        Some(message) => message.as_bytes().to_vec(),
+        None => read_input("-").unwrap(),
+    };
+    let data = envelope_data(args, &message);
+    let shares = match shamir::split(&data, count, threshold) {
+        Ok(shares) => shares,
+        Err(e) => fail(&e.to_string()),
+    };
+    for (path, share) in paths.iter().zip(shares) {
+        let bytes = read_input(path).unwrap();
+        let mut carrier = carrier::open(&bytes).unwrap();
+        let touched = store_data(args, carrier.as_mut(), chunk_type, share.as_bytes());
+        write_edited(args, path, &bytes, carrier.as_bytes(), "encode", &touched).unwrap();
+    }
+}
+
+/// Wraps `message` as `encode` is asked to: in a labelled envelope, then
+/// encrypted for recipients.
+fn envelope_data(args: &ArgMatches, message: &[u8]) -> Vec<u8> {
+    let data = match args.value_of("label") {
+        Some(label) => {
+            let content_type = args.value_of("content_type").unwrap();
+            let mut envelope = Message::new(label, content_type, message.to_vec());
+            if let Some(duration) = args.value_of("expires_in") {
+                let expires = parse_duration(duration).and_then(|x| {
+                    envelope
+                        .created
+                        .checked_add(x)
+                        .ok_or_else(|| "duration is too long".into())
+                });
+                match expires {
+                    Ok(expires) => envelope.expires = Some(expires),
+                    Err(e) => fail(&format!("--expires-in {}: {}", duration, e)),
+                }
+            }
+            envelope.as_bytes().unwrap()
+        }
+        None => message.to_vec(),
+    };
+    match args.values_of("recipient") {
+        Some(key_files) => {
+            let recipients: Vec<Recipient> = key_files
+                .map(|x| Recipient::from_str(&fs::read_to_string(x).unwrap()).unwrap())
+                .collect();
+            recipient::seal(&data, &recipients).unwrap()
+        }
+        None => data,
+    }
+}
+
+/// Stores `data` in a new `chunk_type` chunk, or after IEND with `--mode
+/// trailer`, adding error correction if asked to. Returns the chunk types
+/// touched, for the history.
+fn store_data<'a>(
+    args: &ArgMatches,
+    carrier: &mut dyn Carrier,
+    chunk_type: &'a str,
+    data: Vec<u8>,
+) -> Vec<&'a str> {
+    let data = match args.value_of("ecc") {
+        Some(ecc) => Ecc::from_str(ecc).unwrap().protect(&data).unwrap(),
+        None => data,
+    };
+    if args.value_of("mode") == Some("trailer") {
+        carrier.append_trailer(&data).unwrap();
+        vec![]
+    } else {
+        carrier.embed(chunk_type, &data).unwrap();
+        vec![chunk_type]
+    }
+}
+
+/// Builds a chunk from `--set` fields following the layout in the schema file.
+fn encode_with_schema(args: &ArgMatches, schema_path: &str) {
+    let file_path = args.value_of("file_path").unwrap();
+    let chunk_type = args.value_of("schema_type").unwrap();
+    let output_path = args.value_of("schema_output").unwrap_or(file_path);
+    let schema = Schema::from_file(schema_path).unwrap();
+    let layout = match schema.get(chunk_type) {
+        Some(layout) => layout,
+        None => fail(&format!(
+            "{} is not described in {}",
+            chunk_type, schema_path
+        )),
+    };
+    let mut values = Vec::new();
+    for field in args.values_of("set").unwrap_or_default() {
+        match field.split_once('=') {
+            Some(value) => values.push(value),
+            None => fail(&format!("--set {:?} must look like FIELD=VALUE", field)),
+        }
+    }
+    let data = layout
+        .encode(&values)
+        .unwrap_or_else(|e| fail(&format!("{}: {}", chunk_type, e)));
+    let bytes = read_input(file_path).unwrap();
+    let mut carrier = carrier::open(&bytes).unwrap();
+    carrier.embed(chunk_type, &data).unwrap();
+    write_edited(
+        args,
+        output_path,
+        &bytes,
+        carrier.as_bytes(),
+        "encode",
+        &[chunk_type],
+    )
+    .unwrap();
+}
+
+/// Parses durations such as `90`, `90s`, `30m`, `12h` or `7d` into seconds.
+fn parse_duration(s: &str) -> Result<u64> {
+    let (value, unit) = match s.find(|x: char| !x.is_ascii_digit()) {
+        Some(idx) => s.split_at(idx),
+        None => (s, "s"),
+    };
+    let multiplier = match unit {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(format!("invalid duration unit {:?}", unit).into()),
+    };
+    value
+        .parse::<u64>()?
+        .checked_mul(multiplier)
+        .ok_or_else(|| "duration is too long".into())
 }
 
 pub fn handle_decode(args: &ArgMatches) {
+    let mut paths: Vec<&str> = args.values_of("file_path").unwrap().collect();
+    // `decode FILE CHUNK_TYPE` still works; with several files the chunk type
+    // only comes from --chunk-type, and `./ruSt` names a file called ruSt.
+    let chunk_type = match args.value_of("chunk_type") {
+        Some(chunk_type) => Some(chunk_type),
+        None if paths.len() == 2 && ChunkType::from_str(paths[1]).is_ok() => paths.pop(),
+        None => None,
+    };
+    let label = args.value_of("label");
+    let trailer = args.is_present("trailer");
+    if trailer && chunk_type.is_some() {
+        fail("a chunk type cannot be given with --trailer");
+    }
+    let shares_only = chunk_type.is_none() && label.is_none() && !trailer;
+    let include_expired = args.is_present("include_expired");
+    let identity = args
+        .value_of("identity")
+        .map(|x| Identity::from_str(&fs::read_to_string(x).unwrap()).unwrap());
+    let mut payloads: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut shares: Vec<(String, Vec<Share>)> = Vec::new();
+    for path in paths {
+        let bytes = read_input(path).unwrap();
+        let carrier = if args.is_present("lenient") {
+            carrier::open_lenient(&bytes).unwrap()
+        } else {
+            carrier::open(&bytes).unwrap()
+        };
+        let blocks = if trailer {
+            vec![("trailer".to_string(), carrier.trailer())]
+        } else {
+            carrier.list()
+        };
+        for (chunk_label, data) in blocks {
+            if data.is_empty() || chunk_type.is_some_and(|x| x != chunk_label) {
+                continue;
+            }
+            let data = match recover_ecc(&chunk_label, data) {
+                Some(data) => data,
+                None => continue,
+            };
+            if !Share::is_share(&data) {
+                if !shares_only {
+                    payloads.push((chunk_label, data.into_owned()));
+                }
+                continue;
+            }
+            let share = match Share::try_from(&data[..]) {
+                Ok(share) => share,
+                Err(e) => {
+                    eprintln!("{}: {}", chunk_label, e);
+                    continue;
+                }
+            };
+            match shares.iter_mut().find(|(_, x)| x[0].id == share.id) {
+                Some((_, group)) => group.push(share),
+                None => shares.push((chunk_label, vec![share])),
+            }
+        }
+    }
+    if shares_only && shares.is_empty() {
+        fail("no secret shares found, give --chunk-type or --label to decode other messages");
+    }
+    let mut missing_shares = false;
+    for (chunk_label, group) in shares {
+        match shamir::combine(&group) {
+            Ok(secret) => payloads.push((chunk_label, secret)),
+            Err(e) => {
+                eprintln!("{}: {}", chunk_label, e);
+                missing_shares = true;
+            }
+        }
+    }
+
+    let now = message::now();
+    // Whether any encrypted payload was opened, and whether any could not be.
+    let (mut opened, mut unopened) = (false, false);
+    for (chunk_label, data) in payloads {
+        let data = if recipient::is_sealed(&data) {
+            let plaintext = match &identity {
+                Some(identity) => {
+                    recipient::open(&data, identity).map_err(|e| format!("{}: {}", chunk_label, e))
+                }
+                None => Err(format!(
+                    "{} is encrypted, use --identity to decrypt it",
+                    chunk_label
+                )),
+            };
+            match plaintext {
+                Ok(plaintext) => {
+                    opened = true;
+                    plaintext
+                }
+                Err(message) => {
+                    eprintln!("{}", message);
+                    unopened = true;
+                    continue;
+                }
+            }
+        } else {
+            data
+        };
+        match Message::try_from(&data[..]) {
+            Ok(envelope) => {
+                if label.is_some_and(|x| x != envelope.label)
+                    || (envelope.is_expired(now) && !include_expired)
+                {
+                    continue;
+                }
+                println!("{}", envelope);
+                println!("{}", String::from_utf8_lossy(&envelope.payload));
+            }
+            Err(_) if label.is_none() => println!("{}", String::from_utf8_lossy(&data)),
+            Err(_) => (),
+        }
+    }
+    if missing_shares || (unopened && !opened) {
+        std::process::exit(1);
+    }
+}
+
+/// Undoes `encode --ecc`, reporting corrected errors on stderr. Data without
+/// error correction is returned as is, and `None` if it is damaged beyond repair.
+fn recover_ecc<'a>(chunk_label: &str, data: &'a [u8]) -> Option<Cow<'a, [u8]>> {
+    if !ecc::is_protected(data) {
+        return Some(Cow::Borrowed(data));
+    }
+    match ecc::recover(data) {
+        Ok(recovered) => {
+            if recovered.corrected > 0 {
+                eprintln!(
+                    "{}: corrected {} symbol errors",
+                    chunk_label, recovered.corrected
+                );
+            }
+            Some(Cow::Owned(recovered.data))
+        }
+        Err(e) => {
+            eprintln!("{}: {}", chunk_label, e);
+            None
+        }
+    }
+}
+
+pub fn handle_list(args: &ArgMatches) {
     let file_path = args.value_of("file_path").unwrap();
-    let chunk_type = args.value_of("chunk_type").unwrap();
-    let mut png = Png::from_file(file_path).unwrap();
-    while let Some(chunk) = png.chunk_by_type(chunk_type) {
-        println!("{}", chunk);
-        png.remove_chunk(chunk_type).unwrap();
+    let carrier = carrier::open(&read_input(file_path).unwrap()).unwrap();
+    let now = message::now();
+    for (index, (chunk_type, data)) in carrier.list().into_iter().enumerate() {
+        let data = match recover_ecc(&chunk_type, data) {
+            Some(data) => data,
+            None => continue,
+        };
+        if recipient::is_sealed(&data) {
+            println!("#{} {} [encrypted]", index, chunk_type);
+            continue;
+        }
+        let envelope = match Message::try_from(&data[..]) {
+            Ok(envelope) => envelope,
+            Err(_) => continue,
+        };
+        println!(
+            "#{} {} {}{}",
+            index,
+            chunk_type,
+            envelope,
+            if envelope.is_expired(now) {
+                " [expired]"
+            } else {
+                ""
+            }
+        );
     }
 }
 
+pub fn handle_keygen(args: &ArgMatches) {
+    let output_path = args.value_of("output_file").unwrap();
+    let identity = Identity::generate();
+    write_private(output_path, &format!("{}\n", identity)).unwrap();
+    let public_path = format!("{}.pub", output_path);
+    fs::write(&public_path, format!("{}\n", identity.recipient())).unwrap();
+    println!("Private key: {}", output_path);
+    println!("Public key: {} ({})", public_path, identity.recipient());
+}
+
+/// Writes `contents` to a file only its owner can read.
+fn write_private(path: &str, contents: &str) -> Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)?.write_all(contents.as_bytes())?;
+    Ok(())
+}
+
 pub fn handle_remove(args: &ArgMatches) {
     let file_path = args.value_of("file_path").unwrap();
     let chunk_type = args.value_of("chunk_type").unwrap();
-    let mut png = Png::from_file(file_path).unwrap();
-    let removed = png.remove_chunk(chunk_type).unwrap();
-    println!("Removed: {}", removed);
-    png.to_file(file_path).unwrap();
+    let output_path = args.value_of("output_file").unwrap_or(file_path);
+    let bytes = read_input(file_path).unwrap();
+    let mut carrier = carrier::open(&bytes).unwrap();
+    let removed = carrier.remove(chunk_type).unwrap();
+    report(
+        output_path,
+        &format!("Removed: {} ({} bytes)", chunk_type, removed.len()),
+    );
+    write_edited(
+        args,
+        output_path,
+        &bytes,
+        carrier.as_bytes(),
+        "remove",
+        &[chunk_type],
+    )
+    .unwrap();
 }
 
-pub fn handle_print(args: &ArgMatches) {  
+pub fn handle_print(args: &ArgMatches) {
     let file_path = args.value_of("file_path").unwrap();
-    let png = Png::from_file(file_path).unwrap();
+    let png = read_png(file_path).unwrap();
     println!("{}", png);
+    if let Some(schema_path) = args.value_of("schema") {
+        let schema = Schema::from_file(schema_path).unwrap();
+        for (index, chunk) in png.chunks().iter().enumerate() {
+            match schema.decode(chunk) {
+                Ok(Some(fields)) => {
+                    let fields: Vec<String> = fields
+                        .iter()
+                        .map(|(name, value)| format!("{}={}", name, value))
+                        .collect();
+                    println!("#{} {}: {}", index, chunk.chunk_type(), fields.join(", "));
+                }
+                Ok(None) => (),
+                Err(e) => println!("#{} {}: decoding failed: {}", index, chunk.chunk_type(), e),
+            }
+        }
+    }
+    if !png.trailer().is_empty() {
+        println!(
+            "Trailing data: {}",
+            scan::describe_trailer(png.trailer(), png.kind().end_chunk())
+        );
+    }
+}
+
+pub fn handle_scan(args: &ArgMatches) {
+    let json = args.value_of("format") == Some("json");
+    let mut reports = Vec::new();
+    for file_path in args.values_of("file_path").unwrap() {
+        let bytes = match read_input(file_path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                eprintln!("{}: {}", file_path, e);
+                reports.push(serde_json::json!({ "file": file_path, "error": e.to_string() }));
+                continue;
+            }
+        };
+        let mut png = match Png::try_from(&bytes[..]) {
+            Ok(png) => png,
+            Err(e) => {
+                eprintln!("{}: {}", file_path, e);
+                reports.push(serde_json::json!({ "file": file_path, "error": e.to_string() }));
+                continue;
+            }
+        };
+        let scanned = scan::scan(&png);
+        if json {
+            reports.push(serde_json::json!({
+                "file": file_path,
+                "risk": scanned.risk,
+                "findings": scanned.findings,
+            }));
+        } else {
+            println!("{}: {} risk", file_path, scanned.risk);
+            for finding in &scanned.findings {
+                println!(
+                    "  [{}] {}: {}",
+                    finding.risk, finding.check, finding.message
+                );
+            }
+        }
+        if args.is_present("strip_trailing") && !png.trailer().is_empty() {
+            let stripped = png.strip_trailer();
+            write_edited(
+                args,
+                file_path,
+                &bytes,
+                png.as_bytes(),
+                "strip-trailing",
+                &[],
+            )
+            .unwrap();
+            eprintln!("{}: stripped {} trailing bytes", file_path, stripped.len());
+        }
+    }
+    if json {
+        println!("{}", serde_json::to_string_pretty(&reports).unwrap());
+    }
+}
+
+pub fn handle_history(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let png = read_png(file_path).unwrap();
+    let entries = provenance::history(&png).unwrap();
+    if entries.is_empty() {
+        println!("{} has no recorded history", file_path);
+        return;
+    }
+    for (index, entry) in entries.iter().enumerate() {
+        println!("#{} {}", index, entry);
+        println!("  previous: {}", entry.previous);
+        println!("  state:    {}", entry.state);
+    }
+    let problems = provenance::verify(&png).unwrap();
+    if problems.is_empty() {
+        println!("All {} hash links hold", entries.len());
+        return;
+    }
+    for problem in &problems {
+        println!("warning: {}", problem);
+    }
+    std::process::exit(1);
+}
+
+pub fn handle_optimize(args: &ArgMatches) {
+    let strip = args.is_present("strip");
+    for file_path in args.values_of("file_path").unwrap() {
+        let bytes = fs::read(file_path).unwrap();
+        let png = Png::try_from(&bytes[..]).unwrap();
+        let optimized = optimize::optimize(&png, strip).unwrap();
+        if optimized.bytes_saved() > 0 {
+            let after = optimized.png.as_bytes();
+            write_edited(args, file_path, &bytes, after, "optimize", &["IDAT"]).unwrap();
+            let method = match optimized.recompressed {
+                Some((strategy, level)) => format!("{} filter, level {}", strategy, level),
+                None => "image data kept".to_string(),
+            };
+            println!(
+                "{}: {} -> {} bytes, saved {} bytes ({})",
+                file_path,
+                optimized.original_size,
+                optimized.optimized_size,
+                optimized.bytes_saved(),
+                method
+            );
+        } else {
+            println!(
+                "{}: {} bytes, already optimal",
+                file_path, optimized.original_size
+            );
+        }
+    }
+}
+
+pub fn handle_reidat(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let output_path = args.value_of("output_file").unwrap_or(file_path);
+    let bytes = read_input(file_path).unwrap();
+    let mut png = Png::try_from(&bytes[..]).unwrap();
+    match args.value_of("max_chunk_size") {
+        Some(size) => png.split_idat(size.parse().unwrap()).unwrap(),
+        None => png.merge_idat(),
+    }
+    write_edited(
+        args,
+        output_path,
+        &bytes,
+        png.as_bytes(),
+        "reidat",
+        &["IDAT"],
+    )
+    .unwrap();
+}
+
+pub fn handle_inspect(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    // Parse leniently, so chunks with a broken CRC can be inspected too.
+    let png = Png::from_bytes_lenient(&read_input(file_path).unwrap()).unwrap();
+    let indices: Vec<usize> = match args.value_of("index") {
+        Some(index) => match index.parse() {
+            Ok(index) if index < png.chunks().len() => vec![index],
+            _ => fail(&format!("no chunk at index {}", index)),
+        },
+        None => {
+            let chunk_type = args.value_of("chunk_type").unwrap();
+            png.chunks()
+                .iter()
+                .enumerate()
+                .filter(|(_, x)| x.chunk_type().to_string() == chunk_type)
+                .map(|(i, _)| i)
+                .collect()
+        }
+    };
+    if indices.is_empty() {
+        fail("no matching chunk found");
+    }
+    for index in indices {
+        let chunk = &png.chunks()[index];
+        let offset = png.chunk_offset(index).unwrap();
+        let chunk_type = chunk.chunk_type();
+        println!(
+            "Chunk #{} {} at offset {} ({} bytes)",
+            index,
+            chunk_type,
+            offset,
+            chunk.as_bytes().len()
+        );
+        println!(
+            "  Critical: {}, Public: {}, Safe to copy: {}",
+            chunk_type.is_critical(),
+            chunk_type.is_public(),
+            chunk_type.is_safe_to_copy()
+        );
+        println!("  Stored CRC:   {:#010x}", chunk.crc());
+        println!("  Computed CRC: {:#010x}", chunk.calculate_crc());
+        print!("{}", hex::dump(&chunk.as_bytes(), offset));
+        match KnownChunk::decode(chunk) {
+            Ok(Some(known)) => println!("  Decoded: {}", known),
+            Ok(None) => (),
+            Err(e) => println!("  Decoding failed: {}", e),
+        }
+        println!();
+    }
+}
+
+pub fn handle_export(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let chunk_types: Vec<&str> = args.values_of("chunk_type").unwrap().collect();
+    let output_dir = Path::new(args.value_of("output_dir").unwrap());
+    let png = read_png(file_path).unwrap();
+    fs::create_dir_all(output_dir).unwrap();
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type().to_string();
+        if !chunk_types.contains(&&chunk_type[..]) {
+            continue;
+        }
+        let path = output_dir.join(format!("{}_{}.chunk", chunk_type, index));
+        fs::write(&path, chunk.as_bytes()).unwrap();
+        println!("Exported: {}", path.display());
+    }
+}
+
+/// Reads a chunk written by `export`. The file must hold exactly one chunk
+/// with a valid CRC.
+fn read_chunk_file<P: AsRef<Path>>(path: P) -> Result<Chunk> {
+    let bytes = fs::read(path)?;
+    let chunk = Chunk::try_from(&bytes[..])?;
+    if chunk.as_bytes().len() != bytes.len() {
+        return Err("chunk file has trailing data".into());
+    }
+    Ok(chunk)
+}
+
+pub fn handle_import(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let output_path = args.value_of("output_file").unwrap_or(file_path);
+    let bytes = read_input(file_path).unwrap();
+    let mut png = Png::try_from(&bytes[..]).unwrap();
+    let mut imported = Vec::new();
+    for chunk_file in args.values_of("chunk_files").unwrap() {
+        let chunk = read_chunk_file(chunk_file).unwrap();
+        if chunk.chunk_type().is_critical() {
+            eprintln!("Skipped: {} is a critical chunk", chunk.chunk_type());
+            continue;
+        }
+        let chunk_type = chunk.chunk_type().to_string();
+        let action = match png.replace_chunk(chunk) {
+            Ok(true) => "Replaced",
+            Ok(false) => "Imported",
+            Err(e) => {
+                eprintln!("Skipped: {}", e);
+                continue;
+            }
+        };
+        report(
+            output_path,
+            &format!("{}: {} from {}", action, chunk_type, chunk_file),
+        );
+        imported.push(chunk_type);
+    }
+    let imported: Vec<&str> = imported.iter().map(|x| &x[..]).collect();
+    write_edited(
+        args,
+        output_path,
+        &bytes,
+        png.as_bytes(),
+        "import",
+        &imported,
+    )
+    .unwrap();
+}
+
+pub fn handle_transplant(args: &ArgMatches) {
+    let source = read_png(args.value_of("source").unwrap()).unwrap();
+    let destination_path = args.value_of("destination").unwrap();
+    let output_path = args.value_of("output_file").unwrap_or(destination_path);
+    let chunk_types: Vec<&str> = args.values_of("chunk_types").unwrap().collect();
+    let bytes = read_input(destination_path).unwrap();
+    let mut destination = Png::try_from(&bytes[..]).unwrap();
+    for chunk in source.chunks() {
+        if !chunk_types.contains(&&chunk.chunk_type().to_string()[..]) {
+            continue;
+        }
+        if chunk.chunk_type().is_critical() {
+            eprintln!("Skipped: {} is a critical chunk", chunk.chunk_type());
+        } else {
+            let action = match destination.replace_chunk(chunk.clone()) {
+                Ok(true) => "Replaced",
+                Ok(false) => "Copied",
+                Err(e) => {
+                    eprintln!("Skipped: {}", e);
+                    continue;
+                }
+            };
+            report(output_path, &format!("{}: {}", action, chunk.chunk_type()));
+        }
+    }
+    write_edited(
+        args,
+        output_path,
+        &bytes,
+        destination.as_bytes(),
+        "transplant",
+        &chunk_types,
+    )
+    .unwrap();
+}
+
+pub fn handle_dump(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let manifest = Manifest::from_png(&read_png(file_path).unwrap());
+    let output = match args.value_of("format").unwrap() {
+        "yaml" => manifest.to_yaml().unwrap(),
+        _ => manifest.to_json().unwrap(),
+    };
+    println!("{}", output.trim_end());
+}
+
+pub fn handle_build(args: &ArgMatches) {
+    let manifest_path = args.value_of("manifest").unwrap();
+    let contents = fs::read_to_string(manifest_path).unwrap();
+    let manifest = if manifest_path.ends_with(".yaml") || manifest_path.ends_with(".yml") {
+        Manifest::from_yaml(&contents).unwrap()
+    } else {
+        Manifest::from_json(&contents).unwrap()
+    };
+    let png = manifest
+        .to_png()
+        .unwrap_or_else(|e| fail(&format!("{}: {}", manifest_path, e)));
+    if let Ok(violations) = color::violations(&png) {
+        for violation in violations {
+            eprintln!("warning: {}", violation);
+        }
+    }
+    write_output(args.value_of("output_file").unwrap(), &png.as_bytes()).unwrap();
+}
+
+pub fn handle_exif(args: &ArgMatches) {
+    match args.subcommand() {
+        Some(("get", get_args)) => handle_exif_get(get_args),
+        Some(("set", set_args)) => handle_exif_set(set_args),
+        Some(("strip", strip_args)) => handle_exif_strip(strip_args),
+        _ => (),
+    }
+}
+
+fn handle_exif_get(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let png = read_png(file_path).unwrap();
+    let exif = match png.exif().unwrap() {
+        Some(exif) => exif,
+        None => {
+            eprintln!("{} has no eXIf chunk", file_path);
+            return;
+        }
+    };
+    if let Some(tag) = args.value_of("tag") {
+        match exif.get(tag) {
+            Some(value) => println!("{}", value),
+            None => eprintln!("{} is not set", tag),
+        }
+        return;
+    }
+    if let Some(orientation) = exif.orientation() {
+        println!("Orientation: {}", orientation);
+    }
+    if let Some(date) = exif.capture_date() {
+        println!("Captured: {}", date);
+    }
+    if let Some((latitude, longitude)) = exif.gps() {
+        println!("Location: {:.6}, {:.6}", latitude, longitude);
+    }
+    for entry in &exif.entries {
+        println!("{} {}: {}", entry.group, entry.name(), entry.value);
+    }
+    if let Some(thumbnail) = &exif.thumbnail {
+        println!("Thumbnail: {} bytes", thumbnail.len());
+    }
+}
+
+fn handle_exif_set(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let output_path = args.value_of("output_file").unwrap_or(file_path);
+    let bytes = read_input(file_path).unwrap();
+    let mut png = Png::try_from(&bytes[..]).unwrap();
+    let mut exif = png.exif().unwrap().unwrap_or_default();
+    exif.set(
+        args.value_of("tag").unwrap(),
+        args.value_of("value").unwrap(),
+    )
+    .unwrap();
+    png.set_exif(&exif);
+    write_edited(
+        args,
+        output_path,
+        &bytes,
+        png.as_bytes(),
+        "exif set",
+        &["eXIf"],
+    )
+    .unwrap();
+}
+
+fn handle_exif_strip(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let bytes = read_input(file_path).unwrap();
+    let mut png = Png::try_from(&bytes[..]).unwrap();
+    let tags: Vec<&str> = args.values_of("tag").unwrap_or_default().collect();
+    let gps = args.is_present("gps");
+    let thumbnail = args.is_present("thumbnail");
+    if tags.is_empty() && !gps && !thumbnail {
+        if png.remove_chunk("eXIf").is_err() {
+            eprintln!("{} has no eXIf chunk", file_path);
+            return;
+        }
+    } else {
+        let mut exif = match png.exif().unwrap() {
+            Some(exif) => exif,
+            None => {
+                eprintln!("{} has no eXIf chunk", file_path);
+                return;
+            }
+        };
+        for tag in tags {
+            if exif.remove(tag).unwrap().is_none() {
+                eprintln!("{} is not set", tag);
+            }
+        }
+        if gps {
+            exif.strip_group(Group::Gps);
+        }
+        if thumbnail {
+            exif.strip_group(Group::Thumbnail);
+        }
+        png.set_exif(&exif);
+    }
+    write_edited(
+        args,
+        file_path,
+        &bytes,
+        png.as_bytes(),
+        "exif strip",
+        &["eXIf"],
+    )
+    .unwrap();
+}
+
+#[cfg(target_os = "linux")]
+pub fn handle_watch(args: &ArgMatches) {
+    use inotify::{Inotify, WatchMask};
+    use std::path::PathBuf;
+    use std::time::{Duration, Instant};
+
+    let dir = Path::new(args.value_of("dir").unwrap());
+    let pipeline = Pipeline::from_str(args.value_of("exec").unwrap()).unwrap();
+    let delay = Duration::from_millis(args.value_of("debounce").unwrap().parse().unwrap());
+    let log_path = match args.value_of("log") {
+        Some(path) => PathBuf::from(path),
+        None => dir.join("pngme-watch.log"),
+    };
+    let mut log = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(log_path)
+        .unwrap();
+    let mut inotify = Inotify::init().unwrap();
+    inotify
+        .watches()
+        .add(
+            dir,
+            WatchMask::MODIFY | WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO,
+        )
+        .unwrap();
+    let mut debouncer = Debouncer::new(delay);
+    let mut seen = Seen::default();
+    let mut buffer = [0; 4096];
+    println!("Watching {}", dir.display());
+    loop {
+        match inotify.read_events(&mut buffer) {
+            Ok(events) => {
+                for name in events.filter_map(|x| x.name) {
+                    let path = dir.join(name);
+                    if path
+                        .extension()
+                        .is_some_and(|x| x.eq_ignore_ascii_case("png"))
+                    {
+                        debouncer.touch(path, Instant::now());
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                std::thread::sleep(Duration::from_millis(50))
+            }
+            Err(e) => panic!("{}", e),
+        }
+        for path in debouncer.ready(Instant::now()) {
+            if let Some(outcome) = process_watched(args, &path, &pipeline, &mut seen) {
+                println!("{}", outcome);
+                writeln!(
+                    log,
+                    "{} {}",
+                    message::format_timestamp(message::now()),
+                    outcome
+                )
+                .unwrap();
+            }
+        }
+    }
+}
+
+/// Runs `pipeline` on `path` by starting this executable once per command,
+/// and describes the outcome for the log. Returns `None` for files that are
+/// gone or unchanged since they were last handled, which includes the writes
+/// made by the pipeline itself.
+#[cfg(target_os = "linux")]
+fn process_watched(
+    args: &ArgMatches,
+    path: &Path,
+    pipeline: &Pipeline,
+    seen: &mut Seen,
+) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    if !seen.is_new(path, &bytes) {
+        return None;
+    }
+    if let Err(e) = Png::try_from(&bytes[..]) {
+        return Some(format!("Skipped: {} ({})", path.display(), e));
+    }
+    let exe = std::env::current_exe().unwrap();
+    let mut outcome = format!("Processed: {}", path.display());
+    for command in pipeline.commands_for(&path.to_string_lossy()) {
+        let mut child = std::process::Command::new(&exe);
+        child.args(&command);
+        if args.is_present("provenance") && accepts_provenance(&command) {
+            child.arg("--provenance");
+        }
+        let status = child.status().unwrap();
+        if !status.success() {
+            outcome = format!(
+                "Failed: {} (`{}` {})",
+                path.display(),
+                command.join(" "),
+                status
+            );
+            break;
+        }
+    }
+    if let Ok(bytes) = fs::read(path) {
+        seen.remember(path, &bytes);
+    }
+    Some(outcome)
+}
+
+/// Returns true if the pngme subcommand `command` starts with has a
+/// `--provenance` flag.
+#[cfg(target_os = "linux")]
+fn accepts_provenance(command: &[String]) -> bool {
+    let app = app();
+    let mut current = &app;
+    for word in command {
+        match current.find_subcommand(word) {
+            Some(subcommand) => current = subcommand,
+            None => break,
+        }
+    }
+    let accepts = current.get_arguments().any(|x| x.get_id() == "provenance");
+    accepts
+}
+
+#[cfg(feature = "tui")]
+pub fn handle_edit(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    crate::tui::run(file_path, args.is_present("provenance")).unwrap();
+}
+
+pub fn handle_color(args: &ArgMatches) {
+    match args.subcommand() {
+        Some(("show", show_args)) => handle_color_show(show_args),
+        Some(("set-srgb", srgb_args)) => handle_color_set_srgb(srgb_args),
+        Some(("extract-icc", extract_args)) => handle_color_extract_icc(extract_args),
+        _ => (),
+    }
+}
+
+fn handle_color_show(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let png = read_png(file_path).unwrap();
+    match ColorInfo::from_png(&png) {
+        Ok(info) => println!("Color space: {}", info.effective()),
+        Err(e) => println!("Color space: unknown ({})", e),
+    }
+    for chunk in png.chunks() {
+        if color::COLOR_CHUNKS.contains(&&chunk.chunk_type().to_string()[..]) {
+            match KnownChunk::decode(chunk) {
+                Ok(Some(known)) => println!("{}: {}", chunk.chunk_type(), known),
+                _ => println!("{}: invalid", chunk.chunk_type()),
+            }
+        }
+    }
+    match color::violations(&png) {
+        Ok(violations) => {
+            for violation in violations {
+                println!("warning: {}", violation);
+            }
+        }
+        Err(e) => println!("warning: {}", e),
+    }
+}
+
+fn handle_color_set_srgb(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let output_path = args.value_of("output_file").unwrap_or(file_path);
+    let intent = RenderingIntent::from_str(args.value_of("intent").unwrap()).unwrap();
+    let bytes = read_input(file_path).unwrap();
+    let mut png = Png::try_from(&bytes[..]).unwrap();
+    color::set_srgb(&mut png, intent).unwrap();
+    write_edited(
+        args,
+        output_path,
+        &bytes,
+        png.as_bytes(),
+        "color set-srgb",
+        &["sRGB", "gAMA", "cHRM"],
+    )
+    .unwrap();
+}
+
+fn handle_color_extract_icc(args: &ArgMatches) {
+    let file_path = args.value_of("file_path").unwrap();
+    let png = read_png(file_path).unwrap();
+    match ColorInfo::from_png(&png).unwrap().icc {
+        Some(icc) => {
+            let output_path = args.value_of("output_file").unwrap();
+            write_output(output_path, &icc.profile).unwrap();
+            report(
+                output_path,
+                &format!("Saved {:?} ({} bytes)", icc.name, icc.profile.len()),
+            );
+        }
+        None => eprintln!("{} has no iCCP chunk", file_path),
+    }
+}
+
+/// Prints `message` on stderr and exits with a failure status.
+fn fail(message: &str) -> ! {
+    eprintln!("{}", message);
+    std::process::exit(1)
+}
+
+/// Reads `path`, or stdin if it is `-`.
+fn read_input(path: &str) -> Result<Vec<u8>> {
+    if path == "-" {
+        let mut bytes = Vec::new();
+        io::stdin().read_to_end(&mut bytes)?;
+        Ok(bytes)
+    } else {
+        Ok(fs::read(path)?)
+    }
+}
+
+fn read_png(path: &str) -> Result<Png> {
+    Png::try_from(&read_input(path)?[..])
+}
+
+/// Writes `bytes` to `path`, or stdout if it is `-`.
+fn write_output(path: &str, bytes: &[u8]) -> Result<()> {
+    if path == "-" {
+        let mut stdout = io::stdout().lock();
+        stdout.write_all(bytes)?;
+        stdout.flush()?;
+    } else {
+        fs::write(path, bytes)?;
+    }
+    Ok(())
+}
+
+/// Writes the edited file `after` like `write_output`. With `--provenance`, an
+/// entry for `operation` is added to its history first, linking it to `before`.
+/// Only PNG, MNG and JNG files have a history; others are written unchanged.
+fn write_edited(
+    args: &ArgMatches,
+    path: &str,
+    before: &[u8],
+    after: Vec<u8>,
+    operation: &str,
+    chunk_types: &[&str],
+) -> Result<()> {
+    if !args.is_present("provenance") {
+        return write_output(path, &after);
+    }
+    match (Png::try_from(before), Png::try_from(&after[..])) {
+        (Ok(before), Ok(mut after)) => {
+            provenance::record(&before, &mut after, operation, chunk_types)?;
+            write_output(path, &after.as_bytes())
+        }
+        _ => {
+            eprintln!(
+                "{}: history is only recorded for PNG, MNG and JNG files",
+                path
+            );
+            write_output(path, &after)
+        }
+    }
+}
+
+/// Prints a status line about writing `output_path`. It goes to stderr when the
+/// file itself goes to stdout, so the image stream stays clean.
+fn report(output_path: &str, message: &str) {
+    if output_path == "-" {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
 }
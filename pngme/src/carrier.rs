@@ -0,0 +1,248 @@
+use std::convert::TryFrom;
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::container::ContainerKind;
+use crate::png::Png;
+use crate::riff::{Riff, RiffChunk};
+use crate::Result;
+
+/// An image format that can hold labelled blocks of data next to the image
+/// itself. For chunk based formats the label is the chunk type.
+pub trait Carrier {
+    /// Stores `bytes` in a new block labelled `label`.
+    fn embed(&mut self, label: &str, bytes: &[u8]) -> Result<()>;
+
+    /// Returns the data of the first block labelled `label`.
+    fn extract(&self, label: &str) -> Result<Vec<u8>>;
+
+    /// Removes the first block labelled `label` and returns its data.
+    fn remove(&mut self, label: &str) -> Result<Vec<u8>>;
+
+    /// Lists the label and data of every block in file order.
+    fn list(&self) -> Vec<(String, &[u8])>;
+
+    /// Returns the bytes stored after the end of the image, if any.
+    fn trailer(&self) -> &[u8];
+
+    /// Stores `bytes` after the end of the image, behind any trailer already there.
+    fn append_trailer(&mut self, bytes: &[u8]) -> Result<()>;
+
+    /// Returns the whole file as a byte sequence.
+    fn as_bytes(&self) -> Vec<u8>;
+}
+
+/// The file formats a `Carrier` can be opened for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Png(ContainerKind),
+    WebP,
+}
+
+impl Format {
+    /// Detects the format of `bytes` from their magic bytes.
+    pub fn sniff(bytes: &[u8]) -> Option<Format> {
+        if let Some(kind) = ContainerKind::detect(bytes) {
+            Some(Format::Png(kind))
+        } else if Riff::is_webp(bytes) {
+            Some(Format::WebP)
+        } else {
+            None
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Format::Png(kind) => write!(f, "{}", kind),
+            Format::WebP => write!(f, "WebP"),
+        }
+    }
+}
+
+/// Opens `bytes` as whichever carrier format their magic bytes announce.
+pub fn open(bytes: &[u8]) -> Result<Box<dyn Carrier>> {
+    match Format::sniff(bytes) {
+        Some(Format::Png(_)) => Ok(Box::new(Png::try_from(bytes)?)),
+        Some(Format::WebP) => Ok(Box::new(Riff::try_from(bytes)?)),
+        None => Err("unsupported file format".into()),
+    }
+}
+
+/// Like `open`, but keeps PNG chunks whose CRC doesn't match their contents.
+/// RIFF chunks carry no checksum, so WebP files open as usual.
+pub fn open_lenient(bytes: &[u8]) -> Result<Box<dyn Carrier>> {
+    match Format::sniff(bytes) {
+        Some(Format::Png(_)) => Ok(Box::new(Png::from_bytes_lenient(bytes)?)),
+        _ => open(bytes),
+    }
+}
+
+/// Opens the file at `path`, see `open`.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Box<dyn Carrier>> {
+    open(&fs::read(path)?)
+}
+
+impl Carrier for Png {
+    fn embed(&mut self, label: &str, bytes: &[u8]) -> Result<()> {
+        let chunk_type = ChunkType::from_str(label)?;
+        self.insert_chunk(Chunk::new(chunk_type, bytes.to_vec()));
+        Ok(())
+    }
+
+    fn extract(&self, label: &str) -> Result<Vec<u8>> {
+        match self.chunk_by_type(label) {
+            Some(chunk) => Ok(chunk.data().to_vec()),
+            None => Err(format!("{} not found", label).into()),
+        }
+    }
+
+    fn remove(&mut self, label: &str) -> Result<Vec<u8>> {
+        Ok(self.remove_chunk(label)?.data().to_vec())
+    }
+
+    fn list(&self) -> Vec<(String, &[u8])> {
+        self.chunks()
+            .iter()
+            .map(|x| (x.chunk_type().to_string(), x.data()))
+            .collect()
+    }
+
+    fn trailer(&self) -> &[u8] {
+        Png::trailer(self)
+    }
+
+    fn append_trailer(&mut self, bytes: &[u8]) -> Result<()> {
+        let mut trailer = self.strip_trailer();
+        trailer.extend(bytes);
+        self.set_trailer(trailer);
+        Ok(())
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        Png::as_bytes(self)
+    }
+}
+
+impl Carrier for Riff {
+    fn embed(&mut self, label: &str, bytes: &[u8]) -> Result<()> {
+        self.append_chunk(RiffChunk::new(fourcc(label)?, bytes.to_vec()))
+    }
+
+    fn extract(&self, label: &str) -> Result<Vec<u8>> {
+        let fourcc = fourcc(label)?;
+        match self.chunks().iter().find(|x| x.fourcc() == &fourcc) {
+            Some(chunk) => Ok(chunk.data().to_vec()),
+            None => Err(format!("{} not found", label).into()),
+        }
+    }
+
+    fn remove(&mut self, label: &str) -> Result<Vec<u8>> {
+        Ok(self.remove_chunk(&fourcc(label)?)?.data().to_vec())
+    }
+
+    fn list(&self) -> Vec<(String, &[u8])> {
+        self.chunks()
+            .iter()
+            .map(|x| (x.label(), x.data()))
+            .collect()
+    }
+
+    fn trailer(&self) -> &[u8] {
+        &[]
+    }
+
+    fn append_trailer(&mut self, _bytes: &[u8]) -> Result<()> {
+        Err("trailing data is only supported for PNG files".into())
+    }
+
+    fn as_bytes(&self) -> Vec<u8> {
+        Riff::as_bytes(self)
+    }
+}
+
+/// Checks that `label` is a valid RIFF chunk identifier.
+fn fourcc(label: &str) -> Result<[u8; 4]> {
+    match <[u8; 4]>::try_from(label.as_bytes()) {
+        Ok(bytes) if bytes.iter().all(|x| x.is_ascii_graphic() || *x == b' ') => Ok(bytes),
+        _ => Err(format!("{:?} is not a four character code", label).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::minimal_png;
+
+    fn testing_files() -> Vec<Vec<u8>> {
+        let png = minimal_png();
+        let vp8l = [0x2f, 0x00, 0x00, 0x00, 0x00, 0x88, 0x88, 0x08, 0x00];
+        let webp = Riff::from_chunks(Riff::WEBP, vec![RiffChunk::new(*b"VP8L", vp8l.to_vec())]);
+        vec![png.as_bytes(), webp.as_bytes()]
+    }
+
+    #[test]
+    fn test_sniff() {
+        let files = testing_files();
+        assert_eq!(
+            Format::sniff(&files[0]),
+            Some(Format::Png(ContainerKind::Png))
+        );
+        assert_eq!(Format::sniff(&files[1]), Some(Format::WebP));
+        assert_eq!(Format::sniff(b"GIF89a"), None);
+        assert!(open(b"GIF89a").is_err());
+    }
+
+    #[test]
+    fn test_carrier_roundtrip() {
+        for bytes in testing_files() {
+            let mut carrier = open(&bytes).unwrap();
+            carrier.embed("ruSt", b"secret message").unwrap();
+            let mut carrier = open(&carrier.as_bytes()).unwrap();
+            assert_eq!(carrier.extract("ruSt").unwrap(), b"secret message");
+            assert!(carrier.list().iter().any(|(label, _)| label == "ruSt"));
+
+            assert_eq!(carrier.remove("ruSt").unwrap(), b"secret message");
+            assert!(carrier.extract("ruSt").is_err());
+            assert!(carrier.remove("ruSt").is_err());
+        }
+    }
+
+    #[test]
+    fn test_embed_before_end() {
+        let mut png = minimal_png();
+        png.embed("ruSt", b"secret message").unwrap();
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|x| x.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "ruSt", "IEND"]);
+    }
+
+    #[test]
+    fn test_trailer() {
+        let files = testing_files();
+        let mut png = open(&files[0]).unwrap();
+        png.append_trailer(b"PK\x03\x04").unwrap();
+        png.append_trailer(b" more").unwrap();
+        let png = open(&png.as_bytes()).unwrap();
+        assert_eq!(png.trailer(), b"PK\x03\x04 more");
+        assert!(open(&files[1]).unwrap().append_trailer(b"x").is_err());
+    }
+
+    #[test]
+    fn test_fourcc() {
+        assert_eq!(fourcc("VP8 ").unwrap(), *b"VP8 ");
+        assert!(fourcc("toolong").is_err());
+        assert!(fourcc("ab\0c").is_err());
+    }
+}
@@ -1,6 +1,7 @@
 use std::convert::TryFrom;
 use std::fmt;
-use std::io::{BufReader, Read};
+
+use crc::crc32::{self, Hasher32};
 
 use crate::chunk_type::ChunkType;
 use crate::{Error, Result};
@@ -15,15 +16,12 @@ pub struct Chunk {
 }
 
 impl Chunk {
+    /// The largest data length the spec allows, 2^31-1 bytes.
+    pub const MAX_LENGTH: u32 = (1 << 31) - 1;
+
     pub fn new(chunk_type: ChunkType, chunk_data: Vec<u8>) -> Chunk {
         let len = chunk_data.len() as u32;
-        let crc_bytes: Vec<u8> = chunk_type
-            .bytes()
-            .iter()
-            .chain(chunk_data.iter())
-            .cloned()
-            .collect();
-        let crc = crc::crc32::checksum_ieee(&crc_bytes);
+        let crc = Self::checksum(&chunk_type, &chunk_data);
 
         Self {
             length: len,
@@ -53,6 +51,19 @@ impl Chunk {
         self.crc
     }
 
+    /// Computes the CRC of the chunk type and data. This matches `crc` for every
+    /// chunk built through `new` or `try_from`.
+    pub fn calculate_crc(&self) -> u32 {
+        Self::checksum(&self.chunk_type, &self.chunk_data)
+    }
+
+    fn checksum(chunk_type: &ChunkType, chunk_data: &[u8]) -> u32 {
+        let mut digest = crc32::Digest::new(crc32::IEEE);
+        digest.write(&chunk_type.bytes());
+        digest.write(chunk_data);
+        digest.sum32()
+    }
+
     /// Returns the data stored in this chunk as a `String`. This function will return an error
     /// if the stored data is not valid UTF-8.
     pub fn data_as_string(&self) -> Result<String> {
@@ -81,41 +92,99 @@ impl TryFrom<&[u8]> for Chunk {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self> {
-        let mut reader = BufReader::new(bytes);
-        let mut buffer: [u8; 4] = [0, 0, 0, 0];
+        Ok(ChunkRef::try_from(bytes)?.to_chunk())
+    }
+}
 
-        reader.read_exact(&mut buffer)?;
-        let len = u32::from_be_bytes(buffer);
+/// A chunk borrowed from the bytes it was parsed from. Parsing and reading a
+/// `ChunkRef` never allocates; `to_chunk` makes an owned `Chunk` for editing.
+#[derive(Debug, Clone)]
+pub struct ChunkRef<'a> {
+    chunk_type: ChunkType,
+    data: &'a [u8],
+    crc: u32,
+}
 
-        reader.read_exact(&mut buffer)?;
-        let chunk_type = ChunkType::try_from(buffer)?;
+impl<'a> ChunkRef<'a> {
+    /// The length of the data portion of this chunk.
+    pub fn length(&self) -> u32 {
+        self.data.len() as u32
+    }
+
+    pub fn chunk_type(&self) -> &ChunkType {
+        &self.chunk_type
+    }
 
-        let mut buffer = vec![0u8; len as usize];
-        reader.read_exact(&mut buffer)?;
-        let data = buffer.clone();
+    /// The data of this chunk, borrowed from the input.
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
 
-        let mut buffer: [u8; 4] = [0, 0, 0, 0];
-        reader.read_exact(&mut buffer)?;
-        let provided_crc = u32::from_be_bytes(buffer);
+    pub fn crc(&self) -> u32 {
+        self.crc
+    }
 
-        let crc_bytes: Vec<u8> = bytes
-            .iter()
-            .cloned()
-            .skip(4)
-            .take(len as usize + 4)
-            .collect();
-        let calculated_crc = crc::crc32::checksum_ieee(&crc_bytes[..]);
+    /// The number of input bytes this chunk spans, including length, type and CRC.
+    pub fn size(&self) -> usize {
+        12 + self.data.len()
+    }
+
+    /// Copies this chunk into an owned `Chunk`.
+    pub fn to_chunk(&self) -> Chunk {
+        Chunk {
+            length: self.length(),
+            chunk_type: self.chunk_type.clone(),
+            chunk_data: self.data.to_vec(),
+            crc: self.crc,
+        }
+    }
 
-        if calculated_crc != provided_crc {
+    /// Returns true if the stored CRC matches the chunk type and data.
+    pub fn is_crc_valid(&self) -> bool {
+        Chunk::checksum(&self.chunk_type, self.data) == self.crc
+    }
+
+    /// Parses a chunk from the start of `bytes`. The declared length is checked
+    /// against the spec limit and the input before the data is looked at. A CRC
+    /// mismatch is only an error if `verify_crc` is set.
+    pub fn parse(bytes: &'a [u8], verify_crc: bool) -> Result<Self> {
+        if bytes.len() < 12 {
+            return Err("chunk is truncated".into());
+        }
+        let len = u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+        if len > Chunk::MAX_LENGTH {
+            return Err(format!("chunk length {} exceeds the maximum of 2^31-1", len).into());
+        }
+        let data_end = 8 + len as usize;
+        if bytes.len() < data_end + 4 {
+            return Err(format!(
+                "chunk declares {} bytes of data but the input is shorter",
+                len
+            )
+            .into());
+        }
+
+        let chunk_type = ChunkType::try_from([bytes[4], bytes[5], bytes[6], bytes[7]])?;
+        let data = &bytes[8..data_end];
+        let crc = &bytes[data_end..data_end + 4];
+        let chunk = Self {
+            chunk_type,
+            data,
+            crc: u32::from_be_bytes([crc[0], crc[1], crc[2], crc[3]]),
+        };
+        if verify_crc && !chunk.is_crc_valid() {
             return Err("provided crc is not valid".into());
         }
+        Ok(chunk)
+    }
+}
 
-        Ok(Self {
-            length: len,
-            chunk_type: chunk_type,
-            chunk_data: data,
-            crc: provided_crc,
-        })
+impl<'a> TryFrom<&'a [u8]> for ChunkRef<'a> {
+    type Error = Error;
+
+    /// Parses a chunk from the start of `bytes`, see `ChunkRef::parse`.
+    fn try_from(bytes: &'a [u8]) -> Result<Self> {
+        Self::parse(bytes, true)
     }
 }
 
@@ -179,6 +248,12 @@ mod tests {
         assert_eq!(chunk.crc(), 2882656334);
     }
 
+    #[test]
+    fn test_chunk_calculate_crc() {
+        let chunk = testing_chunk();
+        assert_eq!(chunk.calculate_crc(), chunk.crc());
+    }
+
     #[test]
     fn test_valid_chunk_from_bytes() {
         let data_length: u32 = 42;
@@ -227,6 +302,37 @@ mod tests {
         assert!(chunk.is_err());
     }
 
+    #[test]
+    fn test_chunk_ref_borrows() {
+        let bytes = testing_chunk().as_bytes();
+        let chunk_ref = ChunkRef::try_from(&bytes[..]).unwrap();
+        assert!(std::ptr::eq(chunk_ref.data(), &bytes[8..bytes.len() - 4]));
+        assert_eq!(chunk_ref.size(), bytes.len());
+        assert_eq!(chunk_ref.to_chunk().as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_chunk_ref_lenient() {
+        let mut bytes = testing_chunk().as_bytes();
+        bytes[10] ^= 1;
+        assert!(ChunkRef::try_from(&bytes[..]).is_err());
+        let chunk_ref = ChunkRef::parse(&bytes[..], false).unwrap();
+        assert!(!chunk_ref.is_crc_valid());
+        assert_eq!(chunk_ref.crc(), testing_chunk().crc());
+    }
+
+    #[test]
+    fn test_chunk_length_limits() {
+        let mut bytes = vec![0x80, 0, 0, 0];
+        bytes.extend(b"RuSt");
+        bytes.extend([0; 4]);
+        assert!(Chunk::try_from(&bytes[..]).is_err());
+
+        bytes[0] = 0x7f;
+        assert!(Chunk::try_from(&bytes[..]).is_err());
+        assert!(Chunk::try_from(&bytes[..6]).is_err());
+    }
+
     #[test]
     pub fn test_chunk_trait_impls() {
         let data_length: u32 = 42;
@@ -12,7 +12,7 @@ pub struct ChunkType {
 impl ChunkType {
     /// Returns the raw bytes contained in this chunk
     pub fn bytes(&self) -> [u8; 4] {
-        self.bytes.clone()
+        self.bytes
     }
 
     /// Returns true if the reserved byte is valid and all four bytes are represented by the characters A-Z or a-z.
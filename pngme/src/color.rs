@@ -0,0 +1,402 @@
+use std::convert::TryFrom;
+use std::fmt;
+use std::str::FromStr;
+
+use crate::known::KnownChunk;
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// The color space chunks, in the order they are described by the spec.
+pub const COLOR_CHUNKS: [&str; 4] = ["iCCP", "sRGB", "cHRM", "gAMA"];
+
+/// The gamma sRGB images should declare for decoders without sRGB support.
+pub const SRGB_GAMMA: u32 = 45_455;
+
+/// The chromaticities sRGB images should declare for decoders without sRGB support.
+pub const SRGB_CHROMATICITIES: Chromaticities = Chromaticities {
+    white_point: (31_270, 32_900),
+    red: (64_000, 33_000),
+    green: (30_000, 60_000),
+    blue: (15_000, 6_000),
+};
+
+/// How colors outside of the sRGB gamut are mapped, as stored in the `sRGB` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RenderingIntent {
+    Perceptual,
+    RelativeColorimetric,
+    Saturation,
+    AbsoluteColorimetric,
+}
+
+impl TryFrom<u8> for RenderingIntent {
+    type Error = Error;
+
+    fn try_from(value: u8) -> Result<Self> {
+        match value {
+            0 => Ok(RenderingIntent::Perceptual),
+            1 => Ok(RenderingIntent::RelativeColorimetric),
+            2 => Ok(RenderingIntent::Saturation),
+            3 => Ok(RenderingIntent::AbsoluteColorimetric),
+            _ => Err(format!("invalid rendering intent {}", value).into()),
+        }
+    }
+}
+
+impl FromStr for RenderingIntent {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "perceptual" => Ok(RenderingIntent::Perceptual),
+            "relative" => Ok(RenderingIntent::RelativeColorimetric),
+            "saturation" => Ok(RenderingIntent::Saturation),
+            "absolute" => Ok(RenderingIntent::AbsoluteColorimetric),
+            _ => Err(format!("unknown rendering intent {:?}", s).into()),
+        }
+    }
+}
+
+impl fmt::Display for RenderingIntent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            RenderingIntent::Perceptual => "perceptual",
+            RenderingIntent::RelativeColorimetric => "relative colorimetric",
+            RenderingIntent::Saturation => "saturation",
+            RenderingIntent::AbsoluteColorimetric => "absolute colorimetric",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// CIE x,y chromaticities in units of 1/100000, as stored in the `cHRM` chunk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Chromaticities {
+    pub white_point: (u32, u32),
+    pub red: (u32, u32),
+    pub green: (u32, u32),
+    pub blue: (u32, u32),
+}
+
+/// An embedded ICC profile, as stored in the `iCCP` chunk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IccProfile {
+    pub name: String,
+    pub profile: Vec<u8>,
+}
+
+impl IccProfile {
+    /// The data color space signature from the profile header, e.g. `RGB` or `GRAY`.
+    pub fn color_space(&self) -> Option<String> {
+        self.profile
+            .get(16..20)
+            .map(|x| String::from_utf8_lossy(x).trim_end().to_string())
+    }
+}
+
+/// The color space an image should be displayed in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColorSpace {
+    Icc(IccProfile),
+    Srgb(RenderingIntent),
+    Calibrated {
+        gamma: Option<u32>,
+        chromaticities: Option<Chromaticities>,
+    },
+    /// Without any color chunk, decoders usually assume sRGB.
+    Unspecified,
+}
+
+impl fmt::Display for ColorSpace {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ColorSpace::Icc(icc) => write!(
+                f,
+                "ICC profile {:?} ({} bytes, {})",
+                icc.name,
+                icc.profile.len(),
+                icc.color_space().unwrap_or_else(|| "unknown".to_string())
+            ),
+            ColorSpace::Srgb(intent) => write!(f, "sRGB, {} rendering intent", intent),
+            ColorSpace::Calibrated {
+                gamma,
+                chromaticities,
+            } => {
+                write!(f, "calibrated")?;
+                if let Some(gamma) = gamma {
+                    write!(f, ", gamma {:.5}", *gamma as f64 / 100_000.0)?;
+                }
+                if let Some(c) = chromaticities {
+                    write!(
+                        f,
+                        ", white ({}, {}), red ({}, {}), green ({}, {}), blue ({}, {})",
+                        c.white_point.0,
+                        c.white_point.1,
+                        c.red.0,
+                        c.red.1,
+                        c.green.0,
+                        c.green.1,
+                        c.blue.0,
+                        c.blue.1
+                    )?;
+                }
+                Ok(())
+            }
+            ColorSpace::Unspecified => write!(f, "unspecified"),
+        }
+    }
+}
+
+/// The color space chunks of a `Png`, decoded.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ColorInfo {
+    pub icc: Option<IccProfile>,
+    pub srgb: Option<RenderingIntent>,
+    pub chromaticities: Option<Chromaticities>,
+    pub gamma: Option<u32>,
+}
+
+impl ColorInfo {
+    /// Decodes the first of each color space chunk in `png`.
+    pub fn from_png(png: &Png) -> Result<ColorInfo> {
+        let mut info = ColorInfo::default();
+        let chunks = png
+            .chunks()
+            .iter()
+            .filter(|x| COLOR_CHUNKS.contains(&&x.chunk_type().to_string()[..]));
+        for chunk in chunks {
+            match KnownChunk::decode(chunk)? {
+                Some(KnownChunk::Iccp { name, profile }) if info.icc.is_none() => {
+                    info.icc = Some(IccProfile { name, profile })
+                }
+                Some(KnownChunk::Srgb(intent)) if info.srgb.is_none() => {
+                    info.srgb = Some(RenderingIntent::try_from(intent)?)
+                }
+                Some(KnownChunk::Chrm {
+                    white_point,
+                    red,
+                    green,
+                    blue,
+                }) if info.chromaticities.is_none() => {
+                    info.chromaticities = Some(Chromaticities {
+                        white_point,
+                        red,
+                        green,
+                        blue,
+                    })
+                }
+                Some(KnownChunk::Gama(gamma)) if info.gamma.is_none() => info.gamma = Some(gamma),
+                _ => (),
+            }
+        }
+        Ok(info)
+    }
+
+    /// The color space decoders use: an embedded profile takes precedence over
+    /// `sRGB`, which takes precedence over `cHRM` and `gAMA`.
+    pub fn effective(&self) -> ColorSpace {
+        if let Some(icc) = &self.icc {
+            ColorSpace::Icc(icc.clone())
+        } else if let Some(intent) = self.srgb {
+            ColorSpace::Srgb(intent)
+        } else if self.gamma.is_some() || self.chromaticities.is_some() {
+            ColorSpace::Calibrated {
+                gamma: self.gamma,
+                chromaticities: self.chromaticities,
+            }
+        } else {
+            ColorSpace::Unspecified
+        }
+    }
+}
+
+/// Lists every way the color space chunks of `png` break the rules of the spec:
+/// each may appear once, before `PLTE` and `IDAT`; `iCCP` and `sRGB` exclude each
+/// other; and an embedded profile must match the color type of the image.
+pub fn violations(png: &Png) -> Result<Vec<String>> {
+    let mut violations = Vec::new();
+    let positions = |chunk_type: &str| -> Vec<usize> {
+        png.chunks()
+            .iter()
+            .enumerate()
+            .filter(|(_, x)| x.chunk_type().to_string() == chunk_type)
+            .map(|(i, _)| i)
+            .collect()
+    };
+    let image_start = ["PLTE", "IDAT"]
+        .iter()
+        .flat_map(|x| positions(x).first().copied())
+        .min();
+    for chunk_type in COLOR_CHUNKS {
+        let found = positions(chunk_type);
+        if found.len() > 1 {
+            violations.push(format!("{} appears {} times", chunk_type, found.len()));
+        }
+        if image_start.is_some_and(|start| found.iter().any(|x| *x > start)) {
+            violations.push(format!("{} must come before PLTE and IDAT", chunk_type));
+        }
+    }
+    if !positions("iCCP").is_empty() && !positions("sRGB").is_empty() {
+        violations.push("iCCP and sRGB must not both be present".to_string());
+    }
+
+    let info = ColorInfo::from_png(png)?;
+    if let (Some(icc), Ok(ihdr)) = (&info.icc, png.ihdr()) {
+        let expected = if ihdr.color_type & 2 == 0 {
+            "GRAY"
+        } else {
+            "RGB"
+        };
+        match icc.color_space() {
+            Some(space) if space == expected => (),
+            space => violations.push(format!(
+                "ICC profile color space {} does not match a {} image",
+                space.unwrap_or_else(|| "unknown".to_string()),
+                expected
+            )),
+        }
+    }
+    Ok(violations)
+}
+
+/// Replaces every color space chunk of `png` with an `sRGB` chunk, along with the
+/// `gAMA` and `cHRM` values the spec recommends for decoders without sRGB support.
+pub fn set_srgb(png: &mut Png, intent: RenderingIntent) -> Result<()> {
+    for chunk_type in COLOR_CHUNKS {
+        while png.remove_chunk(chunk_type).is_ok() {}
+    }
+    png.insert_chunk(KnownChunk::Srgb(intent as u8).to_chunk()?);
+    png.insert_chunk(KnownChunk::Gama(SRGB_GAMMA).to_chunk()?);
+    let c = SRGB_CHROMATICITIES;
+    png.insert_chunk(
+        KnownChunk::Chrm {
+            white_point: c.white_point,
+            red: c.red,
+            green: c.green,
+            blue: c.blue,
+        }
+        .to_chunk()?,
+    );
+    Ok(())
+}
+
+/// Replaces the embedded profile of `png`, removing `sRGB` since the two
+/// exclude each other. `gAMA` and `cHRM` are kept as fallbacks.
+pub fn set_icc(png: &mut Png, icc: &IccProfile) -> Result<()> {
+    if icc.name.is_empty() || icc.name.len() > 79 {
+        return Err("profile name must be 1 to 79 bytes long".into());
+    }
+    let chunk = KnownChunk::Iccp {
+        name: icc.name.clone(),
+        profile: icc.profile.clone(),
+    }
+    .to_chunk()?;
+    for chunk_type in ["iCCP", "sRGB"] {
+        while png.remove_chunk(chunk_type).is_ok() {}
+    }
+    png.insert_chunk(chunk);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::chunk;
+    use crate::zlib;
+
+    fn testing_profile() -> IccProfile {
+        let mut profile = vec![0u8; 128];
+        profile[16..20].copy_from_slice(b"RGB ");
+        IccProfile {
+            name: "Display P3".to_string(),
+            profile,
+        }
+    }
+
+    fn testing_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 2, 0, 0, 0]),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("IDAT", &zlib::compress(&[0, 0, 0, 0], 9).unwrap()),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    #[test]
+    fn test_effective_color_space() {
+        let mut png = testing_png();
+        let info = ColorInfo::from_png(&png).unwrap();
+        assert_eq!(
+            info.effective(),
+            ColorSpace::Calibrated {
+                gamma: Some(45_455),
+                chromaticities: None
+            }
+        );
+
+        set_icc(&mut png, &testing_profile()).unwrap();
+        let info = ColorInfo::from_png(&png).unwrap();
+        assert_eq!(info.effective(), ColorSpace::Icc(testing_profile()));
+        assert_eq!(info.gamma, Some(45_455));
+        assert!(violations(&png).unwrap().is_empty());
+
+        assert_eq!(ColorInfo::default().effective(), ColorSpace::Unspecified);
+    }
+
+    #[test]
+    fn test_set_srgb() {
+        let mut png = testing_png();
+        set_icc(&mut png, &testing_profile()).unwrap();
+        set_srgb(&mut png, RenderingIntent::Perceptual).unwrap();
+        let info = ColorInfo::from_png(&png).unwrap();
+        assert_eq!(info.icc, None);
+        assert_eq!(
+            info.effective(),
+            ColorSpace::Srgb(RenderingIntent::Perceptual)
+        );
+        assert_eq!(info.chromaticities, Some(SRGB_CHROMATICITIES));
+        assert!(violations(&png).unwrap().is_empty());
+
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|x| x.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "sRGB", "gAMA", "cHRM", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_violations() {
+        let mut png = testing_png();
+        png.insert_chunk(chunk("sRGB", &[0]));
+        png.insert_chunk(
+            KnownChunk::Iccp {
+                name: "Gray".to_string(),
+                profile: b"0000000000000000GRAY".to_vec(),
+            }
+            .to_chunk()
+            .unwrap(),
+        );
+        png.append_chunk(chunk("gAMA", &[0, 0, 177, 143]));
+        let violations = violations(&png).unwrap();
+        assert_eq!(
+            violations,
+            vec![
+                "gAMA appears 2 times",
+                "gAMA must come before PLTE and IDAT",
+                "iCCP and sRGB must not both be present",
+                "ICC profile color space GRAY does not match a RGB image",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_set_icc_name() {
+        let mut png = testing_png();
+        let icc = IccProfile {
+            name: String::new(),
+            ..testing_profile()
+        };
+        assert!(set_icc(&mut png, &icc).is_err());
+    }
+}
@@ -0,0 +1,93 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Error, Result};
+
+/// The members of the PNG family. They share the same chunk framing and only
+/// differ in their 8 byte signature and the chunks they expect.
+/// http://www.libpng.org/pub/mng/spec/
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContainerKind {
+    #[default]
+    Png,
+    Mng,
+    Jng,
+}
+
+impl ContainerKind {
+    pub const ALL: [ContainerKind; 3] =
+        [ContainerKind::Png, ContainerKind::Mng, ContainerKind::Jng];
+
+    /// The signature every file of this kind starts with.
+    pub const fn signature(&self) -> [u8; 8] {
+        match self {
+            ContainerKind::Png => [137, 80, 78, 71, 13, 10, 26, 10],
+            ContainerKind::Mng => [138, 77, 78, 71, 13, 10, 26, 10],
+            ContainerKind::Jng => [139, 74, 78, 71, 13, 10, 26, 10],
+        }
+    }
+
+    /// The chunk type that ends a file of this kind.
+    pub fn end_chunk(&self) -> &'static str {
+        match self {
+            ContainerKind::Mng => "MEND",
+            ContainerKind::Png | ContainerKind::Jng => "IEND",
+        }
+    }
+
+    /// Detects the kind of file `bytes` holds from its signature.
+    pub fn detect(bytes: &[u8]) -> Option<ContainerKind> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|x| bytes.starts_with(&x.signature()))
+    }
+}
+
+impl TryFrom<[u8; 8]> for ContainerKind {
+    type Error = Error;
+
+    fn try_from(header: [u8; 8]) -> Result<Self> {
+        Self::detect(&header).ok_or_else(|| "invalid header provided".into())
+    }
+}
+
+impl fmt::Display for ContainerKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            ContainerKind::Png => "PNG",
+            ContainerKind::Mng => "MNG",
+            ContainerKind::Jng => "JNG",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(
+            ContainerKind::detect(b"\x8aMNG\r\n\x1a\n\0\0\0\x1cMHDR"),
+            Some(ContainerKind::Mng)
+        );
+        assert_eq!(
+            ContainerKind::detect(b"\x8bJNG\r\n\x1a\n"),
+            Some(ContainerKind::Jng)
+        );
+        assert_eq!(ContainerKind::detect(b"GIF89a"), None);
+    }
+
+    #[test]
+    fn test_signature_roundtrip() {
+        for kind in ContainerKind::ALL.iter() {
+            assert_eq!(ContainerKind::try_from(kind.signature()).unwrap(), *kind);
+        }
+        assert!(ContainerKind::try_from([13, 80, 78, 71, 13, 10, 26, 10]).is_err());
+    }
+}
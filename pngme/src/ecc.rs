@@ -0,0 +1,364 @@
+use std::str::FromStr;
+
+use crate::{Error, Result};
+
+const MAGIC: [u8; 4] = *b"PFEC";
+const VERSION: u8 = 1;
+const HEADER_PARITY: usize = 8;
+const HEADER_LENGTH: usize = MAGIC.len() + 6 + HEADER_PARITY;
+
+/// Reed-Solomon parity over GF(2^8), added to chunk data so that it survives
+/// tools that damage bytes. Data is split into blocks of at most 255 bytes
+/// including parity, and each block corrects up to half as many damaged bytes
+/// as it has parity bytes. The blocks are interleaved so a burst of damage is
+/// spread over all of them.
+///
+/// The protected data holds the following fields in order:
+/// 1. The magic bytes `PFEC` *(4 bytes)*
+/// 2. The format version, parity bytes per block and data length, followed by
+///    8 parity bytes of their own *(14 bytes)*
+/// 3. The interleaved blocks *(remaining bytes)*
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ecc {
+    parity: u8,
+}
+
+/// Data recovered from protected bytes.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Recovered {
+    pub data: Vec<u8>,
+    /// The number of damaged bytes that were corrected.
+    pub corrected: usize,
+}
+
+impl Ecc {
+    /// Adds `parity` bytes to every block of 255 bytes. Blocks hold at least one
+    /// data byte, and parity must be at least 2 to correct anything.
+    pub fn new(parity: u8) -> Result<Ecc> {
+        if !(2..=254).contains(&parity) {
+            return Err("parity must be between 2 and 254 bytes per block".into());
+        }
+        Ok(Self { parity })
+    }
+
+    pub fn parity(&self) -> u8 {
+        self.parity
+    }
+
+    /// Returns `data` with parity added, in the layout described above.
+    pub fn protect(&self, data: &[u8]) -> Result<Vec<u8>> {
+        let len = u32::try_from(data.len()).map_err(|_| "data is too long to protect")?;
+        let mut bytes = MAGIC.to_vec();
+        let mut header = vec![VERSION, self.parity];
+        header.extend(len.to_be_bytes());
+        bytes.extend(rs::encode(&header, HEADER_PARITY));
+
+        let nsym = self.parity as usize;
+        let mut pos = 0;
+        let blocks: Vec<Vec<u8>> = block_lengths(data.len(), nsym)
+            .into_iter()
+            .map(|x| {
+                pos += x;
+                rs::encode(&data[pos - x..pos], nsym)
+            })
+            .collect();
+        let longest = blocks.iter().map(|x| x.len()).max().unwrap_or(0);
+        for i in 0..longest {
+            bytes.extend(blocks.iter().filter_map(|x| x.get(i)));
+        }
+        Ok(bytes)
+    }
+}
+
+/// Parses a share of parity as a percentage of the protected data, such as `20%`.
+impl FromStr for Ecc {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let percent: f64 = s.trim().trim_end_matches('%').parse()?;
+        if !(percent > 0.0 && percent < 100.0) {
+            return Err(format!("{:?} is not a percentage between 0 and 100", s).into());
+        }
+        Self::new((255.0 * percent / 100.0).round().clamp(2.0, 254.0) as u8)
+    }
+}
+
+/// Returns true if `bytes` look like protected data. A single damaged byte in
+/// the magic is tolerated.
+pub fn is_protected(bytes: &[u8]) -> bool {
+    bytes.len() >= HEADER_LENGTH && bytes.iter().zip(MAGIC).filter(|(a, b)| **a == *b).count() >= 3
+}
+
+/// Corrects what damage it can and returns the original data, see `Ecc`.
+pub fn recover(bytes: &[u8]) -> Result<Recovered> {
+    if !is_protected(bytes) {
+        return Err("data has no error correction".into());
+    }
+    let mut header = bytes[MAGIC.len()..HEADER_LENGTH].to_vec();
+    let mut corrected = rs::correct(&mut header, HEADER_PARITY)?;
+    if header[0] != VERSION {
+        return Err(format!("unsupported error correction version {}", header[0]).into());
+    }
+    let nsym = header[1] as usize;
+    let len = u32::from_be_bytes([header[2], header[3], header[4], header[5]]) as usize;
+    if !(2..=254).contains(&nsym) || len > bytes.len() {
+        return Err("error correction header is invalid".into());
+    }
+
+    let lengths = block_lengths(len, nsym);
+    let stream = &bytes[HEADER_LENGTH..];
+    if stream.len() != len + lengths.len() * nsym {
+        return Err("protected data is truncated".into());
+    }
+    let mut blocks: Vec<Vec<u8>> = lengths
+        .iter()
+        .map(|x| Vec::with_capacity(x + nsym))
+        .collect();
+    let mut stream = stream.iter();
+    let longest = lengths.iter().max().unwrap_or(&0) + nsym;
+    for i in 0..longest {
+        for (block, block_len) in blocks.iter_mut().zip(&lengths) {
+            if i < block_len + nsym {
+                block.extend(stream.next());
+            }
+        }
+    }
+
+    let mut data = Vec::with_capacity(len);
+    for mut block in blocks {
+        corrected += rs::correct(&mut block, nsym)?;
+        data.extend(&block[..block.len() - nsym]);
+    }
+    Ok(Recovered { data, corrected })
+}
+
+/// Splits `len` data bytes into as few blocks as fit next to `nsym` parity
+/// bytes, spreading the data evenly.
+fn block_lengths(len: usize, nsym: usize) -> Vec<usize> {
+    let count = len.div_ceil(255 - nsym).max(1);
+    (0..count)
+        .map(|i| len / count + usize::from(i < len % count))
+        .collect()
+}
+
+/// Reed-Solomon coding following "Reed-Solomon codes for coders" on Wikiversity. Polynomials are stored with
+/// the highest degree coefficient first.
+mod rs {
+    use crate::gf256::{alpha, inverse, mul};
+    use crate::Result;
+
+    fn scale(p: &[u8], x: u8) -> Vec<u8> {
+        p.iter().map(|c| mul(*c, x)).collect()
+    }
+
+    fn add(p: &[u8], q: &[u8]) -> Vec<u8> {
+        let len = p.len().max(q.len());
+        let mut r = vec![0; len];
+        for (i, c) in p.iter().enumerate() {
+            r[i + len - p.len()] = *c;
+        }
+        for (i, c) in q.iter().enumerate() {
+            r[i + len - q.len()] ^= *c;
+        }
+        r
+    }
+
+    fn multiply(p: &[u8], q: &[u8]) -> Vec<u8> {
+        let mut r = vec![0; p.len() + q.len() - 1];
+        for (j, b) in q.iter().enumerate() {
+            for (i, a) in p.iter().enumerate() {
+                r[i + j] ^= mul(*a, *b);
+            }
+        }
+        r
+    }
+
+    fn eval(p: &[u8], x: u8) -> u8 {
+        p.iter().fold(0, |y, c| mul(y, x) ^ c)
+    }
+
+    /// Returns `data` followed by `nsym` parity bytes.
+    pub fn encode(data: &[u8], nsym: usize) -> Vec<u8> {
+        let generator = (0..nsym).fold(vec![1], |g, i| multiply(&g, &[1, alpha(i)]));
+        let mut out = data.to_vec();
+        out.resize(data.len() + nsym, 0);
+        for i in 0..data.len() {
+            let coef = out[i];
+            if coef != 0 {
+                for (j, g) in generator.iter().enumerate().skip(1) {
+                    out[i + j] ^= mul(*g, coef);
+                }
+            }
+        }
+        out[..data.len()].copy_from_slice(data);
+        out
+    }
+
+    /// The syndromes of `codeword`, after a leading zero.
+    fn syndromes(codeword: &[u8], nsym: usize) -> Vec<u8> {
+        let mut synd = vec![0];
+        synd.extend((0..nsym).map(|i| eval(codeword, alpha(i))));
+        synd
+    }
+
+    /// Finds the error locator polynomial with Berlekamp-Massey.
+    fn error_locator(synd: &[u8], nsym: usize) -> Result<Vec<u8>> {
+        let mut err_loc = vec![1];
+        let mut old_loc = vec![1];
+        for k in 1..=nsym {
+            let mut delta = synd[k];
+            for j in 1..err_loc.len() {
+                delta ^= mul(err_loc[err_loc.len() - 1 - j], synd[k - j]);
+            }
+            old_loc.push(0);
+            if delta != 0 {
+                if old_loc.len() > err_loc.len() {
+                    let new_loc = scale(&old_loc, delta);
+                    old_loc = scale(&err_loc, inverse(delta));
+                    err_loc = new_loc;
+                }
+                err_loc = add(&err_loc, &scale(&old_loc, delta));
+            }
+        }
+        let start = err_loc
+            .iter()
+            .position(|x| *x != 0)
+            .unwrap_or(err_loc.len());
+        err_loc.drain(..start);
+        if err_loc.is_empty() || (err_loc.len() - 1) * 2 > nsym {
+            return Err("too many errors to correct".into());
+        }
+        Ok(err_loc)
+    }
+
+    /// Finds the positions of the errors with a Chien search.
+    fn error_positions(err_loc: &[u8], len: usize) -> Result<Vec<usize>> {
+        let reversed: Vec<u8> = err_loc.iter().rev().copied().collect();
+        let positions: Vec<usize> = (0..len)
+            .filter(|i| eval(&reversed, alpha(*i)) == 0)
+            .map(|i| len - 1 - i)
+            .collect();
+        if positions.len() != err_loc.len() - 1 {
+            return Err("too many errors to correct".into());
+        }
+        Ok(positions)
+    }
+
+    /// Computes the error magnitudes with Forney's algorithm and applies them.
+    fn correct_errata(codeword: &mut [u8], synd: &[u8], positions: &[usize]) -> Result<()> {
+        let coef_pos: Vec<usize> = positions.iter().map(|p| codeword.len() - 1 - p).collect();
+        let locator = coef_pos
+            .iter()
+            .fold(vec![1], |l, i| multiply(&l, &add(&[1], &[alpha(*i), 0])));
+        let reversed: Vec<u8> = synd.iter().rev().copied().collect();
+        let product = multiply(&reversed, &locator);
+        let evaluator = &product[product.len() - locator.len()..];
+
+        let x: Vec<u8> = coef_pos.iter().map(|i| alpha(*i)).collect();
+        for (i, xi) in x.iter().enumerate() {
+            let xi_inv = inverse(*xi);
+            let locator_prime = x
+                .iter()
+                .enumerate()
+                .filter(|(j, _)| *j != i)
+                .fold(1, |acc, (_, xj)| mul(acc, 1 ^ mul(xi_inv, *xj)));
+            if locator_prime == 0 {
+                return Err("too many errors to correct".into());
+            }
+            let y = mul(*xi, eval(evaluator, xi_inv));
+            codeword[positions[i]] ^= mul(y, inverse(locator_prime));
+        }
+        Ok(())
+    }
+
+    /// Corrects `codeword` in place and returns how many bytes were wrong.
+    pub fn correct(codeword: &mut [u8], nsym: usize) -> Result<usize> {
+        let synd = syndromes(codeword, nsym);
+        if synd.iter().all(|x| *x == 0) {
+            return Ok(0);
+        }
+        let err_loc = error_locator(&synd, nsym)?;
+        let positions = error_positions(&err_loc, codeword.len())?;
+        correct_errata(codeword, &synd, &positions)?;
+        if syndromes(codeword, nsym).iter().any(|x| *x != 0) {
+            return Err("too many errors to correct".into());
+        }
+        Ok(positions.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_data() -> Vec<u8> {
+        (0..1000u32).map(|x| (x * 7 + x / 13) as u8).collect()
+    }
+
+    #[test]
+    fn test_rs_correct() {
+        let data = b"This is where your secret message will be!";
+        let encoded = rs::encode(data, 10);
+        assert_eq!(&encoded[..data.len()], data);
+
+        let mut damaged = encoded.clone();
+        for pos in [0, 7, 20, 41, 50] {
+            damaged[pos] ^= 0x5a;
+        }
+        assert_eq!(rs::correct(&mut damaged, 10).unwrap(), 5);
+        assert_eq!(damaged, encoded);
+
+        let mut damaged = encoded.clone();
+        for pos in [0, 3, 7, 20, 41, 50] {
+            damaged[pos] ^= 0x5a;
+        }
+        assert!(rs::correct(&mut damaged, 10).is_err());
+    }
+
+    #[test]
+    fn test_protect_recover() {
+        let data = testing_data();
+        let ecc = Ecc::from_str("20%").unwrap();
+        assert_eq!(ecc.parity(), 51);
+        let protected = ecc.protect(&data).unwrap();
+        assert!(is_protected(&protected));
+        assert_eq!(
+            recover(&protected).unwrap(),
+            Recovered {
+                data: data.clone(),
+                corrected: 0
+            }
+        );
+
+        // A burst is spread over the interleaved blocks.
+        let mut damaged = protected.clone();
+        for byte in &mut damaged[HEADER_LENGTH + 100..HEADER_LENGTH + 180] {
+            *byte = !*byte;
+        }
+        damaged[1] ^= 1;
+        damaged[6] ^= 1;
+        let recovered = recover(&damaged).unwrap();
+        assert_eq!(recovered.data, data);
+        assert_eq!(recovered.corrected, 81);
+
+        let mut damaged = protected.clone();
+        for byte in &mut damaged[HEADER_LENGTH..HEADER_LENGTH + 300] {
+            *byte = !*byte;
+        }
+        assert!(recover(&damaged).is_err());
+        assert!(recover(&protected[..protected.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_small_data() {
+        let ecc = Ecc::new(4).unwrap();
+        for data in [&b""[..], b"x"] {
+            let protected = ecc.protect(data).unwrap();
+            assert_eq!(recover(&protected).unwrap().data, data);
+        }
+        assert!(!is_protected(b"PMSG"));
+        assert!(Ecc::new(1).is_err());
+        assert!(Ecc::from_str("100%").is_err());
+        assert!(Ecc::from_str("abc").is_err());
+    }
+}
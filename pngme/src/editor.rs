@@ -0,0 +1,254 @@
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::known::KnownChunk;
+use crate::png::Png;
+use crate::provenance;
+use crate::Result;
+
+/// The state of an interactive editing session: a `Png`, the selected chunk and
+/// the chunk types changed since the last save. Drawing it is left to the caller.
+pub struct Editor {
+    png: Png,
+    selected: usize,
+    touched: Vec<String>,
+}
+
+impl Editor {
+    pub fn new(png: Png) -> Editor {
+        Self {
+            png,
+            selected: 0,
+            touched: Vec::new(),
+        }
+    }
+
+    pub fn png(&self) -> &Png {
+        &self.png
+    }
+
+    /// Index of the selected chunk.
+    pub fn selected(&self) -> usize {
+        self.selected
+    }
+
+    pub fn selected_chunk(&self) -> Option<&Chunk> {
+        self.png.chunks().get(self.selected)
+    }
+
+    /// Returns true if the `Png` changed since it was loaded or last saved.
+    pub fn is_modified(&self) -> bool {
+        !self.touched.is_empty()
+    }
+
+    /// Remembers the type of the chunk at `index` as changed in this session.
+    fn touch(&mut self, index: usize) {
+        let chunk_type = self.png.chunks()[index].chunk_type().to_string();
+        if !self.touched.contains(&chunk_type) {
+            self.touched.push(chunk_type);
+        }
+    }
+
+    pub fn select_next(&mut self) {
+        if self.selected + 1 < self.png.chunks().len() {
+            self.selected += 1;
+        }
+    }
+
+    pub fn select_previous(&mut self) {
+        self.selected = self.selected.saturating_sub(1);
+    }
+
+    /// Removes the selected chunk and selects the one that took its place.
+    pub fn delete(&mut self) -> Option<Chunk> {
+        if self.selected >= self.png.chunks().len() {
+            return None;
+        }
+        self.touch(self.selected);
+        let removed = self.png.chunks_mut().remove(self.selected);
+        let len = self.png.chunks().len();
+        if self.selected >= len {
+            self.selected = len.saturating_sub(1);
+        }
+        Some(removed)
+    }
+
+    /// Swaps the selected chunk with the one before it, keeping it selected.
+    pub fn move_up(&mut self) {
+        if self.selected > 0 && self.selected < self.png.chunks().len() {
+            self.touch(self.selected);
+            self.touch(self.selected - 1);
+            self.png.chunks_mut().swap(self.selected, self.selected - 1);
+            self.selected -= 1;
+        }
+    }
+
+    /// Swaps the selected chunk with the one after it, keeping it selected.
+    pub fn move_down(&mut self) {
+        if self.selected + 1 < self.png.chunks().len() {
+            self.touch(self.selected);
+            self.touch(self.selected + 1);
+            self.png.chunks_mut().swap(self.selected, self.selected + 1);
+            self.selected += 1;
+        }
+    }
+
+    /// The text of the selected chunk, if it is a `tEXt`, `zTXt` or `iTXt` chunk.
+    pub fn text(&self) -> Option<String> {
+        match KnownChunk::decode(self.selected_chunk()?) {
+            Ok(Some(KnownChunk::Text { text, .. }))
+            | Ok(Some(KnownChunk::ZText { text, .. }))
+            | Ok(Some(KnownChunk::IText { text, .. })) => Some(text),
+            _ => None,
+        }
+    }
+
+    /// Replaces the text of the selected text chunk, keeping its keyword.
+    pub fn set_text(&mut self, new_text: &str) -> Result<()> {
+        let chunk = self.selected_chunk().ok_or("no chunk selected")?;
+        let mut known = match KnownChunk::decode(chunk)? {
+            Some(known) => known,
+            None => return Err(format!("{} is not a text chunk", chunk.chunk_type()).into()),
+        };
+        match &mut known {
+            KnownChunk::Text { text, .. }
+            | KnownChunk::ZText { text, .. }
+            | KnownChunk::IText { text, .. } => *text = new_text.to_string(),
+            _ => return Err(format!("{} is not a text chunk", chunk.chunk_type()).into()),
+        }
+        let index = self.selected;
+        self.png.chunks_mut()[index] = known.to_chunk()?;
+        self.touch(index);
+        Ok(())
+    }
+
+    /// Adds a chunk where the spec allows it, see `Png::insert_chunk`, and selects it.
+    pub fn add(&mut self, chunk_type: &str, data: &[u8]) -> Result<()> {
+        let chunk = Chunk::new(ChunkType::from_str(chunk_type)?, data.to_vec());
+        let before = self.png.chunks().len();
+        self.png.insert_chunk(chunk);
+        self.selected = self
+            .png
+            .chunks()
+            .iter()
+            .rposition(|x| x.chunk_type().to_string() == chunk_type)
+            .unwrap_or(before);
+        self.touch(self.selected);
+        Ok(())
+    }
+
+    /// Adds an entry for this session to the history of the `Png`, linking it
+    /// to `before`, the file as it was on disk, and listing the chunk types
+    /// changed since the last save. See `provenance::record`.
+    pub fn record_history(&mut self, before: &Png) -> Result<()> {
+        let touched: Vec<&str> = self.touched.iter().map(|x| x.as_str()).collect();
+        provenance::record(before, &mut self.png, "edit", &touched)
+    }
+
+    /// Writes the `Png` to `path`. Chunks are rebuilt with `Chunk::new`, so their
+    /// CRCs always match their contents.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn save<P: AsRef<Path>>(&mut self, path: P) -> Result<()> {
+        self.png.to_file(path)?;
+        self.touched.clear();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::chunk;
+
+    fn testing_editor() -> Editor {
+        Editor::new(Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("tEXt", b"Title\0Dice"),
+            chunk("IDAT", &[]),
+            chunk("IEND", &[]),
+        ]))
+    }
+
+    fn types(editor: &Editor) -> Vec<String> {
+        editor
+            .png()
+            .chunks()
+            .iter()
+            .map(|x| x.chunk_type().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_select() {
+        let mut editor = testing_editor();
+        editor.select_previous();
+        assert_eq!(editor.selected(), 0);
+        for _ in 0..10 {
+            editor.select_next();
+        }
+        assert_eq!(editor.selected(), 3);
+        assert!(!editor.is_modified());
+    }
+
+    #[test]
+    fn test_reorder_and_delete() {
+        let mut editor = testing_editor();
+        editor.select_next();
+        editor.move_down();
+        assert_eq!(editor.selected(), 2);
+        assert_eq!(types(&editor), vec!["IHDR", "IDAT", "tEXt", "IEND"]);
+        editor.move_up();
+        editor.move_up();
+        assert_eq!(types(&editor), vec!["tEXt", "IHDR", "IDAT", "IEND"]);
+
+        editor.select_next();
+        editor.select_next();
+        editor.select_next();
+        assert_eq!(editor.delete().unwrap().chunk_type().to_string(), "IEND");
+        assert_eq!(editor.selected(), 2);
+        assert!(editor.is_modified());
+        assert_eq!(editor.touched, vec!["tEXt", "IDAT", "IHDR", "IEND"]);
+    }
+
+    #[test]
+    fn test_edit_text() {
+        let mut editor = testing_editor();
+        assert_eq!(editor.text(), None);
+        assert!(editor.set_text("nope").is_err());
+
+        editor.select_next();
+        assert_eq!(editor.text().as_deref(), Some("Dice"));
+        editor.set_text("Edited").unwrap();
+        let chunk = editor.selected_chunk().unwrap();
+        assert_eq!(chunk.data(), b"Title\0Edited");
+        assert_eq!(chunk.crc(), chunk.calculate_crc());
+    }
+
+    #[test]
+    fn test_add() {
+        let mut editor = testing_editor();
+        editor.add("ruSt", b"secret").unwrap();
+        assert_eq!(editor.selected(), 3);
+        assert_eq!(types(&editor), vec!["IHDR", "tEXt", "IDAT", "ruSt", "IEND"]);
+        assert!(editor.add("bad", b"").is_err());
+    }
+
+    #[test]
+    fn test_record_history() {
+        let before = testing_editor();
+        let mut editor = testing_editor();
+        editor.add("ruSt", b"secret").unwrap();
+        editor.record_history(before.png()).unwrap();
+        assert_eq!(
+            types(&editor),
+            vec!["IHDR", "tEXt", "IDAT", "ruSt", "prVn", "IEND"]
+        );
+        let entries = provenance::history(editor.png()).unwrap();
+        assert_eq!(entries[0].operation, "edit");
+        assert_eq!(entries[0].chunk_types, vec!["ruSt"]);
+        assert!(provenance::verify(editor.png()).unwrap().is_empty());
+    }
+}
@@ -0,0 +1,713 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::{Error, Result};
+
+const BYTE: u16 = 1;
+const ASCII: u16 = 2;
+const SHORT: u16 = 3;
+const LONG: u16 = 4;
+const RATIONAL: u16 = 5;
+const UNDEFINED: u16 = 7;
+const SRATIONAL: u16 = 10;
+const IFD: u16 = 13;
+
+const EXIF_POINTER: u16 = 0x8769;
+const GPS_POINTER: u16 = 0x8825;
+const INTEROP_POINTER: u16 = 0xa005;
+const THUMBNAIL_OFFSET: u16 = 0x0201;
+const THUMBNAIL_LENGTH: u16 = 0x0202;
+
+const ORIENTATION: u16 = 0x0112;
+const DATE_TIME: u16 = 0x0132;
+const DATE_TIME_ORIGINAL: u16 = 0x9003;
+
+/// Tags this module can name, with the IFD they live in and the field type
+/// used when setting them from a string.
+/// https://www.cipa.jp/std/documents/e/DC-008-2012_E.pdf
+const TAGS: &[(Group, u16, &str, u16)] = &[
+    (Group::Image, 0x010e, "ImageDescription", ASCII),
+    (Group::Image, 0x010f, "Make", ASCII),
+    (Group::Image, 0x0110, "Model", ASCII),
+    (Group::Image, ORIENTATION, "Orientation", SHORT),
+    (Group::Image, 0x011a, "XResolution", RATIONAL),
+    (Group::Image, 0x011b, "YResolution", RATIONAL),
+    (Group::Image, 0x0128, "ResolutionUnit", SHORT),
+    (Group::Image, 0x0131, "Software", ASCII),
+    (Group::Image, DATE_TIME, "DateTime", ASCII),
+    (Group::Image, 0x013b, "Artist", ASCII),
+    (Group::Image, 0x8298, "Copyright", ASCII),
+    (Group::Exif, 0x829a, "ExposureTime", RATIONAL),
+    (Group::Exif, 0x829d, "FNumber", RATIONAL),
+    (Group::Exif, 0x8827, "ISOSpeedRatings", SHORT),
+    (Group::Exif, 0x9000, "ExifVersion", UNDEFINED),
+    (Group::Exif, DATE_TIME_ORIGINAL, "DateTimeOriginal", ASCII),
+    (Group::Exif, 0x9004, "DateTimeDigitized", ASCII),
+    (Group::Exif, 0x9011, "OffsetTimeOriginal", ASCII),
+    (Group::Exif, 0x920a, "FocalLength", RATIONAL),
+    (Group::Exif, 0x9286, "UserComment", UNDEFINED),
+    (Group::Exif, 0xa002, "PixelXDimension", LONG),
+    (Group::Exif, 0xa003, "PixelYDimension", LONG),
+    (Group::Exif, 0xa434, "LensModel", ASCII),
+    (Group::Gps, 0x0000, "GPSVersionID", BYTE),
+    (Group::Gps, 0x0001, "GPSLatitudeRef", ASCII),
+    (Group::Gps, 0x0002, "GPSLatitude", RATIONAL),
+    (Group::Gps, 0x0003, "GPSLongitudeRef", ASCII),
+    (Group::Gps, 0x0004, "GPSLongitude", RATIONAL),
+    (Group::Gps, 0x0005, "GPSAltitudeRef", BYTE),
+    (Group::Gps, 0x0006, "GPSAltitude", RATIONAL),
+    (Group::Gps, 0x0007, "GPSTimeStamp", RATIONAL),
+    (Group::Gps, 0x001d, "GPSDateStamp", ASCII),
+];
+
+/// The IFDs of an EXIF block. `Image` is IFD0 and `Thumbnail` is IFD1, the
+/// others are reached through pointer tags.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Group {
+    Image,
+    Exif,
+    Interop,
+    Gps,
+    Thumbnail,
+}
+
+impl Group {
+    /// All groups, in the order their IFDs are written.
+    pub const ALL: [Group; 5] = [
+        Group::Image,
+        Group::Exif,
+        Group::Interop,
+        Group::Gps,
+        Group::Thumbnail,
+    ];
+}
+
+impl fmt::Display for Group {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let s = match self {
+            Group::Image => "Image",
+            Group::Exif => "Exif",
+            Group::Interop => "Interop",
+            Group::Gps => "GPS",
+            Group::Thumbnail => "Thumbnail",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ByteOrder {
+    Little,
+    Big,
+}
+
+impl ByteOrder {
+    fn u16(&self, bytes: &[u8]) -> u16 {
+        let bytes = [bytes[0], bytes[1]];
+        match self {
+            ByteOrder::Little => u16::from_le_bytes(bytes),
+            ByteOrder::Big => u16::from_be_bytes(bytes),
+        }
+    }
+
+    fn u32(&self, bytes: &[u8]) -> u32 {
+        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+        match self {
+            ByteOrder::Little => u32::from_le_bytes(bytes),
+            ByteOrder::Big => u32::from_be_bytes(bytes),
+        }
+    }
+
+    fn u16_bytes(&self, value: u16) -> [u8; 2] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+
+    fn u32_bytes(&self, value: u32) -> [u8; 4] {
+        match self {
+            ByteOrder::Little => value.to_le_bytes(),
+            ByteOrder::Big => value.to_be_bytes(),
+        }
+    }
+}
+
+/// The value of a tag. Field types without a variant are kept as raw bytes in
+/// the byte order of the block they were read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Value {
+    Byte(Vec<u8>),
+    Ascii(String),
+    Short(Vec<u16>),
+    Long(Vec<u32>),
+    Rational(Vec<(u32, u32)>),
+    Undefined(Vec<u8>),
+    SRational(Vec<(i32, i32)>),
+    Other {
+        field_type: u16,
+        count: u32,
+        data: Vec<u8>,
+    },
+}
+
+impl Value {
+    /// Parses `s` as a value of `field_type`. Lists are separated by commas and
+    /// rationals are written as `numerator/denominator` or as a decimal.
+    pub fn parse(field_type: u16, s: &str) -> Result<Value> {
+        let items = || s.split(',').map(|x| x.trim());
+        Ok(match field_type {
+            ASCII => Value::Ascii(s.to_string()),
+            UNDEFINED => Value::Undefined(s.as_bytes().to_vec()),
+            BYTE => Value::Byte(
+                items()
+                    .map(|x| x.parse())
+                    .collect::<std::result::Result<_, _>>()?,
+            ),
+            SHORT => Value::Short(
+                items()
+                    .map(|x| x.parse())
+                    .collect::<std::result::Result<_, _>>()?,
+            ),
+            LONG => Value::Long(
+                items()
+                    .map(|x| x.parse())
+                    .collect::<std::result::Result<_, _>>()?,
+            ),
+            RATIONAL => Value::Rational(
+                items()
+                    .map(|x| {
+                        let (n, d) = parse_rational(x)?;
+                        match (u32::try_from(n), u32::try_from(d)) {
+                            (Ok(n), Ok(d)) => Ok((n, d)),
+                            _ => Err(format!("{} is not an unsigned rational", x).into()),
+                        }
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            SRATIONAL => Value::SRational(
+                items()
+                    .map(|x| {
+                        let (n, d) = parse_rational(x)?;
+                        match (i32::try_from(n), i32::try_from(d)) {
+                            (Ok(n), Ok(d)) => Ok((n, d)),
+                            _ => Err(format!("{} is out of range", x).into()),
+                        }
+                    })
+                    .collect::<Result<_>>()?,
+            ),
+            _ => return Err(format!("cannot parse values of field type {}", field_type).into()),
+        })
+    }
+
+    fn field_type(&self) -> u16 {
+        match self {
+            Value::Byte(_) => BYTE,
+            Value::Ascii(_) => ASCII,
+            Value::Short(_) => SHORT,
+            Value::Long(_) => LONG,
+            Value::Rational(_) => RATIONAL,
+            Value::Undefined(_) => UNDEFINED,
+            Value::SRational(_) => SRATIONAL,
+            Value::Other { field_type, .. } => *field_type,
+        }
+    }
+
+    fn count(&self) -> u32 {
+        let count = match self {
+            Value::Byte(x) | Value::Undefined(x) => x.len(),
+            Value::Ascii(x) => x.len() + 1,
+            Value::Short(x) => x.len(),
+            Value::Long(x) => x.len(),
+            Value::Rational(x) => x.len(),
+            Value::SRational(x) => x.len(),
+            Value::Other { count, .. } => *count as usize,
+        };
+        count as u32
+    }
+
+    fn as_bytes(&self, order: ByteOrder) -> Vec<u8> {
+        match self {
+            Value::Byte(x) | Value::Undefined(x) => x.clone(),
+            Value::Ascii(x) => x.bytes().chain(std::iter::once(0)).collect(),
+            Value::Short(x) => x.iter().flat_map(|x| order.u16_bytes(*x)).collect(),
+            Value::Long(x) => x.iter().flat_map(|x| order.u32_bytes(*x)).collect(),
+            Value::Rational(x) => x
+                .iter()
+                .flat_map(|(n, d)| [order.u32_bytes(*n), order.u32_bytes(*d)])
+                .flatten()
+                .collect(),
+            Value::SRational(x) => x
+                .iter()
+                .flat_map(|(n, d)| [order.u32_bytes(*n as u32), order.u32_bytes(*d as u32)])
+                .flatten()
+                .collect(),
+            Value::Other { data, .. } => data.clone(),
+        }
+    }
+
+    fn from_bytes(field_type: u16, count: u32, data: &[u8], order: ByteOrder) -> Value {
+        match field_type {
+            BYTE => Value::Byte(data.to_vec()),
+            ASCII => {
+                let end = data.iter().position(|x| *x == 0).unwrap_or(data.len());
+                Value::Ascii(String::from_utf8_lossy(&data[..end]).to_string())
+            }
+            SHORT => Value::Short(data.chunks(2).map(|x| order.u16(x)).collect()),
+            LONG | IFD => Value::Long(data.chunks(4).map(|x| order.u32(x)).collect()),
+            RATIONAL => Value::Rational(
+                data.chunks(8)
+                    .map(|x| (order.u32(&x[..4]), order.u32(&x[4..])))
+                    .collect(),
+            ),
+            UNDEFINED => Value::Undefined(data.to_vec()),
+            SRATIONAL => Value::SRational(
+                data.chunks(8)
+                    .map(|x| (order.u32(&x[..4]) as i32, order.u32(&x[4..]) as i32))
+                    .collect(),
+            ),
+            _ => Value::Other {
+                field_type,
+                count,
+                data: data.to_vec(),
+            },
+        }
+    }
+
+    /// The first number of an integer value.
+    fn first_u32(&self) -> Option<u32> {
+        match self {
+            Value::Short(x) => x.first().map(|x| *x as u32),
+            Value::Long(x) => x.first().copied(),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fn list<T: fmt::Display>(items: impl Iterator<Item = T>) -> String {
+            items.map(|x| x.to_string()).collect::<Vec<_>>().join(", ")
+        }
+        match self {
+            Value::Byte(x) => write!(f, "{}", list(x.iter())),
+            Value::Ascii(x) => write!(f, "{}", x),
+            Value::Short(x) => write!(f, "{}", list(x.iter())),
+            Value::Long(x) => write!(f, "{}", list(x.iter())),
+            Value::Rational(x) => {
+                write!(f, "{}", list(x.iter().map(|(n, d)| format!("{}/{}", n, d))))
+            }
+            Value::SRational(x) => {
+                write!(f, "{}", list(x.iter().map(|(n, d)| format!("{}/{}", n, d))))
+            }
+            Value::Undefined(x) if x.iter().all(|x| x.is_ascii_graphic() || *x == b' ') => {
+                write!(f, "{}", String::from_utf8_lossy(x))
+            }
+            Value::Undefined(x) => write!(f, "{} bytes", x.len()),
+            Value::Other {
+                field_type, data, ..
+            } => write!(f, "{} bytes of field type {}", data.len(), field_type),
+        }
+    }
+}
+
+fn parse_rational(s: &str) -> Result<(i64, i64)> {
+    if let Some((n, d)) = s.split_once('/') {
+        return Ok((n.trim().parse()?, d.trim().parse()?));
+    }
+    let x: f64 = s.parse()?;
+    if x.fract() == 0.0 {
+        Ok((x as i64, 1))
+    } else {
+        Ok(((x * 10_000.0).round() as i64, 10_000))
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Entry {
+    pub group: Group,
+    pub tag: u16,
+    pub value: Value,
+}
+
+impl Entry {
+    /// The name of this entry's tag, or its number if this module doesn't know it.
+    pub fn name(&self) -> String {
+        match TAGS.iter().find(|x| x.0 == self.group && x.1 == self.tag) {
+            Some(x) => x.2.to_string(),
+            None => format!("0x{:04x}", self.tag),
+        }
+    }
+}
+
+/// The TIFF structure held by an `eXIf` chunk.
+/// https://ftp-osl.osuosl.org/pub/libpng/documents/pngext-1.5.0.html#C.eXIf
+///
+/// Pointer tags between IFDs are not kept as entries. They are rebuilt when the
+/// block is written, so entries can be added and removed freely.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Exif {
+    pub byte_order: ByteOrder,
+    pub entries: Vec<Entry>,
+    pub thumbnail: Option<Vec<u8>>,
+}
+
+impl Default for Exif {
+    fn default() -> Self {
+        Self {
+            byte_order: ByteOrder::Big,
+            entries: Vec::new(),
+            thumbnail: None,
+        }
+    }
+}
+
+impl Exif {
+    /// Returns the value of the tag named `name`, in any group.
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        let (group, tag, _) = lookup(name).ok()?;
+        self.value(group, tag)
+    }
+
+    /// Sets the tag named `name` to `value`, parsed as the tag's field type.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<()> {
+        let (group, tag, field_type) = lookup(name)?;
+        let value = Value::parse(field_type, value)?;
+        self.remove_tag(group, tag);
+        self.entries.push(Entry { group, tag, value });
+        Ok(())
+    }
+
+    /// Removes the tag named `name` and returns its value.
+    pub fn remove(&mut self, name: &str) -> Result<Option<Value>> {
+        let (group, tag, _) = lookup(name)?;
+        Ok(self.remove_tag(group, tag))
+    }
+
+    /// Removes every tag of `group`.
+    pub fn strip_group(&mut self, group: Group) {
+        self.entries.retain(|x| x.group != group);
+        if group == Group::Thumbnail {
+            self.thumbnail = None;
+        }
+    }
+
+    pub fn orientation(&self) -> Option<u16> {
+        match self.value(Group::Image, ORIENTATION) {
+            Some(Value::Short(x)) => x.first().copied(),
+            _ => None,
+        }
+    }
+
+    /// When the picture was taken, falling back to when the file was last changed.
+    pub fn capture_date(&self) -> Option<&str> {
+        let value = self
+            .value(Group::Exif, DATE_TIME_ORIGINAL)
+            .or_else(|| self.value(Group::Image, DATE_TIME));
+        match value {
+            Some(Value::Ascii(x)) => Some(x),
+            _ => None,
+        }
+    }
+
+    /// Latitude and longitude in decimal degrees, south and west being negative.
+    pub fn gps(&self) -> Option<(f64, f64)> {
+        let coordinate = |reference: u16, tag: u16, negative: &str| {
+            let degrees = match self.value(Group::Gps, tag)? {
+                Value::Rational(x) if x.len() == 3 && x.iter().all(|(_, d)| *d != 0) => x
+                    .iter()
+                    .zip([1.0, 60.0, 3600.0])
+                    .map(|((n, d), scale)| *n as f64 / *d as f64 / scale)
+                    .sum::<f64>(),
+                _ => return None,
+            };
+            match self.value(Group::Gps, reference) {
+                Some(Value::Ascii(x)) if x == negative => Some(-degrees),
+                _ => Some(degrees),
+            }
+        };
+        Some((coordinate(1, 2, "S")?, coordinate(3, 4, "W")?))
+    }
+
+    /// Returns this block as TIFF bytes: the header followed by IFD0, the Exif,
+    /// Interop and GPS IFDs, IFD1 and finally the thumbnail.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let order = self.byte_order;
+        let mut ifds: Vec<Vec<(u16, Value)>> = Group::ALL
+            .iter()
+            .map(|group| {
+                self.entries
+                    .iter()
+                    .filter(|x| x.group == *group)
+                    .map(|x| (x.tag, x.value.clone()))
+                    .collect()
+            })
+            .collect();
+        let has_interop = !ifds[2].is_empty();
+        let has_exif = !ifds[1].is_empty() || has_interop;
+        let has_gps = !ifds[3].is_empty();
+        let has_ifd1 = !ifds[4].is_empty() || self.thumbnail.is_some();
+        if has_exif {
+            ifds[0].push((EXIF_POINTER, Value::Long(vec![0])));
+        }
+        if has_gps {
+            ifds[0].push((GPS_POINTER, Value::Long(vec![0])));
+        }
+        if has_interop {
+            ifds[1].push((INTEROP_POINTER, Value::Long(vec![0])));
+        }
+        if let Some(thumbnail) = &self.thumbnail {
+            ifds[4].push((THUMBNAIL_OFFSET, Value::Long(vec![0])));
+            ifds[4].push((THUMBNAIL_LENGTH, Value::Long(vec![thumbnail.len() as u32])));
+        }
+        for ifd in ifds.iter_mut() {
+            ifd.sort_by_key(|x| x.0);
+        }
+
+        let written = [true, has_exif, has_interop, has_gps, has_ifd1];
+        let mut offsets = [0u32; 5];
+        let mut pos = 8;
+        for i in 0..ifds.len() {
+            if written[i] {
+                offsets[i] = pos as u32;
+                pos += ifd_len(&ifds[i], order);
+            }
+        }
+        let pointers = [
+            (0, EXIF_POINTER, offsets[1]),
+            (0, GPS_POINTER, offsets[3]),
+            (1, INTEROP_POINTER, offsets[2]),
+            (4, THUMBNAIL_OFFSET, pos as u32),
+        ];
+        for (ifd, tag, offset) in pointers {
+            if let Some(entry) = ifds[ifd].iter_mut().find(|x| x.0 == tag) {
+                entry.1 = Value::Long(vec![offset]);
+            }
+        }
+
+        let mut bytes = match order {
+            ByteOrder::Little => b"II*\0".to_vec(),
+            ByteOrder::Big => b"MM\0*".to_vec(),
+        };
+        bytes.extend(order.u32_bytes(8));
+        for i in 0..ifds.len() {
+            if written[i] {
+                let next = if i == 0 && has_ifd1 { offsets[4] } else { 0 };
+                write_ifd(&mut bytes, &ifds[i], next, order);
+            }
+        }
+        if let Some(thumbnail) = &self.thumbnail {
+            bytes.extend(thumbnail);
+        }
+        bytes
+    }
+
+    fn value(&self, group: Group, tag: u16) -> Option<&Value> {
+        self.entries
+            .iter()
+            .find(|x| x.group == group && x.tag == tag)
+            .map(|x| &x.value)
+    }
+
+    fn remove_tag(&mut self, group: Group, tag: u16) -> Option<Value> {
+        let idx = self
+            .entries
+            .iter()
+            .position(|x| x.group == group && x.tag == tag)?;
+        Some(self.entries.remove(idx).value)
+    }
+
+    /// Reads the IFD at `offset` into `group` and returns the offset of the next IFD.
+    fn read_ifd(&mut self, bytes: &[u8], offset: u32, group: Group) -> Result<u32> {
+        let order = self.byte_order;
+        let offset = offset as usize;
+        let count = order.u16(slice(bytes, offset, 2)?) as usize;
+        for i in 0..count {
+            let raw = slice(bytes, offset + 2 + 12 * i, 12)?;
+            let tag = order.u16(&raw[0..2]);
+            let field_type = order.u16(&raw[2..4]);
+            let count = order.u32(&raw[4..8]);
+            // Readers must skip field types they don't know the size of.
+            let size = match field_type {
+                1 | 2 | 6 | 7 => 1,
+                3 | 8 => 2,
+                4 | 9 | 11 | 13 => 4,
+                5 | 10 | 12 => 8,
+                _ => continue,
+            };
+            let len = size * count as usize;
+            let data = if len <= 4 {
+                &raw[8..8 + len]
+            } else {
+                slice(bytes, order.u32(&raw[8..12]) as usize, len)?
+            };
+            let value = Value::from_bytes(field_type, count, data, order);
+            self.entries.push(Entry { group, tag, value });
+        }
+        Ok(order.u32(slice(bytes, offset + 2 + 12 * count, 4)?))
+    }
+}
+
+impl TryFrom<&[u8]> for Exif {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        let byte_order = match bytes.get(0..4) {
+            Some(b"II*\0") => ByteOrder::Little,
+            Some(b"MM\0*") => ByteOrder::Big,
+            _ => return Err("invalid TIFF header".into()),
+        };
+        let mut exif = Exif {
+            byte_order,
+            ..Default::default()
+        };
+        let ifd0 = byte_order.u32(slice(bytes, 4, 4)?);
+        let ifd1 = exif.read_ifd(bytes, ifd0, Group::Image)?;
+        if ifd1 != 0 {
+            exif.read_ifd(bytes, ifd1, Group::Thumbnail)?;
+        }
+        for (group, tag, target) in [
+            (Group::Image, EXIF_POINTER, Group::Exif),
+            (Group::Image, GPS_POINTER, Group::Gps),
+            (Group::Exif, INTEROP_POINTER, Group::Interop),
+        ] {
+            if let Some(offset) = exif.remove_tag(group, tag).and_then(|x| x.first_u32()) {
+                exif.read_ifd(bytes, offset, target)?;
+            }
+        }
+        let offset = exif.remove_tag(Group::Thumbnail, THUMBNAIL_OFFSET);
+        let length = exif.remove_tag(Group::Thumbnail, THUMBNAIL_LENGTH);
+        if let (Some(offset), Some(length)) = (offset, length) {
+            if let (Some(offset), Some(length)) = (offset.first_u32(), length.first_u32()) {
+                exif.thumbnail = Some(slice(bytes, offset as usize, length as usize)?.to_vec());
+            }
+        }
+        Ok(exif)
+    }
+}
+
+/// Finds a tag by name, ignoring case.
+fn lookup(name: &str) -> Result<(Group, u16, u16)> {
+    match TAGS.iter().find(|x| x.2.eq_ignore_ascii_case(name)) {
+        Some((group, tag, _, field_type)) => Ok((*group, *tag, *field_type)),
+        None => Err(format!("unknown EXIF tag {}", name).into()),
+    }
+}
+
+fn slice(bytes: &[u8], offset: usize, len: usize) -> Result<&[u8]> {
+    offset
+        .checked_add(len)
+        .and_then(|end| bytes.get(offset..end))
+        .ok_or_else(|| "EXIF data is truncated".into())
+}
+
+/// The length of an IFD including the values stored after it.
+fn ifd_len(entries: &[(u16, Value)], order: ByteOrder) -> usize {
+    let values: usize = entries
+        .iter()
+        .map(|(_, value)| value.as_bytes(order).len())
+        .filter(|len| *len > 4)
+        .map(|len| len + len % 2)
+        .sum();
+    2 + 12 * entries.len() + 4 + values
+}
+
+fn write_ifd(bytes: &mut Vec<u8>, entries: &[(u16, Value)], next: u32, order: ByteOrder) {
+    let mut values_offset = bytes.len() + 2 + 12 * entries.len() + 4;
+    let mut values = Vec::new();
+    bytes.extend(order.u16_bytes(entries.len() as u16));
+    for (tag, value) in entries {
+        let mut data = value.as_bytes(order);
+        bytes.extend(order.u16_bytes(*tag));
+        bytes.extend(order.u16_bytes(value.field_type()));
+        bytes.extend(order.u32_bytes(value.count()));
+        if data.len() <= 4 {
+            data.resize(4, 0);
+            bytes.extend(data);
+        } else {
+            if data.len() % 2 == 1 {
+                data.push(0);
+            }
+            bytes.extend(order.u32_bytes(values_offset as u32));
+            values_offset += data.len();
+            values.extend(data);
+        }
+    }
+    bytes.extend(order.u32_bytes(next));
+    bytes.extend(values);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_exif() -> Exif {
+        let mut exif = Exif::default();
+        exif.set("Make", "Pngme").unwrap();
+        exif.set("Orientation", "6").unwrap();
+        exif.set("DateTimeOriginal", "2022:04:15 07:20:00").unwrap();
+        exif.set("GPSLatitudeRef", "N").unwrap();
+        exif.set("GPSLatitude", "51/1, 30/1, 2604/100").unwrap();
+        exif.set("GPSLongitudeRef", "W").unwrap();
+        exif.set("GPSLongitude", "0, 7, 39").unwrap();
+        exif.thumbnail = Some(vec![0xff, 0xd8, 0xff, 0xd9]);
+        exif
+    }
+
+    #[test]
+    fn test_exif_roundtrip() {
+        for byte_order in [ByteOrder::Big, ByteOrder::Little] {
+            let exif = Exif {
+                byte_order,
+                ..testing_exif()
+            };
+            let bytes = exif.as_bytes();
+            let parsed = Exif::try_from(&bytes[..]).unwrap();
+            assert_eq!(parsed.as_bytes(), bytes);
+            assert_eq!(parsed.orientation(), Some(6));
+            assert_eq!(parsed.capture_date(), Some("2022:04:15 07:20:00"));
+            assert_eq!(parsed.thumbnail, exif.thumbnail);
+            assert_eq!(parsed.entries.len(), exif.entries.len());
+        }
+    }
+
+    #[test]
+    fn test_exif_gps() {
+        let (latitude, longitude) = testing_exif().gps().unwrap();
+        assert!((latitude - 51.507233).abs() < 1e-6);
+        assert!((longitude + 0.1275).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_exif_little_endian() {
+        // IFD0 with a single inline Orientation entry.
+        let bytes = b"II*\0\x08\0\0\0\x01\0\x12\x01\x03\0\x01\0\0\0\x03\0\0\0\0\0\0\0";
+        let exif = Exif::try_from(&bytes[..]).unwrap();
+        assert_eq!(exif.orientation(), Some(3));
+        assert_eq!(exif.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_exif_strip() {
+        let mut exif = testing_exif();
+        exif.strip_group(Group::Gps);
+        assert_eq!(exif.gps(), None);
+        assert_eq!(
+            exif.remove("make").unwrap(),
+            Some(Value::Ascii("Pngme".to_string()))
+        );
+        assert_eq!(exif.get("Make"), None);
+        assert!(exif.remove("NoSuchTag").is_err());
+
+        let parsed = Exif::try_from(&exif.as_bytes()[..]).unwrap();
+        assert_eq!(parsed.as_bytes(), exif.as_bytes());
+        assert_eq!(parsed.orientation(), Some(6));
+    }
+
+    #[test]
+    fn test_exif_invalid() {
+        assert!(Exif::try_from(&b"II*\0\xff\0\0\0"[..]).is_err());
+        assert!(Exif::try_from(&b"JFIF"[..]).is_err());
+        assert!(Value::parse(SHORT, "70000").is_err());
+    }
+}
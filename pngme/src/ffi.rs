@@ -0,0 +1,503 @@
+use std::cell::RefCell;
+use std::convert::TryFrom;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use std::slice;
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::png::Png;
+
+/// Result of every fallible C function. On anything but `Ok`,
+/// `pngme_last_error` describes what went wrong. `Internal` means pngme
+/// panicked, which is a bug. Mirrored in `ffi/pngme.h`.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PngmeStatus {
+    Ok = 0,
+    NullPointer = 1,
+    InvalidPng = 2,
+    InvalidChunkType = 3,
+    NotFound = 4,
+    OutOfRange = 5,
+    Internal = 6,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(status: PngmeStatus, message: String) -> PngmeStatus {
+    let message = CString::new(message).unwrap_or_default();
+    LAST_ERROR.with(|x| *x.borrow_mut() = Some(message));
+    status
+}
+
+/// Runs `f`, returning `on_panic` instead of unwinding into C if it panics.
+fn guard<T>(on_panic: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|e| {
+        let message = match (e.downcast_ref::<&str>(), e.downcast_ref::<String>()) {
+            (Some(message), _) => message.to_string(),
+            (_, Some(message)) => message.clone(),
+            _ => "unknown panic".to_string(),
+        };
+        fail(
+            PngmeStatus::Internal,
+            format!("internal error: {}", message),
+        );
+        on_panic
+    })
+}
+
+fn null_pointer(name: &str) -> PngmeStatus {
+    fail(PngmeStatus::NullPointer, format!("{} is NULL", name))
+}
+
+/// Borrows `len` bytes at `data`, allowing NULL for an empty slice.
+unsafe fn bytes<'a>(data: *const u8, len: usize) -> Option<&'a [u8]> {
+    match (data.is_null(), len) {
+        (true, 0) => Some(&[]),
+        (true, _) => None,
+        (false, _) => Some(slice::from_raw_parts(data, len)),
+    }
+}
+
+unsafe fn chunk_type(chunk_type: *const c_char) -> Result<ChunkType, PngmeStatus> {
+    if chunk_type.is_null() {
+        return Err(null_pointer("chunk_type"));
+    }
+    let parsed = CStr::from_ptr(chunk_type)
+        .to_str()
+        .map_err(|e| e.to_string().into())
+        .and_then(ChunkType::from_str);
+    parsed.map_err(|e| fail(PngmeStatus::InvalidChunkType, e.to_string()))
+}
+
+/// Returns a description of the last error on this thread, or NULL if there
+/// was none. The string stays valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn pngme_last_error() -> *const c_char {
+    guard(ptr::null(), || {
+        LAST_ERROR.with(|x| x.borrow().as_ref().map_or(ptr::null(), |x| x.as_ptr()))
+    })
+}
+
+/// Parses the file in the `len` bytes at `data` into a new handle stored in
+/// `*out`, to be released with `pngme_png_free`.
+///
+/// # Safety
+/// `data` must point to `len` readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_png_from_bytes(
+    data: *const u8,
+    len: usize,
+    out: *mut *mut Png,
+) -> PngmeStatus {
+    guard(PngmeStatus::Internal, || {
+        if out.is_null() {
+            return null_pointer("out");
+        }
+        let data = match bytes(data, len) {
+            Some(data) => data,
+            None => return null_pointer("data"),
+        };
+        match Png::try_from(data) {
+            Ok(png) => {
+                *out = Box::into_raw(Box::new(png));
+                PngmeStatus::Ok
+            }
+            Err(e) => fail(PngmeStatus::InvalidPng, e.to_string()),
+        }
+    })
+}
+
+/// Releases a handle made by `pngme_png_from_bytes`. NULL is ignored.
+///
+/// # Safety
+/// `png` must be NULL or a handle that hasn't been freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_png_free(png: *mut Png) {
+    guard((), || {
+        if !png.is_null() {
+            drop(Box::from_raw(png));
+        }
+    })
+}
+
+/// Returns the number of chunks in `png`, or 0 if it is NULL.
+///
+/// # Safety
+/// `png` must be NULL or a live handle.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_png_chunk_count(png: *const Png) -> usize {
+    guard(0, || png.as_ref().map_or(0, |x| x.chunks().len()))
+}
+
+/// Stores the chunk at `index` in `*out`. The chunk belongs to `png` and stays
+/// valid until `png` is changed or freed.
+///
+/// # Safety
+/// `png` must be a live handle and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_png_chunk_at(
+    png: *const Png,
+    index: usize,
+    out: *mut *const Chunk,
+) -> PngmeStatus {
+    guard(PngmeStatus::Internal, || {
+        let png = match png.as_ref() {
+            Some(png) => png,
+            None => return null_pointer("png"),
+        };
+        if out.is_null() {
+            return null_pointer("out");
+        }
+        match png.chunks().get(index) {
+            Some(chunk) => {
+                *out = chunk;
+                PngmeStatus::Ok
+            }
+            None => fail(
+                PngmeStatus::OutOfRange,
+                format!(
+                    "index {} is out of range for {} chunks",
+                    index,
+                    png.chunks().len()
+                ),
+            ),
+        }
+    })
+}
+
+/// Moves `chunk` into `png` at the position the PNG spec requires for its
+/// type. On success `png` owns the chunk and it must not be freed.
+///
+/// # Safety
+/// `png` must be a live handle and `chunk` a chunk made by `pngme_chunk_new`.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_png_add_chunk(png: *mut Png, chunk: *mut Chunk) -> PngmeStatus {
+    guard(PngmeStatus::Internal, || {
+        let png = match png.as_mut() {
+            Some(png) => png,
+            None => return null_pointer("png"),
+        };
+        if chunk.is_null() {
+            return null_pointer("chunk");
+        }
+        png.insert_chunk(*Box::from_raw(chunk));
+        PngmeStatus::Ok
+    })
+}
+
+/// Removes the first chunk of type `chunk_type` from `png`.
+///
+/// # Safety
+/// `png` must be a live handle and `chunk_type` a NUL terminated string.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_png_remove_chunk(
+    png: *mut Png,
+    chunk_type: *const c_char,
+) -> PngmeStatus {
+    guard(PngmeStatus::Internal, || {
+        let png = match png.as_mut() {
+            Some(png) => png,
+            None => return null_pointer("png"),
+        };
+        let chunk_type = match self::chunk_type(chunk_type) {
+            Ok(chunk_type) => chunk_type,
+            Err(status) => return status,
+        };
+        match png.remove_chunk(&chunk_type.to_string()) {
+            Ok(_) => PngmeStatus::Ok,
+            Err(e) => fail(PngmeStatus::NotFound, e.to_string()),
+        }
+    })
+}
+
+/// Serializes `png` into a new buffer stored in `*out` and `*len`, to be
+/// released with `pngme_bytes_free`.
+///
+/// # Safety
+/// `png` must be a live handle, and `out` and `len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_png_to_bytes(
+    png: *const Png,
+    out: *mut *mut u8,
+    len: *mut usize,
+) -> PngmeStatus {
+    guard(PngmeStatus::Internal, || {
+        let png = match png.as_ref() {
+            Some(png) => png,
+            None => return null_pointer("png"),
+        };
+        if out.is_null() || len.is_null() {
+            return null_pointer("out");
+        }
+        let bytes = png.as_bytes().into_boxed_slice();
+        *len = bytes.len();
+        *out = Box::into_raw(bytes) as *mut u8;
+        PngmeStatus::Ok
+    })
+}
+
+/// Releases a buffer made by `pngme_png_to_bytes`. NULL is ignored.
+///
+/// # Safety
+/// `data` and `len` must be exactly as returned by `pngme_png_to_bytes`.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_bytes_free(data: *mut u8, len: usize) {
+    guard((), || {
+        if !data.is_null() {
+            drop(Box::from_raw(ptr::slice_from_raw_parts_mut(data, len)));
+        }
+    })
+}
+
+/// Makes a new chunk of type `chunk_type` holding a copy of the `len` bytes at
+/// `data`, stored in `*out`. Release it with `pngme_chunk_free` unless it is
+/// added to a `Png`.
+///
+/// # Safety
+/// `chunk_type` must be a NUL terminated string, `data` must point to `len`
+/// readable bytes and `out` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_chunk_new(
+    chunk_type: *const c_char,
+    data: *const u8,
+    len: usize,
+    out: *mut *mut Chunk,
+) -> PngmeStatus {
+    guard(PngmeStatus::Internal, || {
+        if out.is_null() {
+            return null_pointer("out");
+        }
+        let chunk_type = match self::chunk_type(chunk_type) {
+            Ok(chunk_type) => chunk_type,
+            Err(status) => return status,
+        };
+        if len > Chunk::MAX_LENGTH as usize {
+            return fail(
+                PngmeStatus::OutOfRange,
+                format!("chunk length {} exceeds the maximum of 2^31-1", len),
+            );
+        }
+        let data = match bytes(data, len) {
+            Some(data) => data,
+            None => return null_pointer("data"),
+        };
+        *out = Box::into_raw(Box::new(Chunk::new(chunk_type, data.to_vec())));
+        PngmeStatus::Ok
+    })
+}
+
+/// Releases a chunk made by `pngme_chunk_new`. NULL is ignored.
+///
+/// # Safety
+/// `chunk` must be NULL or a chunk that is neither freed nor added to a `Png`.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_chunk_free(chunk: *mut Chunk) {
+    guard((), || {
+        if !chunk.is_null() {
+            drop(Box::from_raw(chunk));
+        }
+    })
+}
+
+/// Writes the type of `chunk` to `out` as a NUL terminated string.
+///
+/// # Safety
+/// `chunk` must be a live chunk and `out` must have room for 5 bytes.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_chunk_type(chunk: *const Chunk, out: *mut c_char) -> PngmeStatus {
+    guard(PngmeStatus::Internal, || {
+        let chunk = match chunk.as_ref() {
+            Some(chunk) => chunk,
+            None => return null_pointer("chunk"),
+        };
+        if out.is_null() {
+            return null_pointer("out");
+        }
+        let mut name = [0u8; 5];
+        name[..4].copy_from_slice(&chunk.chunk_type().bytes());
+        ptr::copy_nonoverlapping(name.as_ptr() as *const c_char, out, name.len());
+        PngmeStatus::Ok
+    })
+}
+
+/// Returns the data of `chunk` and stores its length in `*len`. The data
+/// belongs to the chunk. Returns NULL if `chunk` is NULL.
+///
+/// # Safety
+/// `chunk` must be NULL or a live chunk, and `len` must be writable.
+#[no_mangle]
+pub unsafe extern "C" fn pngme_chunk_data(chunk: *const Chunk, len: *mut usize) -> *const u8 {
+    guard(ptr::null(), || match (chunk.as_ref(), len.is_null()) {
+        (Some(chunk), false) => {
+            *len = chunk.data().len();
+            chunk.data().as_ptr()
+        }
+        _ => ptr::null(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::minimal_png;
+    use std::path::Path;
+    use std::process::Command;
+
+    fn last_error() -> String {
+        unsafe { CStr::from_ptr(pngme_last_error()) }
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let bytes = minimal_png().as_bytes();
+        unsafe {
+            let mut png = ptr::null_mut();
+            assert_eq!(
+                pngme_png_from_bytes(bytes.as_ptr(), bytes.len(), &mut png),
+                PngmeStatus::Ok
+            );
+            assert_eq!(pngme_png_chunk_count(png), 2);
+
+            let mut chunk = ptr::null_mut();
+            let data = b"hello";
+            assert_eq!(
+                pngme_chunk_new(c"ruSt".as_ptr(), data.as_ptr(), data.len(), &mut chunk),
+                PngmeStatus::Ok
+            );
+            assert_eq!(pngme_png_add_chunk(png, chunk), PngmeStatus::Ok);
+
+            let mut added = ptr::null();
+            assert_eq!(pngme_png_chunk_at(png, 1, &mut added), PngmeStatus::Ok);
+            let mut name = [0 as c_char; 5];
+            assert_eq!(pngme_chunk_type(added, name.as_mut_ptr()), PngmeStatus::Ok);
+            assert_eq!(CStr::from_ptr(name.as_ptr()).to_str().unwrap(), "ruSt");
+            let mut len = 0;
+            let data = pngme_chunk_data(added, &mut len);
+            assert_eq!(slice::from_raw_parts(data, len), b"hello");
+
+            let (mut out, mut len) = (ptr::null_mut(), 0);
+            assert_eq!(pngme_png_to_bytes(png, &mut out, &mut len), PngmeStatus::Ok);
+            let written = Png::try_from(slice::from_raw_parts(out, len)).unwrap();
+            assert_eq!(written.chunks().len(), 3);
+            pngme_bytes_free(out, len);
+
+            assert_eq!(
+                pngme_png_remove_chunk(png, c"ruSt".as_ptr()),
+                PngmeStatus::Ok
+            );
+            assert_eq!(pngme_png_chunk_count(png), 2);
+            pngme_png_free(png);
+        }
+    }
+
+    #[test]
+    fn test_errors() {
+        unsafe {
+            let mut png = ptr::null_mut();
+            assert_eq!(
+                pngme_png_from_bytes(b"nope".as_ptr(), 4, &mut png),
+                PngmeStatus::InvalidPng
+            );
+            assert!(png.is_null());
+            assert!(!last_error().is_empty());
+            assert_eq!(
+                pngme_png_from_bytes(ptr::null(), 4, &mut png),
+                PngmeStatus::NullPointer
+            );
+
+            let bytes = minimal_png().as_bytes();
+            pngme_png_from_bytes(bytes.as_ptr(), bytes.len(), &mut png);
+            let mut chunk = ptr::null();
+            assert_eq!(
+                pngme_png_chunk_at(png, 2, &mut chunk),
+                PngmeStatus::OutOfRange
+            );
+            assert_eq!(
+                pngme_png_remove_chunk(png, c"ruSt".as_ptr()),
+                PngmeStatus::NotFound
+            );
+            assert_eq!(last_error(), "ruSt not found");
+            assert_eq!(
+                pngme_png_remove_chunk(png, c"ru".as_ptr()),
+                PngmeStatus::InvalidChunkType
+            );
+            pngme_png_free(png);
+
+            let mut chunk = ptr::null_mut();
+            assert_eq!(
+                pngme_chunk_new(c"IEND".as_ptr(), ptr::null(), 0, &mut chunk),
+                PngmeStatus::Ok
+            );
+            pngme_chunk_free(chunk);
+            assert_eq!(
+                pngme_chunk_new(c"ruSt".as_ptr(), b"x".as_ptr(), 1 << 31, &mut chunk),
+                PngmeStatus::OutOfRange
+            );
+        }
+    }
+
+    #[test]
+    fn test_guard() {
+        assert_eq!(
+            guard(PngmeStatus::Internal, || panic!("broken")),
+            PngmeStatus::Internal
+        );
+        assert_eq!(last_error(), "internal error: broken");
+        assert_eq!(
+            guard(PngmeStatus::Internal, || PngmeStatus::Ok),
+            PngmeStatus::Ok
+        );
+    }
+
+    /// Builds the static library and `ffi/test.c` against it, then runs the
+    /// program. Test builds don't produce the static library, so this runs
+    /// cargo with a target directory of its own.
+    #[test]
+    fn test_c_program() {
+        let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+        // Test binaries live in target/<profile>/deps.
+        let exe = std::env::current_exe().unwrap();
+        let target_dir = exe.ancestors().nth(3).unwrap().join("ffi-test");
+        let status = Command::new(std::env::var("CARGO").unwrap_or_else(|_| "cargo".to_string()))
+            .args([
+                "build",
+                "--offline",
+                "--lib",
+                "--no-default-features",
+                "--manifest-path",
+            ])
+            .arg(manifest_dir.join("Cargo.toml"))
+            .arg("--target-dir")
+            .arg(&target_dir)
+            .status()
+            .unwrap();
+        assert!(status.success(), "building the static library failed");
+        let library = target_dir.join("debug").join("libpngme.a");
+
+        let program = exe.with_file_name("pngme_ffi_test");
+        let status = Command::new(std::env::var("CC").unwrap_or_else(|_| "cc".to_string()))
+            .arg(manifest_dir.join("ffi/test.c"))
+            .arg("-I")
+            .arg(manifest_dir.join("ffi"))
+            .arg(&library)
+            .args(["-lpthread", "-ldl", "-lm", "-o"])
+            .arg(&program)
+            .status();
+        let status = status.unwrap_or_else(|e| panic!("no C compiler, set CC: {}", e));
+        assert!(status.success(), "compiling ffi/test.c failed");
+        let output = Command::new(&program).output().unwrap();
+        assert!(
+            output.status.success(),
+            "{}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+}
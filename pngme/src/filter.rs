@@ -0,0 +1,240 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::ihdr::Ihdr;
+use crate::{Error, Result};
+
+/// A scanline filter type as described by the PNG spec
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Filters.html
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterType {
+    None = 0,
+    Sub = 1,
+    Up = 2,
+    Average = 3,
+    Paeth = 4,
+}
+
+/// How a filter type is chosen for each scanline when filtering an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterStrategy {
+    /// Use the same filter type on every scanline.
+    Fixed(FilterType),
+    /// Pick the filter type with the smallest sum of absolute differences for
+    /// each scanline, the heuristic recommended by the PNG spec.
+    Adaptive,
+}
+
+impl FilterType {
+    pub const ALL: [FilterType; 5] = [
+        FilterType::None,
+        FilterType::Sub,
+        FilterType::Up,
+        FilterType::Average,
+        FilterType::Paeth,
+    ];
+}
+
+impl FilterStrategy {
+    /// Every strategy worth trying when looking for the smallest output.
+    pub fn all() -> Vec<FilterStrategy> {
+        FilterType::ALL
+            .iter()
+            .map(|&t| FilterStrategy::Fixed(t))
+            .chain(std::iter::once(FilterStrategy::Adaptive))
+            .collect()
+    }
+}
+
+impl TryFrom<u8> for FilterType {
+    type Error = Error;
+
+    fn try_from(byte: u8) -> Result<Self> {
+        match byte {
+            0 => Ok(FilterType::None),
+            1 => Ok(FilterType::Sub),
+            2 => Ok(FilterType::Up),
+            3 => Ok(FilterType::Average),
+            4 => Ok(FilterType::Paeth),
+            _ => Err(format!("invalid filter type {}", byte).into()),
+        }
+    }
+}
+
+impl fmt::Display for FilterStrategy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FilterStrategy::Fixed(t) => write!(f, "{:?}", t),
+            FilterStrategy::Adaptive => write!(f, "Adaptive"),
+        }
+    }
+}
+
+fn paeth(a: u8, b: u8, c: u8) -> u8 {
+    let p = a as i16 + b as i16 - c as i16;
+    let pa = (p - a as i16).abs();
+    let pb = (p - b as i16).abs();
+    let pc = (p - c as i16).abs();
+    if pa <= pb && pa <= pc {
+        a
+    } else if pb <= pc {
+        b
+    } else {
+        c
+    }
+}
+
+/// Filters a single scanline, writing the filtered bytes (without the filter
+/// type byte) into `out`.
+fn filter_line(filter: FilterType, bpp: usize, line: &[u8], prev: &[u8], out: &mut Vec<u8>) {
+    for i in 0..line.len() {
+        let a = if i >= bpp { line[i - bpp] } else { 0 };
+        let b = prev[i];
+        let c = if i >= bpp { prev[i - bpp] } else { 0 };
+        let predictor = match filter {
+            FilterType::None => 0,
+            FilterType::Sub => a,
+            FilterType::Up => b,
+            FilterType::Average => ((a as u16 + b as u16) / 2) as u8,
+            FilterType::Paeth => paeth(a, b, c),
+        };
+        out.push(line[i].wrapping_sub(predictor));
+    }
+}
+
+/// Reverses `filter` on a single scanline in place.
+fn unfilter_line(filter: FilterType, bpp: usize, line: &mut [u8], prev: &[u8]) {
+    for i in 0..line.len() {
+        let a = if i >= bpp { line[i - bpp] } else { 0 };
+        let b = prev[i];
+        let c = if i >= bpp { prev[i - bpp] } else { 0 };
+        let predictor = match filter {
+            FilterType::None => 0,
+            FilterType::Sub => a,
+            FilterType::Up => b,
+            FilterType::Average => ((a as u16 + b as u16) / 2) as u8,
+            FilterType::Paeth => paeth(a, b, c),
+        };
+        line[i] = line[i].wrapping_add(predictor);
+    }
+}
+
+fn sum_of_abs(filtered: &[u8]) -> u64 {
+    filtered
+        .iter()
+        .map(|&x| (x as i8).unsigned_abs() as u64)
+        .sum()
+}
+
+/// Removes the scanline filters from decompressed IDAT data. The returned
+/// bytes hold the raw scanlines of every pass back to back, without filter
+/// type bytes.
+pub fn unfilter(ihdr: &Ihdr, data: &[u8]) -> Result<Vec<u8>> {
+    let bpp = ihdr.bytes_per_pixel();
+    let mut raw = Vec::with_capacity(data.len());
+    let mut pos = 0;
+    for (width, height) in ihdr.passes() {
+        let len = ihdr.scanline_len(width);
+        let mut prev = vec![0u8; len];
+        for _ in 0..height {
+            if pos + 1 + len > data.len() {
+                return Err("image data is shorter than the header describes".into());
+            }
+            let filter = FilterType::try_from(data[pos])?;
+            let mut line = data[pos + 1..pos + 1 + len].to_vec();
+            unfilter_line(filter, bpp, &mut line, &prev);
+            raw.extend_from_slice(&line);
+            prev = line;
+            pos += 1 + len;
+        }
+    }
+    if pos != data.len() {
+        return Err("image data is longer than the header describes".into());
+    }
+    Ok(raw)
+}
+
+/// Filters raw scanlines as returned by `unfilter`, prefixing each scanline
+/// with the filter type chosen by `strategy`.
+pub fn filter(ihdr: &Ihdr, raw: &[u8], strategy: FilterStrategy) -> Vec<u8> {
+    let bpp = ihdr.bytes_per_pixel();
+    let mut out = Vec::with_capacity(raw.len() + ihdr.height as usize * 7);
+    let mut pos = 0;
+    for (width, height) in ihdr.passes() {
+        let len = ihdr.scanline_len(width);
+        let zeros = vec![0u8; len];
+        let mut prev: &[u8] = &zeros;
+        for _ in 0..height {
+            let line = &raw[pos..pos + len];
+            let filter = match strategy {
+                FilterStrategy::Fixed(t) => t,
+                FilterStrategy::Adaptive => *FilterType::ALL
+                    .iter()
+                    .min_by_key(|&&t| {
+                        let mut candidate = Vec::with_capacity(len);
+                        filter_line(t, bpp, line, prev, &mut candidate);
+                        sum_of_abs(&candidate)
+                    })
+                    .unwrap(),
+            };
+            out.push(filter as u8);
+            filter_line(filter, bpp, line, prev, &mut out);
+            prev = line;
+            pos += len;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_ihdr(interlace_method: u8) -> Ihdr {
+        Ihdr {
+            width: 9,
+            height: 5,
+            bit_depth: 8,
+            color_type: 2,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method,
+        }
+    }
+
+    fn testing_pixels(ihdr: &Ihdr) -> Vec<u8> {
+        let len: usize = ihdr
+            .passes()
+            .iter()
+            .map(|&(w, h)| ihdr.scanline_len(w) * h as usize)
+            .sum();
+        (0..len).map(|i| (i * 37 % 251) as u8).collect()
+    }
+
+    #[test]
+    fn test_filter_roundtrip() {
+        for interlace in 0..2 {
+            let ihdr = testing_ihdr(interlace);
+            let raw = testing_pixels(&ihdr);
+            for strategy in FilterStrategy::all() {
+                let filtered = filter(&ihdr, &raw, strategy);
+                assert_eq!(unfilter(&ihdr, &filtered).unwrap(), raw);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unfilter_invalid_type() {
+        let ihdr = testing_ihdr(0);
+        let mut filtered = filter(&ihdr, &testing_pixels(&ihdr), FilterStrategy::Adaptive);
+        filtered[0] = 5;
+        assert!(unfilter(&ihdr, &filtered).is_err());
+    }
+
+    #[test]
+    fn test_unfilter_truncated() {
+        let ihdr = testing_ihdr(0);
+        let filtered = filter(&ihdr, &testing_pixels(&ihdr), FilterStrategy::Adaptive);
+        assert!(unfilter(&ihdr, &filtered[..filtered.len() - 1]).is_err());
+    }
+}
@@ -0,0 +1,61 @@
+/// Exponent and logarithm tables for GF(2^8) with the polynomial 0x11d, the
+/// field `ecc` and `shamir` work in.
+struct Tables {
+    exp: [u8; 512],
+    log: [u8; 256],
+}
+
+const TABLES: Tables = {
+    let mut exp = [0u8; 512];
+    let mut log = [0u8; 256];
+    let mut x: u16 = 1;
+    let mut i = 0;
+    while i < 255 {
+        exp[i] = x as u8;
+        log[x as usize] = i as u8;
+        x <<= 1;
+        if x & 0x100 != 0 {
+            x ^= 0x11d;
+        }
+        i += 1;
+    }
+    while i < 512 {
+        exp[i] = exp[i - 255];
+        i += 1;
+    }
+    Tables { exp, log }
+};
+
+pub fn mul(a: u8, b: u8) -> u8 {
+    if a == 0 || b == 0 {
+        return 0;
+    }
+    TABLES.exp[TABLES.log[a as usize] as usize + TABLES.log[b as usize] as usize]
+}
+
+/// The multiplicative inverse of `a`, which must not be zero.
+pub fn inverse(a: u8) -> u8 {
+    TABLES.exp[255 - TABLES.log[a as usize] as usize]
+}
+
+/// The generator element raised to `power`.
+pub fn alpha(power: usize) -> u8 {
+    TABLES.exp[power % 255]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_field() {
+        for a in 1..=255u8 {
+            assert_eq!(mul(a, inverse(a)), 1);
+            assert_eq!(mul(a, 1), a);
+            assert_eq!(mul(a, 0), 0);
+        }
+        assert_eq!(alpha(0), 1);
+        assert_eq!(alpha(8), 0x1d);
+        assert_eq!(alpha(255), 1);
+    }
+}
@@ -0,0 +1,55 @@
+use std::fmt::Write;
+
+/// Formats `bytes` as a hex and ASCII dump, 16 bytes per line, in the style
+/// of `hexdump -C`. Each line starts with the offset of its first byte,
+/// counted from `offset`.
+pub fn dump(bytes: &[u8], offset: usize) -> String {
+    let mut out = String::new();
+    for (i, line) in bytes.chunks(16).enumerate() {
+        let _ = write!(out, "{:08x} ", offset + i * 16);
+        for j in 0..16 {
+            if j == 8 {
+                out.push(' ');
+            }
+            match line.get(j) {
+                Some(byte) => {
+                    let _ = write!(out, " {:02x}", byte);
+                }
+                None => out.push_str("   "),
+            }
+        }
+        out.push_str("  |");
+        out.extend(line.iter().map(|&x| {
+            if x.is_ascii_graphic() || x == b' ' {
+                x as char
+            } else {
+                '.'
+            }
+        }));
+        out.push_str("|\n");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_dump_line() {
+        let bytes = b"\x00\x00\x00\x0dIHDR\x00\x00\x002\x00\x00\x002";
+        assert_eq!(
+            dump(bytes, 8),
+            "00000008  00 00 00 0d 49 48 44 52  00 00 00 32 00 00 00 32  |....IHDR...2...2|\n"
+        );
+    }
+
+    #[test]
+    fn test_dump_partial_line() {
+        let out = dump(b"RuSt is here, and there", 0x20);
+        let lines: Vec<&str> = out.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("00000030  64 20 74 68 65 72 65    "));
+        assert!(lines[1].ends_with("|d there|"));
+    }
+}
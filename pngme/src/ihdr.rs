@@ -0,0 +1,252 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::Chunk;
+use crate::{Error, Result};
+
+/// The decoded contents of an `IHDR` chunk. See the PNG spec for more details
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html#C.IHDR
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Ihdr {
+    pub width: u32,
+    pub height: u32,
+    pub bit_depth: u8,
+    pub color_type: u8,
+    pub compression_method: u8,
+    pub filter_method: u8,
+    pub interlace_method: u8,
+}
+
+impl Ihdr {
+    /// Starting column, starting row, column increment and row increment of
+    /// each of the seven Adam7 passes.
+    const ADAM7: [(u32, u32, u32, u32); 7] = [
+        (0, 0, 8, 8),
+        (4, 0, 8, 8),
+        (0, 4, 4, 8),
+        (2, 0, 4, 4),
+        (0, 2, 2, 4),
+        (1, 0, 2, 2),
+        (0, 1, 1, 2),
+    ];
+
+    /// Largest width or height the PNG spec allows.
+    pub const MAX_DIMENSION: u32 = (1 << 31) - 1;
+
+    /// Number of samples in each pixel for this color type.
+    pub fn channels(&self) -> usize {
+        match self.color_type {
+            2 => 3,
+            4 => 2,
+            6 => 4,
+            _ => 1,
+        }
+    }
+
+    /// Number of bytes in a complete pixel, rounded up to one. This is the
+    /// distance used by the `Sub`, `Average` and `Paeth` filters.
+    pub fn bytes_per_pixel(&self) -> usize {
+        (self.channels() * self.bit_depth as usize)
+            .div_ceil(8)
+            .max(1)
+    }
+
+    /// Number of bytes in a scanline `width` pixels wide, not counting the
+    /// filter type byte.
+    pub fn scanline_len(&self, width: u32) -> usize {
+        (width as usize * self.channels() * self.bit_depth as usize).div_ceil(8)
+    }
+
+    /// Length of the decompressed IDAT stream this header describes: the
+    /// scanlines of every pass, each with its filter type byte.
+    pub fn raw_len(&self) -> u64 {
+        self.passes()
+            .iter()
+            .map(|&(width, height)| height as u64 * (1 + self.scanline_len(width) as u64))
+            .sum()
+    }
+
+    /// Returns true if this image is Adam7 interlaced.
+    pub fn is_interlaced(&self) -> bool {
+        self.interlace_method == 1
+    }
+
+    /// Width and height of each reduced image stored in the IDAT stream.
+    /// Non-interlaced images have a single pass; empty Adam7 passes are left out.
+    pub fn passes(&self) -> Vec<(u32, u32)> {
+        if !self.is_interlaced() {
+            return vec![(self.width, self.height)];
+        }
+        Self::ADAM7
+            .iter()
+            .map(|&(x0, y0, dx, dy)| {
+                (
+                    self.width.saturating_sub(x0).div_ceil(dx),
+                    self.height.saturating_sub(y0).div_ceil(dy),
+                )
+            })
+            .filter(|&(w, h)| w > 0 && h > 0)
+            .collect()
+    }
+
+    /// Returns the 13 data bytes of the `IHDR` chunk describing this header.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        self.width
+            .to_be_bytes()
+            .iter()
+            .chain(self.height.to_be_bytes().iter())
+            .chain(
+                [
+                    self.bit_depth,
+                    self.color_type,
+                    self.compression_method,
+                    self.filter_method,
+                    self.interlace_method,
+                ]
+                .iter(),
+            )
+            .copied()
+            .collect()
+    }
+
+    fn is_valid_depth(&self) -> bool {
+        let allowed: &[u8] = match self.color_type {
+            0 => &[1, 2, 4, 8, 16],
+            3 => &[1, 2, 4, 8],
+            2 | 4 | 6 => &[8, 16],
+            _ => &[],
+        };
+        allowed.contains(&self.bit_depth)
+    }
+}
+
+impl TryFrom<&Chunk> for Ihdr {
+    type Error = Error;
+
+    fn try_from(chunk: &Chunk) -> Result<Self> {
+        if chunk.chunk_type().bytes() != *b"IHDR" {
+            return Err(format!("expected IHDR, found {}", chunk.chunk_type()).into());
+        }
+        let data = chunk.data();
+        if data.len() != 13 {
+            return Err("IHDR must be 13 bytes long".into());
+        }
+        let ihdr = Self {
+            width: u32::from_be_bytes([data[0], data[1], data[2], data[3]]),
+            height: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+            bit_depth: data[8],
+            color_type: data[9],
+            compression_method: data[10],
+            filter_method: data[11],
+            interlace_method: data[12],
+        };
+        if ihdr.width == 0 || ihdr.height == 0 {
+            return Err("image dimensions must be non-zero".into());
+        }
+        if ihdr.width > Self::MAX_DIMENSION || ihdr.height > Self::MAX_DIMENSION {
+            return Err("image dimensions must be at most 2^31-1".into());
+        }
+        if !ihdr.is_valid_depth() {
+            return Err(format!(
+                "invalid bit depth {} for color type {}",
+                ihdr.bit_depth, ihdr.color_type
+            )
+            .into());
+        }
+        if ihdr.compression_method != 0 || ihdr.filter_method != 0 || ihdr.interlace_method > 1 {
+            return Err("unknown compression, filter or interlace method".into());
+        }
+        Ok(ihdr)
+    }
+}
+
+impl fmt::Display for Ihdr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}x{}, bit depth {}, color type {}, {}",
+            self.width,
+            self.height,
+            self.bit_depth,
+            self.color_type,
+            if self.is_interlaced() {
+                "interlaced"
+            } else {
+                "non-interlaced"
+            }
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn ihdr_chunk(width: u32, height: u32, bit_depth: u8, color_type: u8, interlace: u8) -> Chunk {
+        let data: Vec<u8> = width
+            .to_be_bytes()
+            .iter()
+            .chain(height.to_be_bytes().iter())
+            .chain([bit_depth, color_type, 0, 0, interlace].iter())
+            .copied()
+            .collect();
+        Chunk::new(ChunkType::from_str("IHDR").unwrap(), data)
+    }
+
+    #[test]
+    fn test_ihdr_from_chunk() {
+        let ihdr = Ihdr::try_from(&ihdr_chunk(50, 40, 8, 6, 0)).unwrap();
+        assert_eq!(ihdr.width, 50);
+        assert_eq!(ihdr.height, 40);
+        assert_eq!(ihdr.channels(), 4);
+        assert_eq!(ihdr.bytes_per_pixel(), 4);
+        assert_eq!(ihdr.scanline_len(ihdr.width), 200);
+        assert_eq!(ihdr.raw_len(), 40 * 201);
+    }
+
+    #[test]
+    fn test_ihdr_as_bytes() {
+        let chunk = ihdr_chunk(50, 40, 16, 2, 1);
+        let ihdr = Ihdr::try_from(&chunk).unwrap();
+        assert_eq!(ihdr.as_bytes(), chunk.data());
+    }
+
+    #[test]
+    fn test_ihdr_sub_byte_pixels() {
+        let ihdr = Ihdr::try_from(&ihdr_chunk(5, 1, 2, 0, 0)).unwrap();
+        assert_eq!(ihdr.bytes_per_pixel(), 1);
+        assert_eq!(ihdr.scanline_len(ihdr.width), 2);
+    }
+
+    #[test]
+    fn test_ihdr_invalid_depth() {
+        assert!(Ihdr::try_from(&ihdr_chunk(1, 1, 4, 2, 0)).is_err());
+    }
+
+    #[test]
+    fn test_ihdr_too_large() {
+        assert!(Ihdr::try_from(&ihdr_chunk(1 << 31, 1, 8, 0, 1)).is_err());
+        assert!(Ihdr::try_from(&ihdr_chunk(1, u32::MAX, 8, 0, 1)).is_err());
+        let ihdr = Ihdr {
+            width: u32::MAX,
+            height: 1,
+            bit_depth: 8,
+            color_type: 0,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 1,
+        };
+        assert_eq!(ihdr.passes()[0], (u32::MAX / 8 + 1, 1));
+    }
+
+    #[test]
+    fn test_ihdr_adam7_passes() {
+        let ihdr = Ihdr::try_from(&ihdr_chunk(3, 3, 8, 0, 1)).unwrap();
+        assert_eq!(ihdr.passes(), vec![(1, 1), (1, 1), (2, 1), (1, 2), (3, 1)]);
+        assert_eq!(ihdr.raw_len(), 2 + 2 + 3 + 4 + 4);
+    }
+}
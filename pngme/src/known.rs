@@ -0,0 +1,459 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::ihdr::Ihdr;
+use crate::zlib;
+use crate::Result;
+
+/// A chunk whose layout is defined by the PNG spec, decoded into its fields.
+/// http://www.libpng.org/pub/png/spec/1.2/PNG-Chunks.html
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum KnownChunk {
+    Ihdr(Ihdr),
+    Plte(Vec<[u8; 3]>),
+    Iend,
+    Text {
+        keyword: String,
+        text: String,
+    },
+    ZText {
+        keyword: String,
+        text: String,
+    },
+    IText {
+        keyword: String,
+        compressed: bool,
+        language_tag: String,
+        translated_keyword: String,
+        text: String,
+    },
+    Iccp {
+        name: String,
+        #[serde(with = "base64_bytes")]
+        profile: Vec<u8>,
+    },
+    Gama(u32),
+    Chrm {
+        white_point: (u32, u32),
+        red: (u32, u32),
+        green: (u32, u32),
+        blue: (u32, u32),
+    },
+    Srgb(u8),
+    Phys {
+        x: u32,
+        y: u32,
+        unit: u8,
+    },
+    Time {
+        year: u16,
+        month: u8,
+        day: u8,
+        hour: u8,
+        minute: u8,
+        second: u8,
+    },
+}
+
+impl KnownChunk {
+    /// Decodes `chunk` if its type is one of the chunks this module knows about.
+    /// Returns `Ok(None)` for any other chunk type.
+    pub fn decode(chunk: &Chunk) -> Result<Option<KnownChunk>> {
+        let data = chunk.data();
+        let known = match &chunk.chunk_type().bytes() {
+            b"IHDR" => KnownChunk::Ihdr(Ihdr::try_from(chunk)?),
+            b"PLTE" => {
+                if !data.len().is_multiple_of(3) {
+                    return Err("PLTE length must be divisible by 3".into());
+                }
+                KnownChunk::Plte(data.chunks(3).map(|x| [x[0], x[1], x[2]]).collect())
+            }
+            b"IEND" => KnownChunk::Iend,
+            b"tEXt" => {
+                let (keyword, text) = split_null(data)?;
+                KnownChunk::Text {
+                    keyword: latin1(keyword),
+                    text: latin1(text),
+                }
+            }
+            b"zTXt" => {
+                let (keyword, rest) = split_null(data)?;
+                if rest.first() != Some(&0) {
+                    return Err("unknown zTXt compression method".into());
+                }
+                KnownChunk::ZText {
+                    keyword: latin1(keyword),
+                    text: latin1(&zlib::decompress(&rest[1..])?),
+                }
+            }
+            b"iTXt" => {
+                let (keyword, rest) = split_null(data)?;
+                if rest.len() < 2 {
+                    return Err("iTXt is too short".into());
+                }
+                let compressed = rest[0] == 1;
+                let (language_tag, rest) = split_null(&rest[2..])?;
+                let (translated_keyword, text) = split_null(rest)?;
+                let text = if compressed {
+                    zlib::decompress(text)?
+                } else {
+                    text.to_vec()
+                };
+                KnownChunk::IText {
+                    keyword: latin1(keyword),
+                    compressed,
+                    language_tag: latin1(language_tag),
+                    translated_keyword: String::from_utf8(translated_keyword.to_vec())?,
+                    text: String::from_utf8(text)?,
+                }
+            }
+            b"iCCP" => {
+                let (name, rest) = split_null(data)?;
+                if rest.first() != Some(&0) {
+                    return Err("unknown iCCP compression method".into());
+                }
+                KnownChunk::Iccp {
+                    name: latin1(name),
+                    profile: zlib::decompress(&rest[1..])?,
+                }
+            }
+            b"gAMA" => KnownChunk::Gama(read_u32(data, 0)?),
+            b"cHRM" => KnownChunk::Chrm {
+                white_point: (read_u32(data, 0)?, read_u32(data, 4)?),
+                red: (read_u32(data, 8)?, read_u32(data, 12)?),
+                green: (read_u32(data, 16)?, read_u32(data, 20)?),
+                blue: (read_u32(data, 24)?, read_u32(data, 28)?),
+            },
+            b"sRGB" => match data {
+                [intent] => KnownChunk::Srgb(*intent),
+                _ => return Err("sRGB must be 1 byte long".into()),
+            },
+            b"pHYs" => KnownChunk::Phys {
+                x: read_u32(data, 0)?,
+                y: read_u32(data, 4)?,
+                unit: *data.get(8).ok_or("pHYs is too short")?,
+            },
+            b"tIME" => match data {
+                [y0, y1, month, day, hour, minute, second] => KnownChunk::Time {
+                    year: u16::from_be_bytes([*y0, *y1]),
+                    month: *month,
+                    day: *day,
+                    hour: *hour,
+                    minute: *minute,
+                    second: *second,
+                },
+                _ => return Err("tIME must be 7 bytes long".into()),
+            },
+            _ => return Ok(None),
+        };
+        Ok(Some(known))
+    }
+
+    /// The chunk type this decoded chunk is stored as.
+    pub fn chunk_type(&self) -> ChunkType {
+        let bytes = match self {
+            KnownChunk::Ihdr(_) => *b"IHDR",
+            KnownChunk::Plte(_) => *b"PLTE",
+            KnownChunk::Iend => *b"IEND",
+            KnownChunk::Text { .. } => *b"tEXt",
+            KnownChunk::ZText { .. } => *b"zTXt",
+            KnownChunk::IText { .. } => *b"iTXt",
+            KnownChunk::Iccp { .. } => *b"iCCP",
+            KnownChunk::Gama(_) => *b"gAMA",
+            KnownChunk::Chrm { .. } => *b"cHRM",
+            KnownChunk::Srgb(_) => *b"sRGB",
+            KnownChunk::Phys { .. } => *b"pHYs",
+            KnownChunk::Time { .. } => *b"tIME",
+        };
+        ChunkType::try_from(bytes).unwrap()
+    }
+
+    /// Encodes the fields back into chunk data. Compressed data is deflated again,
+    /// so its bytes may differ from the chunk it was decoded from.
+    pub fn encode(&self) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        match self {
+            KnownChunk::Ihdr(ihdr) => data = ihdr.as_bytes(),
+            KnownChunk::Plte(entries) => data = entries.concat(),
+            KnownChunk::Iend => (),
+            KnownChunk::Text { keyword, text } => {
+                data.extend(to_latin1(keyword)?);
+                data.push(0);
+                data.extend(to_latin1(text)?);
+            }
+            KnownChunk::ZText { keyword, text } => {
+                data.extend(to_latin1(keyword)?);
+                data.extend([0, 0]);
+                data.extend(zlib::compress(&to_latin1(text)?, 9)?);
+            }
+            KnownChunk::IText {
+                keyword,
+                compressed,
+                language_tag,
+                translated_keyword,
+                text,
+            } => {
+                data.extend(to_latin1(keyword)?);
+                data.extend([0, *compressed as u8, 0]);
+                data.extend(to_latin1(language_tag)?);
+                data.push(0);
+                data.extend(translated_keyword.as_bytes());
+                data.push(0);
+                if *compressed {
+                    data.extend(zlib::compress(text.as_bytes(), 9)?);
+                } else {
+                    data.extend(text.as_bytes());
+                }
+            }
+            KnownChunk::Iccp { name, profile } => {
+                data.extend(to_latin1(name)?);
+                data.extend([0, 0]);
+                data.extend(zlib::compress(profile, 9)?);
+            }
+            KnownChunk::Gama(gamma) => data.extend(gamma.to_be_bytes()),
+            KnownChunk::Chrm {
+                white_point,
+                red,
+                green,
+                blue,
+            } => {
+                for (x, y) in [white_point, red, green, blue] {
+                    data.extend(x.to_be_bytes());
+                    data.extend(y.to_be_bytes());
+                }
+            }
+            KnownChunk::Srgb(intent) => data.push(*intent),
+            KnownChunk::Phys { x, y, unit } => {
+                data.extend(x.to_be_bytes());
+                data.extend(y.to_be_bytes());
+                data.push(*unit);
+            }
+            KnownChunk::Time {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+            } => {
+                data.extend(year.to_be_bytes());
+                data.extend([*month, *day, *hour, *minute, *second]);
+            }
+        }
+        Ok(data)
+    }
+
+    /// Encodes this decoded chunk into a `Chunk`.
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        Ok(Chunk::new(self.chunk_type(), self.encode()?))
+    }
+}
+
+fn split_null(data: &[u8]) -> Result<(&[u8], &[u8])> {
+    match data.iter().position(|&x| x == 0) {
+        Some(idx) => Ok((&data[..idx], &data[idx + 1..])),
+        None => Err("missing null separator".into()),
+    }
+}
+
+fn latin1(bytes: &[u8]) -> String {
+    bytes.iter().map(|&x| x as char).collect()
+}
+
+fn to_latin1(s: &str) -> Result<Vec<u8>> {
+    s.chars()
+        .map(|x| u8::try_from(x as u32).map_err(|_| format!("{:?} is not Latin-1", x).into()))
+        .collect()
+}
+
+fn read_u32(data: &[u8], at: usize) -> Result<u32> {
+    match data.get(at..at + 4) {
+        Some(x) => Ok(u32::from_be_bytes([x[0], x[1], x[2], x[3]])),
+        None => Err("chunk data is too short".into()),
+    }
+}
+
+/// Stores binary fields as base64 strings, like the raw chunk data in a manifest.
+mod base64_bytes {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&STANDARD.encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        STANDARD.decode(s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Formats a value stored in units of 1/100000, as used by gAMA and cHRM.
+fn fixed(value: u32) -> String {
+    format!("{:.5}", value as f64 / 100_000.0)
+}
+
+impl fmt::Display for KnownChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            KnownChunk::Ihdr(ihdr) => write!(f, "{}", ihdr),
+            KnownChunk::Plte(entries) => write!(f, "{} palette entries", entries.len()),
+            KnownChunk::Iend => write!(f, "end of image"),
+            KnownChunk::Text { keyword, text } | KnownChunk::ZText { keyword, text } => {
+                write!(f, "{}: {}", keyword, text)
+            }
+            KnownChunk::IText {
+                keyword,
+                language_tag,
+                translated_keyword,
+                text,
+                ..
+            } => write!(
+                f,
+                "{} [{}] ({}): {}",
+                keyword, language_tag, translated_keyword, text
+            ),
+            KnownChunk::Iccp { name, profile } => {
+                write!(f, "{}: {} byte ICC profile", name, profile.len())
+            }
+            KnownChunk::Gama(gamma) => write!(f, "gamma {}", fixed(*gamma)),
+            KnownChunk::Chrm {
+                white_point,
+                red,
+                green,
+                blue,
+            } => write!(
+                f,
+                "white ({}, {}), red ({}, {}), green ({}, {}), blue ({}, {})",
+                fixed(white_point.0),
+                fixed(white_point.1),
+                fixed(red.0),
+                fixed(red.1),
+                fixed(green.0),
+                fixed(green.1),
+                fixed(blue.0),
+                fixed(blue.1)
+            ),
+            KnownChunk::Srgb(intent) => {
+                let name = match intent {
+                    0 => "perceptual",
+                    1 => "relative colorimetric",
+                    2 => "saturation",
+                    3 => "absolute colorimetric",
+                    _ => "unknown",
+                };
+                write!(f, "rendering intent {} ({})", intent, name)
+            }
+            KnownChunk::Phys { x, y, unit } => write!(
+                f,
+                "{}x{} pixels per {}",
+                x,
+                y,
+                if *unit == 1 { "metre" } else { "unit" }
+            ),
+            KnownChunk::Time {
+                year,
+                month,
+                day,
+                hour,
+                minute,
+                second,
+            } => write!(
+                f,
+                "{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+                year, month, day, hour, minute, second
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::chunk;
+
+    #[test]
+    fn test_decode_text() {
+        let known = KnownChunk::decode(&chunk("tEXt", b"Title\0Dice \xe9")).unwrap();
+        assert_eq!(
+            known,
+            Some(KnownChunk::Text {
+                keyword: "Title".to_string(),
+                text: "Dice é".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_decode_ztxt() {
+        let mut data = b"Comment\0\0".to_vec();
+        data.extend(zlib::compress(b"compressed text", 9).unwrap());
+        let known = KnownChunk::decode(&chunk("zTXt", &data)).unwrap().unwrap();
+        assert_eq!(known.to_string(), "Comment: compressed text");
+    }
+
+    #[test]
+    fn test_decode_iccp() {
+        let mut data = b"sRGB IEC61966-2.1\0\0".to_vec();
+        data.extend(zlib::compress(&[1, 2, 3], 9).unwrap());
+        let known = KnownChunk::decode(&chunk("iCCP", &data)).unwrap().unwrap();
+        assert_eq!(known.to_string(), "sRGB IEC61966-2.1: 3 byte ICC profile");
+        assert_eq!(known.encode().unwrap(), data);
+
+        let json = serde_json::to_string(&known).unwrap();
+        assert_eq!(
+            json,
+            r#"{"Iccp":{"name":"sRGB IEC61966-2.1","profile":"AQID"}}"#
+        );
+        assert_eq!(serde_json::from_str::<KnownChunk>(&json).unwrap(), known);
+    }
+
+    #[test]
+    fn test_decode_time() {
+        let known = KnownChunk::decode(&chunk("tIME", &[7, 230, 1, 2, 3, 4, 5]))
+            .unwrap()
+            .unwrap();
+        assert_eq!(known.to_string(), "2022-01-02 03:04:05 UTC");
+    }
+
+    #[test]
+    fn test_encode_roundtrip() {
+        let chunks = [
+            chunk("tEXt", b"Title\0Dice \xe9"),
+            chunk("iTXt", "Title\0\0\0en\0Titel\0W\u{fc}rfel".as_bytes()),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("pHYs", &[0, 0, 14, 194, 0, 0, 14, 194, 1]),
+            chunk("tIME", &[7, 230, 1, 2, 3, 4, 5]),
+            chunk("IEND", &[]),
+        ];
+        for chunk in chunks.iter() {
+            let known = KnownChunk::decode(chunk).unwrap().unwrap();
+            assert_eq!(known.to_chunk().unwrap().as_bytes(), chunk.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_encode_not_latin1() {
+        let known = KnownChunk::Text {
+            keyword: "Title".to_string(),
+            text: "\u{263a}".to_string(),
+        };
+        assert!(known.encode().is_err());
+    }
+
+    #[test]
+    fn test_decode_unknown() {
+        assert!(KnownChunk::decode(&chunk("RuSt", b"hi")).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_decode_invalid() {
+        assert!(KnownChunk::decode(&chunk("gAMA", &[0, 1])).is_err());
+        assert!(KnownChunk::decode(&chunk("tEXt", b"no separator")).is_err());
+    }
+}
@@ -0,0 +1,33 @@
+pub mod carrier;
+pub mod chunk;
+pub mod chunk_type;
+pub mod color;
+pub mod container;
+pub mod ecc;
+pub mod editor;
+pub mod exif;
+pub mod ffi;
+pub mod filter;
+mod gf256;
+pub mod hex;
+pub mod ihdr;
+pub mod known;
+pub mod manifest;
+pub mod message;
+pub mod optimize;
+pub mod png;
+pub mod provenance;
+pub mod recipient;
+pub mod riff;
+pub mod scan;
+pub mod schema;
+pub mod shamir;
+#[cfg(feature = "async")]
+pub mod stream;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+pub mod watch;
+pub mod zlib;
+
+pub type Error = Box<dyn std::error::Error>;
+pub type Result<T> = std::result::Result<T, Error>;
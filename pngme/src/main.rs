@@ -1,18 +1,34 @@
 mod args;
-mod chunk;
-mod chunk_type;
-mod png;
+#[cfg(feature = "tui")]
+mod tui;
 
-pub type Error = Box<dyn std::error::Error>;
-pub type Result<T> = std::result::Result<T, Error>;
+pub use pngme::{Error, Result};
 
 fn main() -> Result<()> {
     let matches = args::get_matches();
     match matches.subcommand() {
         Some(("encode", encode_args)) => args::handle_encode(encode_args),
         Some(("decode", decode_args)) => args::handle_decode(decode_args),
+        Some(("keygen", keygen_args)) => args::handle_keygen(keygen_args),
+        Some(("list", list_args)) => args::handle_list(list_args),
         Some(("remove", remove_args)) => args::handle_remove(remove_args),
         Some(("print", print_args)) => args::handle_print(print_args),
+        Some(("scan", scan_args)) => args::handle_scan(scan_args),
+        Some(("history", history_args)) => args::handle_history(history_args),
+        Some(("optimize", optimize_args)) => args::handle_optimize(optimize_args),
+        Some(("reidat", reidat_args)) => args::handle_reidat(reidat_args),
+        Some(("inspect", inspect_args)) => args::handle_inspect(inspect_args),
+        Some(("export", export_args)) => args::handle_export(export_args),
+        Some(("import", import_args)) => args::handle_import(import_args),
+        Some(("transplant", transplant_args)) => args::handle_transplant(transplant_args),
+        Some(("dump", dump_args)) => args::handle_dump(dump_args),
+        Some(("build", build_args)) => args::handle_build(build_args),
+        Some(("exif", exif_args)) => args::handle_exif(exif_args),
+        Some(("color", color_args)) => args::handle_color(color_args),
+        #[cfg(target_os = "linux")]
+        Some(("watch", watch_args)) => args::handle_watch(watch_args),
+        #[cfg(feature = "tui")]
+        Some(("edit", edit_args)) => args::handle_edit(edit_args),
         _ => (),
     }
     Ok(())
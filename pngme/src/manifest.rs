@@ -0,0 +1,221 @@
+use std::str::FromStr;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::container::ContainerKind;
+use crate::known::KnownChunk;
+use crate::png::Png;
+use crate::Result;
+
+/// A text description of a whole `Png`, listing every chunk in file order.
+/// Turning a `Png` into a `Manifest` and back reproduces it byte for byte.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub kind: ContainerKind,
+    pub chunks: Vec<ChunkEntry>,
+    /// The bytes after the end chunk, as base64.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub trailer: String,
+}
+
+/// A single chunk in a `Manifest`. Chunks this crate can decode, and encode
+/// back to the exact same bytes, are described by their `fields`. All other
+/// chunks store their raw `data` as base64.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChunkEntry {
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    pub critical: bool,
+    pub public: bool,
+    pub safe_to_copy: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fields: Option<KnownChunk>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub data: Option<String>,
+}
+
+impl ChunkEntry {
+    /// Describes `chunk`, preferring decoded fields over raw data.
+    pub fn from_chunk(chunk: &Chunk) -> ChunkEntry {
+        let chunk_type = chunk.chunk_type();
+        let fields = match KnownChunk::decode(chunk) {
+            Ok(Some(known)) if known.encode().ok().as_deref() == Some(chunk.data()) => Some(known),
+            _ => None,
+        };
+        let data = match fields {
+            Some(_) => None,
+            None => Some(STANDARD.encode(chunk.data())),
+        };
+        ChunkEntry {
+            chunk_type: chunk_type.to_string(),
+            critical: chunk_type.is_critical(),
+            public: chunk_type.is_public(),
+            safe_to_copy: chunk_type.is_safe_to_copy(),
+            fields,
+            data,
+        }
+    }
+
+    /// Rebuilds the chunk described by this entry. The flags must agree with the
+    /// chunk type, since they are derived from it.
+    pub fn to_chunk(&self) -> Result<Chunk> {
+        let chunk_type = ChunkType::from_str(&self.chunk_type)?;
+        if (
+            chunk_type.is_critical(),
+            chunk_type.is_public(),
+            chunk_type.is_safe_to_copy(),
+        ) != (self.critical, self.public, self.safe_to_copy)
+        {
+            return Err(format!("flags do not match chunk type {}", chunk_type).into());
+        }
+        let data = match (&self.fields, &self.data) {
+            (Some(fields), None) => {
+                if fields.chunk_type() != chunk_type {
+                    return Err(format!(
+                        "fields describe {}, not {}",
+                        fields.chunk_type(),
+                        chunk_type
+                    )
+                    .into());
+                }
+                fields.encode()?
+            }
+            (None, Some(data)) => STANDARD.decode(data)?,
+            _ => return Err(format!("{} needs exactly one of fields or data", chunk_type).into()),
+        };
+        Ok(Chunk::new(chunk_type, data))
+    }
+}
+
+impl Manifest {
+    /// Describes every chunk of `png`.
+    pub fn from_png(png: &Png) -> Manifest {
+        Manifest {
+            kind: png.kind(),
+            chunks: png.chunks().iter().map(ChunkEntry::from_chunk).collect(),
+            trailer: STANDARD.encode(png.trailer()),
+        }
+    }
+
+    /// Rebuilds the `Png` described by this manifest.
+    pub fn to_png(&self) -> Result<Png> {
+        let chunks = self
+            .chunks
+            .iter()
+            .map(|x| x.to_chunk())
+            .collect::<Result<Vec<Chunk>>>()?;
+        let mut png = Png::from_chunks_with_kind(self.kind, chunks);
+        png.set_trailer(STANDARD.decode(&self.trailer)?);
+        Ok(png)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        Ok(serde_json::to_string_pretty(self)?)
+    }
+
+    pub fn from_json(s: &str) -> Result<Manifest> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    pub fn to_yaml(&self) -> Result<String> {
+        Ok(serde_yaml::to_string(self)?)
+    }
+
+    pub fn from_yaml(s: &str) -> Result<Manifest> {
+        Ok(serde_yaml::from_str(s)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::chunk;
+    use crate::zlib;
+
+    fn testing_png() -> Png {
+        let mut ztxt = b"Comment\0\0".to_vec();
+        ztxt.extend(zlib::compress(b"compressed with level 1", 1).unwrap());
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("tEXt", b"Title\0Dice"),
+            chunk("zTXt", &ztxt),
+            chunk("IDAT", &zlib::compress(&[0, 0], 9).unwrap()),
+            chunk("RuSt", b"This is where your secret message will be!"),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    #[test]
+    fn test_manifest_json_roundtrip() {
+        let png = testing_png();
+        let json = Manifest::from_png(&png).to_json().unwrap();
+        let rebuilt = Manifest::from_json(&json).unwrap().to_png().unwrap();
+        assert_eq!(rebuilt.as_bytes(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_manifest_yaml_roundtrip() {
+        let png = testing_png();
+        let yaml = Manifest::from_png(&png).to_yaml().unwrap();
+        let rebuilt = Manifest::from_yaml(&yaml).unwrap().to_png().unwrap();
+        assert_eq!(rebuilt.as_bytes(), png.as_bytes());
+    }
+
+    #[test]
+    fn test_manifest_trailer_roundtrip() {
+        let mut png = testing_png();
+        png.set_trailer(b"PK\x03\x04 zip archive follows".to_vec());
+        let json = Manifest::from_png(&png).to_json().unwrap();
+        let rebuilt = Manifest::from_json(&json).unwrap().to_png().unwrap();
+        assert_eq!(rebuilt.as_bytes(), png.as_bytes());
+        assert!(!Manifest::from_png(&testing_png())
+            .to_json()
+            .unwrap()
+            .contains("trailer"));
+    }
+
+    #[test]
+    fn test_manifest_opaque_chunks() {
+        let manifest = Manifest::from_png(&testing_png());
+        let opaque: Vec<&str> = manifest
+            .chunks
+            .iter()
+            .filter(|x| x.data.is_some())
+            .map(|x| &x.chunk_type[..])
+            .collect();
+        assert_eq!(opaque, vec!["zTXt", "IDAT", "RuSt"]);
+    }
+
+    #[test]
+    fn test_manifest_edit_text() {
+        let mut manifest = Manifest::from_png(&testing_png());
+        manifest.chunks[2].fields = Some(KnownChunk::Text {
+            keyword: "Title".to_string(),
+            text: "Edited".to_string(),
+        });
+        let png = manifest.to_png().unwrap();
+        assert_eq!(png.chunk_by_type("tEXt").unwrap().data(), b"Title\0Edited");
+    }
+
+    #[test]
+    fn test_manifest_bad_flags() {
+        let mut manifest = Manifest::from_png(&testing_png());
+        manifest.chunks[0].critical = false;
+        assert!(manifest.to_png().is_err());
+    }
+
+    #[test]
+    fn test_manifest_keeps_color_conflicts() {
+        let mut png = testing_png();
+        png.insert_chunk(chunk("sRGB", &[0]));
+        png.insert_chunk(chunk("iCCP", b"Gray\0\0x"));
+        let rebuilt = Manifest::from_png(&png).to_png().unwrap();
+        assert_eq!(rebuilt.as_bytes(), png.as_bytes());
+    }
+}
@@ -0,0 +1,288 @@
+use std::convert::TryFrom;
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::png::Png;
+use crate::{Error, Result};
+
+/// A labelled message embedded in a chunk. Several messages can share a chunk
+/// type and still be told apart by their label.
+///
+/// The chunk data holds the following fields in order:
+/// 1. The magic bytes `PMSG` *(4 bytes)*
+/// 2. The format version *(1 byte)*
+/// 3. Flags; bit 0 is set if an expiry is present *(1 byte)*
+/// 4. Creation time in seconds since the unix epoch *(8 bytes)*
+/// 5. Expiry time in seconds since the unix epoch, if flagged *(8 bytes)*
+/// 6. Length of the label followed by the UTF-8 label *(1 + n bytes)*
+/// 7. Length of the content type followed by the UTF-8 content type *(1 + n bytes)*
+/// 8. The payload itself *(remaining bytes)*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Message {
+    pub label: String,
+    pub created: u64,
+    pub expires: Option<u64>,
+    pub content_type: String,
+    pub payload: Vec<u8>,
+}
+
+impl Message {
+    const MAGIC: [u8; 4] = *b"PMSG";
+    const VERSION: u8 = 1;
+    const FLAG_EXPIRES: u8 = 1;
+
+    /// Creates a message labelled `label`, created now and never expiring.
+    pub fn new(label: &str, content_type: &str, payload: Vec<u8>) -> Message {
+        Self {
+            label: label.to_string(),
+            created: now(),
+            expires: None,
+            content_type: content_type.to_string(),
+            payload,
+        }
+    }
+
+    /// Returns true if the expiry of this message is at or before `at`.
+    pub fn is_expired(&self, at: u64) -> bool {
+        self.expires.is_some_and(|x| x <= at)
+    }
+
+    /// Returns the payload as a `String`. This function will return an error if the
+    /// payload is not valid UTF-8.
+    pub fn payload_as_string(&self) -> Result<String> {
+        Ok(String::from_utf8(self.payload.clone())?)
+    }
+
+    /// Returns this message as the chunk data described above.
+    pub fn as_bytes(&self) -> Result<Vec<u8>> {
+        if self.label.len() > 255 || self.content_type.len() > 255 {
+            return Err("label and content type must be at most 255 bytes".into());
+        }
+        let mut bytes = Self::MAGIC.to_vec();
+        bytes.push(Self::VERSION);
+        bytes.push(if self.expires.is_some() {
+            Self::FLAG_EXPIRES
+        } else {
+            0
+        });
+        bytes.extend(self.created.to_be_bytes());
+        if let Some(expires) = self.expires {
+            bytes.extend(expires.to_be_bytes());
+        }
+        for field in [&self.label, &self.content_type] {
+            bytes.push(field.len() as u8);
+            bytes.extend(field.as_bytes());
+        }
+        bytes.extend(&self.payload);
+        Ok(bytes)
+    }
+
+    /// Returns true if `bytes` start like a message, so that other chunk data can
+    /// be told apart without reporting an error.
+    pub fn is_message(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::MAGIC)
+    }
+}
+
+impl TryFrom<&[u8]> for Message {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !Self::is_message(bytes) {
+            return Err("chunk data is not a message".into());
+        }
+        let mut reader = Reader { bytes, pos: 4 };
+        let version = reader.u8()?;
+        if version != Self::VERSION {
+            return Err(format!("unsupported message version {}", version).into());
+        }
+        let flags = reader.u8()?;
+        let created = reader.u64()?;
+        let expires = if flags & Self::FLAG_EXPIRES != 0 {
+            Some(reader.u64()?)
+        } else {
+            None
+        };
+        let label = reader.string()?;
+        let content_type = reader.string()?;
+        Ok(Self {
+            label,
+            created,
+            expires,
+            content_type,
+            payload: bytes[reader.pos..].to_vec(),
+        })
+    }
+}
+
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} ({}, created {}",
+            self.label,
+            self.content_type,
+            format_timestamp(self.created)
+        )?;
+        if let Some(expires) = self.expires {
+            write!(f, ", expires {}", format_timestamp(expires))?;
+        }
+        write!(f, ", {} bytes)", self.payload.len())
+    }
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        match self.bytes.get(self.pos..self.pos + len) {
+            Some(x) => {
+                self.pos += len;
+                Ok(x)
+            }
+            None => Err("message is truncated".into()),
+        }
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u64(&mut self) -> Result<u64> {
+        let mut buffer = [0u8; 8];
+        buffer.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(buffer))
+    }
+
+    fn string(&mut self) -> Result<String> {
+        let len = self.u8()? as usize;
+        Ok(String::from_utf8(self.take(len)?.to_vec())?)
+    }
+}
+
+/// Lists every message stored in `png` together with the index of its chunk.
+/// Chunks that don't hold a message are skipped.
+pub fn find_messages(png: &Png) -> Vec<(usize, Message)> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .filter_map(|(i, x)| Message::try_from(x.data()).ok().map(|m| (i, m)))
+        .collect()
+}
+
+/// Seconds since the unix epoch.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|x| x.as_secs())
+        .unwrap_or(0)
+}
+
+/// Seconds since the unix epoch, from the JavaScript clock. `SystemTime` is
+/// not available on `wasm32-unknown-unknown`, so without the `wasm` feature
+/// there is no clock and this is 0.
+#[cfg(target_arch = "wasm32")]
+pub fn now() -> u64 {
+    #[cfg(feature = "wasm")]
+    return (crate::wasm::date_now() / 1000.0) as u64;
+    #[cfg(not(feature = "wasm"))]
+    0
+}
+
+/// Formats seconds since the unix epoch as an RFC 3339 UTC timestamp.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+    // Converts days since the epoch to a civil date, after Howard Hinnant's
+    // `civil_from_days`.
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rem / 3600,
+        rem % 3600 / 60,
+        rem % 60
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use std::str::FromStr;
+
+    fn testing_message() -> Message {
+        Message {
+            label: "deploy-note".to_string(),
+            created: 1_650_000_000,
+            expires: Some(1_650_086_400),
+            content_type: "text/plain".to_string(),
+            payload: b"This is where your secret message will be!".to_vec(),
+        }
+    }
+
+    #[test]
+    fn test_message_roundtrip() {
+        let message = testing_message();
+        let bytes = message.as_bytes().unwrap();
+        assert_eq!(Message::try_from(&bytes[..]).unwrap(), message);
+
+        let message = Message {
+            expires: None,
+            ..testing_message()
+        };
+        let bytes = message.as_bytes().unwrap();
+        assert_eq!(Message::try_from(&bytes[..]).unwrap(), message);
+    }
+
+    #[test]
+    fn test_message_expiry() {
+        let message = testing_message();
+        assert!(!message.is_expired(1_650_000_001));
+        assert!(message.is_expired(1_650_086_400));
+        assert!(!Message::new("x", "text/plain", vec![]).is_expired(u64::MAX));
+    }
+
+    #[test]
+    fn test_message_truncated() {
+        let bytes = testing_message().as_bytes().unwrap();
+        assert!(Message::try_from(&bytes[..20]).is_err());
+        assert!(Message::try_from(&b"plain message"[..]).is_err());
+    }
+
+    #[test]
+    fn test_find_messages() {
+        let chunk_type = ChunkType::from_str("ruSt").unwrap();
+        let png = Png::from_chunks(vec![
+            Chunk::new(chunk_type.clone(), b"plain message".to_vec()),
+            Chunk::new(chunk_type, testing_message().as_bytes().unwrap()),
+        ]);
+        let messages = find_messages(&png);
+        assert_eq!(messages.len(), 1);
+        assert_eq!(messages[0].0, 1);
+        assert_eq!(messages[0].1.label, "deploy-note");
+    }
+
+    #[test]
+    fn test_format_timestamp() {
+        assert_eq!(format_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_timestamp(1_650_000_000), "2022-04-15T05:20:00Z");
+        assert_eq!(format_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+    }
+}
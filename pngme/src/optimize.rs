@@ -0,0 +1,189 @@
+use std::str::FromStr;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::filter::{self, FilterStrategy};
+use crate::png::Png;
+use crate::zlib;
+use crate::Result;
+
+/// Ancillary chunks that change how the pixels are displayed. These are kept
+/// even when stripping, everything else that isn't critical is dropped.
+const RENDERING_CHUNKS: [&str; 7] = ["tRNS", "gAMA", "cHRM", "sRGB", "iCCP", "sBIT", "pHYs"];
+
+/// Deflate levels tried for every filter strategy.
+const LEVELS: [u32; 4] = [4, 6, 8, 9];
+
+/// The outcome of optimizing a single `Png`.
+pub struct Optimized {
+    pub png: Png,
+    /// The filter strategy and deflate level of the new image data, `None`
+    /// if recompressing didn't beat the original data and it was kept.
+    pub recompressed: Option<(FilterStrategy, u32)>,
+    pub original_size: usize,
+    pub optimized_size: usize,
+}
+
+impl Optimized {
+    /// Number of bytes saved compared to the original file. Zero if the
+    /// optimized file is not smaller.
+    pub fn bytes_saved(&self) -> usize {
+        self.original_size.saturating_sub(self.optimized_size)
+    }
+}
+
+/// Returns true if `chunk` can be dropped without changing the decoded image.
+pub fn is_removable(chunk: &Chunk) -> bool {
+    let chunk_type = chunk.chunk_type();
+    !chunk_type.is_critical() && !RENDERING_CHUNKS.contains(&&chunk_type.to_string()[..])
+}
+
+/// Recompresses the image data of `png`, trying every filter strategy with
+/// several deflate levels, and merges the result into a single `IDAT` chunk.
+/// Removable ancillary chunks are dropped if `strip` is set.
+///
+/// The original image data is a candidate too, so the `IDAT` data never grows.
+/// The smallest candidate is decoded again and compared against the original
+/// pixels before it is returned, so the result is always lossless.
+pub fn optimize(png: &Png, strip: bool) -> Result<Optimized> {
+    let ihdr = png.ihdr()?;
+    let original = png.idat_data();
+    let data = zlib::decompress_with_limit(&original, ihdr.raw_len())?;
+    let raw = filter::unfilter(&ihdr, &data)?;
+
+    let (mut idat, mut recompressed) = (original, None);
+    for strategy in FilterStrategy::all() {
+        let filtered = filter::filter(&ihdr, &raw, strategy);
+        for &level in LEVELS.iter() {
+            let compressed = zlib::compress(&filtered, level)?;
+            if compressed.len() < idat.len() {
+                idat = compressed;
+                recompressed = Some((strategy, level));
+            }
+        }
+    }
+
+    if filter::unfilter(&ihdr, &zlib::decompress_with_limit(&idat, ihdr.raw_len())?)? != raw {
+        return Err("optimized image data does not decode to the original pixels".into());
+    }
+
+    let mut chunks = Vec::new();
+    let mut idat = Some(Chunk::new(ChunkType::from_str("IDAT")?, idat));
+    for chunk in png.chunks() {
+        if chunk.chunk_type().bytes() == *b"IDAT" {
+            if let Some(idat) = idat.take() {
+                chunks.push(idat);
+            }
+        } else if !(strip && is_removable(chunk)) {
+            chunks.push(chunk.clone());
+        }
+    }
+    // Keep the header and any trailing data, only the chunks change.
+    let mut optimized = png.clone();
+    *optimized.chunks_mut() = chunks;
+
+    Ok(Optimized {
+        original_size: png.as_bytes().len(),
+        optimized_size: optimized.as_bytes().len(),
+        png: optimized,
+        recompressed,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::FilterType;
+    use crate::ihdr::Ihdr;
+    use crate::png::tests::chunk;
+
+    fn testing_png() -> Png {
+        let ihdr = Ihdr {
+            width: 16,
+            height: 16,
+            bit_depth: 8,
+            color_type: 2,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        };
+        let raw: Vec<u8> = (0..16 * 16 * 3).map(|i| (i % 48) as u8).collect();
+        let filtered = filter::filter(&ihdr, &raw, FilterStrategy::Fixed(FilterType::None));
+        let idat = zlib::compress(&filtered, 0).unwrap();
+        let (first, second) = idat.split_at(idat.len() / 2);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&16u32.to_be_bytes());
+        header.extend_from_slice(&16u32.to_be_bytes());
+        header.extend_from_slice(&[8, 2, 0, 0, 0]);
+
+        Png::from_chunks(vec![
+            chunk("IHDR", &header),
+            chunk("gAMA", &[0, 0, 177, 143]),
+            chunk("tEXt", b"Comment\0hello"),
+            chunk("IDAT", first),
+            chunk("IDAT", second),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    #[test]
+    fn test_optimize_is_smaller_and_lossless() {
+        let png = testing_png();
+        let optimized = optimize(&png, false).unwrap();
+        assert!(optimized.bytes_saved() > 0);
+        assert_eq!(
+            filter::unfilter(
+                &png.ihdr().unwrap(),
+                &zlib::decompress(&optimized.png.idat_data()).unwrap()
+            )
+            .unwrap(),
+            filter::unfilter(
+                &png.ihdr().unwrap(),
+                &zlib::decompress(&png.idat_data()).unwrap()
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_optimize_merges_idat() {
+        let optimized = optimize(&testing_png(), false).unwrap();
+        let types: Vec<String> = optimized
+            .png
+            .chunks()
+            .iter()
+            .map(|c| c.chunk_type().to_string())
+            .collect();
+        assert_eq!(types, vec!["IHDR", "gAMA", "tEXt", "IDAT", "IEND"]);
+    }
+
+    #[test]
+    fn test_optimize_keeps_trailer() {
+        let mut png = testing_png();
+        png.set_trailer(b"PK\x05\x06 zip".to_vec());
+        let optimized = optimize(&png, true).unwrap();
+        assert_eq!(optimized.png.trailer(), b"PK\x05\x06 zip");
+        assert_eq!(
+            optimized.bytes_saved(),
+            optimize(&testing_png(), true).unwrap().bytes_saved()
+        );
+    }
+
+    #[test]
+    fn test_optimize_keeps_smaller_original() {
+        let once = optimize(&testing_png(), false).unwrap();
+        assert!(once.recompressed.is_some());
+        let twice = optimize(&once.png, true).unwrap();
+        assert!(twice.recompressed.is_none());
+        assert_eq!(twice.png.idat_data(), once.png.idat_data());
+        assert!(twice.bytes_saved() > 0);
+    }
+
+    #[test]
+    fn test_optimize_strip() {
+        let optimized = optimize(&testing_png(), true).unwrap();
+        assert!(optimized.png.chunk_by_type("tEXt").is_none());
+        assert!(optimized.png.chunk_by_type("gAMA").is_some());
+    }
+}
@@ -1,36 +1,111 @@
 use std::convert::TryFrom;
 use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
 use std::fs;
-use std::io::{BufReader, Read};
+#[cfg(not(target_arch = "wasm32"))]
 use std::path::Path;
 use std::str::FromStr;
 
-use crate::chunk::Chunk;
+use crate::chunk::{Chunk, ChunkRef};
 use crate::chunk_type::ChunkType;
+use crate::container::ContainerKind;
+use crate::exif::Exif;
+use crate::ihdr::Ihdr;
 use crate::{Error, Result};
 
+/// Ancillary chunks the PNG spec allows at most once per file.
+pub const SINGLE_CHUNKS: [&str; 17] = [
+    "cHRM", "gAMA", "iCCP", "sBIT", "sRGB", "bKGD", "hIST", "tRNS", "pHYs", "tIME", "eXIf", "sCAL",
+    "pCAL", "oFFs", "sTER", "acTL", "cICP",
+];
+
+/// Bounds applied while parsing untrusted files, on top of the spec's own
+/// limit on chunk length.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Limits {
+    /// The largest data length accepted for a single chunk.
+    pub max_chunk_size: u32,
+    /// The largest sum of chunk data lengths accepted for a whole file.
+    pub max_total_size: u64,
+}
+
+impl Limits {
+    /// Checks the declared `len` of chunk #`index`, adding it to the running `total`.
+    pub(crate) fn check(&self, index: usize, len: u32, total: &mut u64) -> Result<()> {
+        if len > self.max_chunk_size {
+            return Err(format!(
+                "chunk #{} is {} bytes, more than the limit of {}",
+                index, len, self.max_chunk_size
+            )
+            .into());
+        }
+        *total += len as u64;
+        if *total > self.max_total_size {
+            return Err(format!(
+                "chunk data exceeds the limit of {} bytes",
+                self.max_total_size
+            )
+            .into());
+        }
+        Ok(())
+    }
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Self {
+            max_chunk_size: Chunk::MAX_LENGTH,
+            max_total_size: u64::MAX,
+        }
+    }
+}
+
 /// A PNG container as described by the PNG spec
 /// http://www.libpng.org/pub/png/spec/1.2/PNG-Contents.html
+///
+/// MNG and JNG files share the same chunk framing and are read into a `Png` as
+/// well. Their signature is kept in the header, see `kind`.
+///
+/// Bytes after the end chunk that don't parse as chunks are kept as the trailer
+/// and written back unchanged, see `trailer`.
+#[derive(Clone)]
 pub struct Png {
     header: [u8; 8],
     chunks: Vec<Chunk>,
+    trailer: Vec<u8>,
 }
 
 impl Png {
-    const STANDARD_HEADER: [u8; 8] = [137, 80, 78, 71, 13, 10, 26, 10];
-
     /// Creates a `Png` from a list of chunks using the correct header
     pub fn from_chunks(chunks: Vec<Chunk>) -> Png {
+        Self::from_chunks_with_kind(ContainerKind::Png, chunks)
+    }
+
+    /// Creates a file of the given `kind` from a list of chunks
+    pub fn from_chunks_with_kind(kind: ContainerKind, chunks: Vec<Chunk>) -> Png {
         Self {
-            header: Self::STANDARD_HEADER.clone(),
+            header: kind.signature(),
             chunks,
+            trailer: Vec::new(),
         }
     }
 
     /// Creates a `Png` from a file path
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
         let bytes = fs::read(path)?;
-        Ok(Self::try_from(&bytes[..])?)
+        Self::try_from(&bytes[..])
+    }
+
+    /// Parses `bytes`, rejecting chunks and files that exceed `limits`. See
+    /// `PngRef` for reading chunks without copying them.
+    pub fn from_bytes_with_limits(bytes: &[u8], limits: &Limits) -> Result<Self> {
+        PngRef::with_limits(bytes, *limits)?.to_png()
+    }
+
+    /// Parses `bytes` like `try_from`, but keeps chunks whose CRC doesn't match.
+    pub fn from_bytes_lenient(bytes: &[u8]) -> Result<Self> {
+        PngRef::new(bytes)?.lenient().to_png()
     }
 
     /// Appends a chunk to the end of this `Png` file's `Chunk` list.
@@ -38,6 +113,66 @@ impl Png {
         self.chunks.push(chunk)
     }
 
+    /// Inserts a chunk at the earliest position the PNG spec requires for its type:
+    /// before `PLTE` for color space chunks, before the first `IDAT` for other
+    /// chunks that must precede the image data, and before `IEND` for anything else.
+    /// MNG and JNG files only get the chunk placed before their end chunk.
+    /// Falls back to appending if the required anchor chunk is missing.
+    pub fn insert_chunk(&mut self, chunk: Chunk) {
+        let chunk_type = chunk.chunk_type().to_string();
+        let end = self.kind().end_chunk();
+        let anchors: Vec<&str> = match (self.kind(), &chunk_type[..]) {
+            (ContainerKind::Png, "cHRM" | "gAMA" | "iCCP" | "sBIT" | "sRGB") => {
+                vec!["PLTE", "IDAT", end]
+            }
+            (ContainerKind::Png, "bKGD" | "hIST" | "tRNS" | "pHYs" | "sPLT" | "eXIf") => {
+                vec!["IDAT", end]
+            }
+            _ => vec![end],
+        };
+        let position = self.chunks.iter().position(|x| {
+            anchors
+                .iter()
+                .any(|anchor| x.chunk_type().bytes() == anchor.as_bytes())
+        });
+        match position {
+            Some(idx) => self.chunks.insert(idx, chunk),
+            None => self.chunks.push(chunk),
+        }
+    }
+
+    /// Inserts a chunk like `insert_chunk`, first removing the existing chunk of
+    /// its type from a PNG file if the spec allows only one of them. Returns true
+    /// if a chunk was replaced. Since `iCCP` and `sRGB` exclude each other,
+    /// adding one to a PNG file holding the other is an error.
+    pub fn replace_chunk(&mut self, chunk: Chunk) -> Result<bool> {
+        let chunk_type = chunk.chunk_type().bytes();
+        let excluded = match &chunk_type {
+            b"iCCP" => Some("sRGB"),
+            b"sRGB" => Some("iCCP"),
+            _ => None,
+        };
+        if let (ContainerKind::Png, Some(excluded)) = (self.kind(), excluded) {
+            if self.chunk_by_type(excluded).is_some() {
+                return Err(format!(
+                    "{} conflicts with the existing {} chunk",
+                    chunk.chunk_type(),
+                    excluded
+                )
+                .into());
+            }
+        }
+        let single = self.kind() == ContainerKind::Png
+            && SINGLE_CHUNKS.iter().any(|x| x.as_bytes() == chunk_type);
+        let count = self.chunks.len();
+        if single {
+            self.chunks.retain(|x| x.chunk_type().bytes() != chunk_type);
+        }
+        let replaced = self.chunks.len() < count;
+        self.insert_chunk(chunk);
+        Ok(replaced)
+    }
+
     /// Searches for a `Chunk` with the specified `chunk_type` and removes the first
     /// matching `Chunk` from this `Png` list of chunks.
     pub fn remove_chunk(&mut self, chunk_type: &str) -> Result<Chunk> {
@@ -57,11 +192,21 @@ impl Png {
         &self.header
     }
 
+    /// The member of the PNG family this file belongs to, as told by its header.
+    pub fn kind(&self) -> ContainerKind {
+        ContainerKind::detect(&self.header).unwrap_or_default()
+    }
+
     /// Lists the `Chunk`s stored in this `Png`
     pub fn chunks(&self) -> &[Chunk] {
         &self.chunks
     }
 
+    /// The `Chunk`s of this `Png`, for editing them in place or reordering them.
+    pub fn chunks_mut(&mut self) -> &mut Vec<Chunk> {
+        &mut self.chunks
+    }
+
     /// Searches for a `Chunk` with the specified `chunk_type` and returns the first
     /// matching `Chunk` from this `Png`.
     pub fn chunk_by_type(&self, chunk_type: &str) -> Option<&Chunk> {
@@ -70,20 +215,312 @@ impl Png {
             .find(|&x| &x.chunk_type().to_string()[..] == chunk_type)
     }
 
+    /// Decodes the `IHDR` chunk of this `Png`.
+    pub fn ihdr(&self) -> Result<Ihdr> {
+        match self.chunk_by_type("IHDR") {
+            Some(chunk) => Ihdr::try_from(chunk),
+            None => Err("IHDR not found".into()),
+        }
+    }
+
+    /// Decodes the `eXIf` chunk of this `Png`, if it has one.
+    pub fn exif(&self) -> Result<Option<Exif>> {
+        match self.chunk_by_type("eXIf") {
+            Some(chunk) => Ok(Some(Exif::try_from(chunk.data())?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Replaces the `eXIf` chunk of this `Png` with `exif`, or inserts one if
+    /// there is none yet.
+    pub fn set_exif(&mut self, exif: &Exif) {
+        let chunk = Chunk::new(ChunkType::try_from(*b"eXIf").unwrap(), exif.as_bytes());
+        match self
+            .chunks
+            .iter()
+            .position(|x| x.chunk_type().bytes() == *b"eXIf")
+        {
+            Some(idx) => self.chunks[idx] = chunk,
+            None => self.insert_chunk(chunk),
+        }
+    }
+
+    /// Concatenates the data of every `IDAT` chunk into the compressed image stream.
+    pub fn idat_data(&self) -> Vec<u8> {
+        self.chunks
+            .iter()
+            .filter(|x| x.chunk_type().bytes() == *b"IDAT")
+            .flat_map(|x| x.data().iter().copied())
+            .collect()
+    }
+
+    /// Replaces all `IDAT` chunks with a single `IDAT` chunk holding the whole
+    /// compressed image stream, placed where the first `IDAT` chunk was.
+    pub fn merge_idat(&mut self) {
+        let data = self.idat_data();
+        let first = match self.idat_position() {
+            Some(idx) => idx,
+            None => return,
+        };
+        self.chunks.retain(|x| x.chunk_type().bytes() != *b"IDAT");
+        let idat = Chunk::new(ChunkType::try_from(*b"IDAT").unwrap(), data);
+        self.chunks.insert(first, idat);
+    }
+
+    /// Re-cuts the compressed image stream into consecutive `IDAT` chunks holding
+    /// at most `max_len` bytes of data each. The zlib stream itself is left as is.
+    pub fn split_idat(&mut self, max_len: usize) -> Result<()> {
+        if max_len == 0 {
+            return Err("IDAT chunk size must be greater than zero".into());
+        }
+        self.merge_idat();
+        let first = match self.idat_position() {
+            Some(idx) if !self.chunks[idx].data().is_empty() => idx,
+            _ => return Ok(()),
+        };
+        let merged = self.chunks.remove(first);
+        let idats = merged
+            .data()
+            .chunks(max_len)
+            .map(|x| Chunk::new(merged.chunk_type().clone(), x.to_vec()));
+        self.chunks.splice(first..first, idats);
+        Ok(())
+    }
+
+    /// Byte offset in the file of the chunk at `index`, counting the header.
+    pub fn chunk_offset(&self, index: usize) -> Option<usize> {
+        if index >= self.chunks.len() {
+            return None;
+        }
+        let preceding: usize = self.chunks[..index]
+            .iter()
+            .map(|x| 12 + x.length() as usize)
+            .sum();
+        Some(self.header.len() + preceding)
+    }
+
+    /// Bytes after the end chunk that are not chunks themselves. Data hidden
+    /// this way is ignored by decoders, which also makes it a way to build files
+    /// that are valid in two formats at once.
+    pub fn trailer(&self) -> &[u8] {
+        &self.trailer
+    }
+
+    pub fn set_trailer(&mut self, trailer: Vec<u8>) {
+        self.trailer = trailer;
+    }
+
+    /// Removes the trailer and returns it.
+    pub fn strip_trailer(&mut self) -> Vec<u8> {
+        std::mem::take(&mut self.trailer)
+    }
+
+    fn idat_position(&self) -> Option<usize> {
+        self.chunks
+            .iter()
+            .position(|x| x.chunk_type().bytes() == *b"IDAT")
+    }
+
     /// Returns this `Png` as a byte sequence.
-    /// These bytes will contain the header followed by the bytes of all of the chunks.
+    /// These bytes will contain the header followed by the bytes of all of the
+    /// chunks and the trailer.
     pub fn as_bytes(&self) -> Vec<u8> {
         let chunks: Vec<u8> = self.chunks.iter().flat_map(|x| x.as_bytes()).collect();
 
-        self.header.iter().chain(chunks.iter()).cloned().collect()
+        self.header
+            .iter()
+            .chain(chunks.iter())
+            .chain(self.trailer.iter())
+            .cloned()
+            .collect()
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
     pub fn to_file<P: AsRef<Path>>(&self, path: P) -> Result<()> {
         fs::write(path, self.as_bytes())?;
         Ok(())
     }
 }
 
+/// A PNG family file borrowed from its bytes, such as a memory mapped file.
+/// Chunks are parsed lazily into `ChunkRef`s that point into those bytes, so
+/// read-only scans never copy chunk data.
+pub struct PngRef<'a> {
+    header: [u8; 8],
+    bytes: &'a [u8],
+    limits: Limits,
+    verify_crc: bool,
+}
+
+impl<'a> PngRef<'a> {
+    /// Checks the header of `bytes`. Chunks are only parsed when iterated.
+    pub fn new(bytes: &'a [u8]) -> Result<PngRef<'a>> {
+        Self::with_limits(bytes, Limits::default())
+    }
+
+    /// Like `new`, rejecting chunks and files that exceed `limits` while iterating.
+    pub fn with_limits(bytes: &'a [u8], limits: Limits) -> Result<PngRef<'a>> {
+        let header: [u8; 8] = match bytes.get(..8) {
+            Some(header) => header.try_into()?,
+            None => return Err("file is too short for a header".into()),
+        };
+        ContainerKind::try_from(header)?;
+        Ok(Self {
+            header,
+            bytes,
+            limits,
+            verify_crc: true,
+        })
+    }
+
+    /// Accepts chunks whose CRC doesn't match their contents, for salvaging data
+    /// from damaged files. See `ChunkRef::is_crc_valid`.
+    pub fn lenient(self) -> PngRef<'a> {
+        Self {
+            verify_crc: false,
+            ..self
+        }
+    }
+
+    pub fn header(&self) -> &[u8; 8] {
+        &self.header
+    }
+
+    pub fn kind(&self) -> ContainerKind {
+        ContainerKind::detect(&self.header).unwrap_or_default()
+    }
+
+    /// Iterates over the chunks in file order. Iteration ends after the first
+    /// error, or when fewer bytes remain than a chunk length takes. Past the end
+    /// chunk, bytes that don't frame as a chunk end iteration without an error;
+    /// they are the trailer, see `ChunkRefs::remainder`. A chunk there that
+    /// frames but fails its CRC is still an error.
+    pub fn chunks(&self) -> ChunkRefs<'a> {
+        ChunkRefs {
+            bytes: self.bytes,
+            pos: self.header.len(),
+            index: 0,
+            total: 0,
+            limits: self.limits,
+            verify_crc: self.verify_crc,
+            end: self.kind().end_chunk(),
+            seen_end: false,
+            failed: false,
+        }
+    }
+
+    /// Returns the bytes after the end chunk that are not chunks, see `Png::trailer`.
+    pub fn trailer(&self) -> Result<&'a [u8]> {
+        let mut chunks = self.chunks();
+        for chunk in &mut chunks {
+            chunk?;
+        }
+        Ok(chunks.remainder())
+    }
+
+    /// Returns the first chunk of type `chunk_type`, parsing only the chunks before it.
+    pub fn chunk_by_type(&self, chunk_type: &str) -> Result<Option<ChunkRef<'a>>> {
+        for chunk in self.chunks() {
+            let chunk = chunk?;
+            if chunk.chunk_type().bytes() == chunk_type.as_bytes() {
+                return Ok(Some(chunk));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Copies every chunk into an owned `Png`.
+    pub fn to_png(&self) -> Result<Png> {
+        let mut refs = self.chunks();
+        let chunks = (&mut refs)
+            .map(|x| x.map(|chunk| chunk.to_chunk()))
+            .collect::<Result<Vec<Chunk>>>()?;
+        Ok(Png {
+            header: self.header,
+            chunks,
+            trailer: refs.remainder().to_vec(),
+        })
+    }
+}
+
+/// Iterator over the chunks of a `PngRef`.
+pub struct ChunkRefs<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+    index: usize,
+    total: u64,
+    limits: Limits,
+    verify_crc: bool,
+    end: &'static str,
+    seen_end: bool,
+    failed: bool,
+}
+
+impl<'a> ChunkRefs<'a> {
+    /// Iterates over whatever follows an end chunk in `bytes`, as `PngRef::chunks`
+    /// does once it has passed the end chunk.
+    #[cfg(feature = "async")]
+    pub(crate) fn after_end(bytes: &'a [u8], limits: Limits) -> ChunkRefs<'a> {
+        ChunkRefs {
+            bytes,
+            pos: 0,
+            index: 0,
+            total: 0,
+            limits,
+            verify_crc: true,
+            end: "",
+            seen_end: true,
+            failed: false,
+        }
+    }
+
+    /// The bytes not yet parsed. Once iteration has ended without an error,
+    /// these are the trailer.
+    pub fn remainder(&self) -> &'a [u8] {
+        &self.bytes[self.pos.min(self.bytes.len())..]
+    }
+
+    /// Frames the next chunk, without looking at its CRC.
+    fn frame_next(&mut self, len_bytes: &[u8]) -> Result<ChunkRef<'a>> {
+        let len = u32::from_be_bytes([len_bytes[0], len_bytes[1], len_bytes[2], len_bytes[3]]);
+        self.limits.check(self.index, len, &mut self.total)?;
+        ChunkRef::parse(&self.bytes[self.pos..], false)
+    }
+}
+
+impl<'a> Iterator for ChunkRefs<'a> {
+    type Item = Result<ChunkRef<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let len_bytes = self.bytes.get(self.pos..self.pos + 4)?;
+        match self.frame_next(len_bytes) {
+            Ok(chunk) if self.verify_crc && !chunk.is_crc_valid() => {
+                self.failed = true;
+                Some(Err("provided crc is not valid".into()))
+            }
+            Ok(chunk) => {
+                self.pos += chunk.size();
+                self.index += 1;
+                if chunk.chunk_type().bytes() == self.end.as_bytes() {
+                    self.seen_end = true;
+                }
+                Some(Ok(chunk))
+            }
+            Err(_) if self.seen_end => {
+                self.failed = true;
+                None
+            }
+            Err(e) => {
+                self.failed = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
 impl fmt::Display for Png {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{:?}{:?}", self.header, self.chunks)
@@ -94,50 +531,38 @@ impl TryFrom<&[u8]> for Png {
     type Error = Error;
 
     fn try_from(bytes: &[u8]) -> Result<Self> {
-        let mut reader = BufReader::new(bytes);
-        let mut header: [u8; 8] = [0, 0, 0, 0, 0, 0, 0, 0];
-        reader.read_exact(&mut header)?;
-        if header != Self::STANDARD_HEADER {
-            return Err("invalid header provided".into());
-        }
-        let mut chunks: Vec<Chunk> = Vec::new();
-        loop {
-            let mut len_buffer: [u8; 4] = [0, 0, 0, 0];
-            match reader.read_exact(&mut len_buffer) {
-                Err(_) => break,
-                Ok(()) => (),
-            };
-            let len = u32::from_be_bytes(len_buffer);
-            let mut chunk_data = vec![0u8; 8 + len as usize];
-            reader.read_exact(&mut chunk_data)?;
-            let data: Vec<u8> = len_buffer
-                .iter()
-                .chain(chunk_data.iter())
-                .cloned()
-                .collect();
-            let chunk = Chunk::try_from(&data[..])?;
-            chunks.push(chunk);
-        }
-        Ok(Self { header, chunks })
+        Self::from_bytes_with_limits(bytes, &Limits::default())
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::chunk::Chunk;
     use crate::chunk_type::ChunkType;
     use std::convert::TryFrom;
     use std::str::FromStr;
 
-    fn testing_chunks() -> Vec<Chunk> {
-        let mut chunks = Vec::new();
+    /// A 1x1 grayscale image with only `IHDR` and `IEND`, for the tests of
+    /// other modules.
+    pub(crate) fn minimal_png() -> Png {
+        Png::from_chunks(vec![
+            chunk("IHDR", &[0, 0, 0, 1, 0, 0, 0, 1, 8, 0, 0, 0, 0]),
+            chunk("IEND", &[]),
+        ])
+    }
 
-        chunks.push(chunk_from_strings("FrSt", "I am the first chunk").unwrap());
-        chunks.push(chunk_from_strings("miDl", "I am another chunk").unwrap());
-        chunks.push(chunk_from_strings("LASt", "I am the last chunk").unwrap());
+    /// Builds a chunk from a type that is known to be valid.
+    pub(crate) fn chunk(chunk_type: &str, data: &[u8]) -> Chunk {
+        Chunk::new(ChunkType::from_str(chunk_type).unwrap(), data.to_vec())
+    }
 
-        chunks
+    fn testing_chunks() -> Vec<Chunk> {
+        vec![
+            chunk_from_strings("FrSt", "I am the first chunk").unwrap(),
+            chunk_from_strings("miDl", "I am another chunk").unwrap(),
+            chunk_from_strings("LASt", "I am the last chunk").unwrap(),
+        ]
     }
 
     fn testing_png() -> Png {
@@ -167,7 +592,8 @@ mod tests {
             .flat_map(|chunk| chunk.as_bytes())
             .collect();
 
-        let bytes: Vec<u8> = Png::STANDARD_HEADER
+        let bytes: Vec<u8> = ContainerKind::Png
+            .signature()
             .iter()
             .chain(chunk_bytes.iter())
             .copied()
@@ -218,6 +644,30 @@ mod tests {
         assert!(png.is_err());
     }
 
+    #[test]
+    fn test_mng_from_bytes() {
+        let chunk_bytes: Vec<u8> = testing_chunks()
+            .into_iter()
+            .chain(std::iter::once(chunk_from_strings("MEND", "").unwrap()))
+            .flat_map(|chunk| chunk.as_bytes())
+            .collect();
+
+        let bytes: Vec<u8> = ContainerKind::Mng
+            .signature()
+            .iter()
+            .chain(chunk_bytes.iter())
+            .copied()
+            .collect();
+
+        let mut png = Png::try_from(bytes.as_ref()).unwrap();
+        assert_eq!(png.kind(), ContainerKind::Mng);
+        assert_eq!(png.as_bytes(), bytes);
+
+        png.insert_chunk(chunk_from_strings("gAMA", "gama").unwrap());
+        assert_eq!(&png.chunks()[3].chunk_type().to_string(), "gAMA");
+        assert_eq!(&png.chunks()[4].chunk_type().to_string(), "MEND");
+    }
+
     #[test]
     fn test_list_chunks() {
         let png = testing_png();
@@ -251,6 +701,187 @@ mod tests {
         assert!(chunk.is_none());
     }
 
+    #[test]
+    fn test_insert_chunk() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.insert_chunk(chunk_from_strings("iCCP", "profile").unwrap());
+        png.insert_chunk(chunk_from_strings("tEXt", "Title\0Dice").unwrap());
+        let types: Vec<String> = png
+            .chunks()
+            .iter()
+            .map(|x| x.chunk_type().to_string())
+            .collect();
+        assert_eq!(
+            types,
+            vec!["IHDR", "sRGB", "gAMA", "pHYs", "iCCP", "IDAT", "RuSt", "tEXt", "IEND"]
+        );
+    }
+
+    #[test]
+    fn test_replace_chunk() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let mut replace = |chunk_type: &str, data: &str| {
+            png.replace_chunk(chunk_from_strings(chunk_type, data).unwrap())
+        };
+        assert!(replace("gAMA", "\0\0\0\x01").unwrap());
+        assert!(!replace("tEXt", "a\0b").unwrap());
+        assert!(!replace("tEXt", "c\0d").unwrap());
+        assert!(replace("iCCP", "profile").is_err());
+        let count = |chunk_type: &str| {
+            png.chunks()
+                .iter()
+                .filter(|x| x.chunk_type().to_string() == chunk_type)
+                .count()
+        };
+        assert_eq!(count("gAMA"), 1);
+        assert_eq!(count("tEXt"), 2);
+        assert_eq!(png.chunk_by_type("gAMA").unwrap().data(), &[0, 0, 0, 1]);
+        assert_eq!(count("iCCP"), 0);
+    }
+
+    #[test]
+    fn test_chunk_offset() {
+        let png = testing_png();
+        assert_eq!(png.chunk_offset(0), Some(8));
+        assert_eq!(png.chunk_offset(1), Some(8 + 12 + 20));
+        assert_eq!(png.chunk_offset(3), None);
+    }
+
+    #[test]
+    fn test_split_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let stream = png.idat_data();
+        png.split_idat(1000).unwrap();
+        let idats: Vec<&Chunk> = png
+            .chunks()
+            .iter()
+            .filter(|x| x.chunk_type().bytes() == *b"IDAT")
+            .collect();
+        assert_eq!(idats.len(), 5);
+        assert!(idats.iter().all(|x| x.length() <= 1000));
+        assert_eq!(png.idat_data(), stream);
+        assert!(png.split_idat(0).is_err());
+    }
+
+    #[test]
+    fn test_merge_idat() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.split_idat(100).unwrap();
+        png.merge_idat();
+        assert_eq!(png.as_bytes(), PNG_FILE.to_vec());
+    }
+
+    #[test]
+    fn test_huge_declared_length() {
+        let mut bytes = ContainerKind::Png.signature().to_vec();
+        bytes.extend([0xff, 0xff, 0xff, 0xff]);
+        assert!(Png::try_from(&bytes[..]).is_err());
+
+        bytes[8] = 0x7f;
+        bytes.extend(b"IDAT");
+        assert!(Png::try_from(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn test_png_ref() {
+        let png_ref = PngRef::new(&PNG_FILE[..]).unwrap();
+        let png = Png::try_from(&PNG_FILE[..]).unwrap();
+        let chunks: Vec<ChunkRef> = png_ref.chunks().collect::<Result<_>>().unwrap();
+        assert_eq!(chunks.len(), png.chunks().len());
+        for (chunk_ref, chunk) in chunks.iter().zip(png.chunks()) {
+            assert_eq!(chunk_ref.to_chunk().as_bytes(), chunk.as_bytes());
+        }
+
+        let ihdr = png_ref.chunk_by_type("IHDR").unwrap().unwrap();
+        assert!(std::ptr::eq(ihdr.data(), &PNG_FILE[16..29]));
+        assert!(png_ref.chunk_by_type("zzZz").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_png_ref_stops_at_error() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[8 + 12 + 13 + 8] ^= 1;
+        let png_ref = PngRef::new(&bytes).unwrap();
+        let results: Vec<bool> = png_ref.chunks().map(|x| x.is_ok()).collect();
+        assert_eq!(results, vec![true, false]);
+        assert!(PngRef::new(&bytes[..4]).is_err());
+    }
+
+    #[test]
+    fn test_from_bytes_lenient() {
+        let mut bytes = PNG_FILE.to_vec();
+        bytes[8 + 12 + 13 + 8] ^= 1;
+        assert!(Png::try_from(&bytes[..]).is_err());
+        let png = Png::from_bytes_lenient(&bytes).unwrap();
+        assert_eq!(png.as_bytes(), bytes);
+        assert!(png.chunks()[1].crc() != png.chunks()[1].calculate_crc());
+    }
+
+    #[test]
+    fn test_trailer() {
+        for trailer in [&b"PK\x03\x04 zip archive follows"[..], b"\r\n"] {
+            let mut bytes = PNG_FILE.to_vec();
+            bytes.extend(trailer);
+            let mut png = Png::try_from(&bytes[..]).unwrap();
+            assert_eq!(png.trailer(), trailer);
+            assert_eq!(
+                png.chunks().len(),
+                Png::try_from(&PNG_FILE[..]).unwrap().chunks().len()
+            );
+            assert_eq!(png.as_bytes(), bytes);
+            assert_eq!(PngRef::new(&bytes).unwrap().trailer().unwrap(), trailer);
+
+            assert_eq!(png.strip_trailer(), trailer);
+            assert_eq!(png.as_bytes(), PNG_FILE);
+        }
+    }
+
+    #[test]
+    fn test_chunks_after_end() {
+        let mut png = Png::try_from(&PNG_FILE[..]).unwrap();
+        png.append_chunk(chunk_from_strings("LASt", "after IEND").unwrap());
+        png.set_trailer(b"junk".to_vec());
+        let read = Png::try_from(&png.as_bytes()[..]).unwrap();
+        assert_eq!(
+            read.chunks().last().unwrap().chunk_type().to_string(),
+            "LASt"
+        );
+        assert_eq!(read.trailer(), b"junk");
+
+        // So is a chunk after it that frames but fails its CRC.
+        let mut bytes = png.as_bytes();
+        let len = bytes.len();
+        bytes[len - b"junk".len() - 5] ^= 1;
+        assert!(Png::try_from(&bytes[..]).is_err());
+        assert_eq!(Png::from_bytes_lenient(&bytes).unwrap().trailer(), b"junk");
+
+        // Garbage before the end chunk is still an error.
+        let mut bytes = PNG_FILE[..PNG_FILE.len() - 12].to_vec();
+        bytes.extend(b"junk and more junk");
+        assert!(Png::try_from(&bytes[..]).is_err());
+    }
+
+    #[test]
+    fn test_limits() {
+        let limits = Limits {
+            max_chunk_size: 100,
+            ..Default::default()
+        };
+        assert!(Png::from_bytes_with_limits(&PNG_FILE[..], &limits).is_err());
+
+        let limits = Limits {
+            max_total_size: 1000,
+            ..Default::default()
+        };
+        assert!(Png::from_bytes_with_limits(&PNG_FILE[..], &limits).is_err());
+
+        let limits = Limits {
+            max_total_size: PNG_FILE.len() as u64,
+            ..Default::default()
+        };
+        assert!(Png::from_bytes_with_limits(&PNG_FILE[..], &limits).is_ok());
+    }
+
     #[test]
     fn test_png_from_image_file() {
         let png = Png::try_from(&PNG_FILE[..]);
@@ -261,7 +892,7 @@ mod tests {
     fn test_as_bytes() {
         let png = Png::try_from(&PNG_FILE[..]).unwrap();
         let actual = png.as_bytes();
-        let expected: Vec<u8> = PNG_FILE.iter().copied().collect();
+        let expected: Vec<u8> = PNG_FILE.to_vec();
         assert_eq!(actual, expected);
     }
 
@@ -272,7 +903,8 @@ mod tests {
             .flat_map(|chunk| chunk.as_bytes())
             .collect();
 
-        let bytes: Vec<u8> = Png::STANDARD_HEADER
+        let bytes: Vec<u8> = ContainerKind::Png
+            .signature()
             .iter()
             .chain(chunk_bytes.iter())
             .copied()
@@ -0,0 +1,188 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::message;
+use crate::png::Png;
+use crate::Result;
+
+/// The chunk holding the edit history of a file, as a JSON array of `Entry`.
+pub const CHUNK_TYPE: &str = "prVn";
+
+/// One edit made to a file. `previous` and `state` hash the file contents
+/// before and after the edit, leaving out the history chunk itself, so each
+/// entry's `previous` must match the `state` of the entry before it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Entry {
+    pub tool: String,
+    pub version: String,
+    pub operation: String,
+    pub chunk_types: Vec<String>,
+    pub timestamp: u64,
+    pub previous: String,
+    pub state: String,
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{} {} {} {}",
+            message::format_timestamp(self.timestamp),
+            self.tool,
+            self.version,
+            self.operation
+        )?;
+        if !self.chunk_types.is_empty() {
+            write!(f, " ({})", self.chunk_types.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Hashes everything in `png` except its history chunk, as lowercase hex SHA-256.
+pub fn state_hash(png: &Png) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(png.header());
+    for chunk in png.chunks() {
+        if chunk.chunk_type().bytes() != CHUNK_TYPE.as_bytes() {
+            hasher.update(chunk.as_bytes());
+        }
+    }
+    hasher.update(png.trailer());
+    hasher
+        .finalize()
+        .iter()
+        .map(|x| format!("{:02x}", x))
+        .collect()
+}
+
+/// Reads the history of `png`, oldest first. Files without a history chunk
+/// have an empty history.
+pub fn history(png: &Png) -> Result<Vec<Entry>> {
+    match png.chunk_by_type(CHUNK_TYPE) {
+        Some(chunk) => Ok(serde_json::from_slice(chunk.data())?),
+        None => Ok(Vec::new()),
+    }
+}
+
+/// Appends an entry for `operation` to the history of `after`, which is the
+/// result of editing `before`. The history is taken from `before`, so it
+/// survives operations that drop unknown chunks.
+pub fn record(before: &Png, after: &mut Png, operation: &str, chunk_types: &[&str]) -> Result<()> {
+    let mut entries = history(before)?;
+    entries.push(Entry {
+        tool: env!("CARGO_PKG_NAME").to_string(),
+        version: env!("CARGO_PKG_VERSION").to_string(),
+        operation: operation.to_string(),
+        chunk_types: chunk_types.iter().map(|x| x.to_string()).collect(),
+        timestamp: message::now(),
+        previous: state_hash(before),
+        state: state_hash(after),
+    });
+    let chunk = Chunk::new(
+        ChunkType::from_str(CHUNK_TYPE)?,
+        serde_json::to_vec(&entries)?,
+    );
+    after
+        .chunks_mut()
+        .retain(|x| x.chunk_type().bytes() != CHUNK_TYPE.as_bytes());
+    after.insert_chunk(chunk);
+    Ok(())
+}
+
+/// Checks the hash links of the history of `png` and describes every break:
+/// an entry that does not follow on from the one before it, or a file that
+/// was changed after its last entry.
+pub fn verify(png: &Png) -> Result<Vec<String>> {
+    let entries = history(png)?;
+    let mut problems = Vec::new();
+    for (index, pair) in entries.windows(2).enumerate() {
+        if pair[1].previous != pair[0].state {
+            problems.push(format!(
+                "entry #{} does not follow #{}, the file was changed in between",
+                index + 1,
+                index
+            ));
+        }
+    }
+    if let Some(last) = entries.last() {
+        if last.state != state_hash(png) {
+            problems.push(format!(
+                "the file was changed after entry #{}",
+                entries.len() - 1
+            ));
+        }
+    }
+    Ok(problems)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::png::tests::minimal_png;
+    use std::convert::TryFrom;
+
+    fn add_chunk(png: &Png, chunk_type: &str) -> Png {
+        let mut after = Png::try_from(&png.as_bytes()[..]).unwrap();
+        after.insert_chunk(Chunk::new(
+            ChunkType::from_str(chunk_type).unwrap(),
+            b"data".to_vec(),
+        ));
+        record(png, &mut after, "encode", &[chunk_type]).unwrap();
+        after
+    }
+
+    #[test]
+    fn test_record() {
+        let first = add_chunk(&minimal_png(), "ruSt");
+        let second = add_chunk(&first, "teSt");
+        let entries = history(&second).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].operation, "encode");
+        assert_eq!(entries[1].chunk_types, vec!["teSt"]);
+        assert_eq!(entries[0].previous, state_hash(&minimal_png()));
+        assert_eq!(entries[1].previous, entries[0].state);
+        assert_eq!(
+            second.chunks().last().unwrap().chunk_type().to_string(),
+            "IEND"
+        );
+        assert_eq!(
+            second
+                .chunks()
+                .iter()
+                .filter(|x| x.chunk_type().to_string() == CHUNK_TYPE)
+                .count(),
+            1
+        );
+        assert!(verify(&second).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_state_hash_ignores_history() {
+        let png = add_chunk(&minimal_png(), "ruSt");
+        let mut stripped = Png::try_from(&png.as_bytes()[..]).unwrap();
+        stripped.remove_chunk(CHUNK_TYPE).unwrap();
+        assert_eq!(state_hash(&png), state_hash(&stripped));
+        assert!(history(&stripped).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_verify_breaks() {
+        let mut png = add_chunk(&minimal_png(), "ruSt");
+        png.remove_chunk("ruSt").unwrap();
+        assert_eq!(
+            verify(&png).unwrap(),
+            vec!["the file was changed after entry #0"]
+        );
+        let png = add_chunk(&png, "teSt");
+        assert_eq!(
+            verify(&png).unwrap(),
+            vec!["entry #1 does not follow #0, the file was changed in between"]
+        );
+    }
+}
@@ -0,0 +1,232 @@
+use std::fmt;
+use std::str::FromStr;
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use hkdf::Hkdf;
+use rand_core::{OsRng, RngCore};
+use sha2::Sha256;
+use x25519_dalek::{PublicKey, SharedSecret, StaticSecret};
+
+use crate::{Error, Result};
+
+const MAGIC: [u8; 4] = *b"PENC";
+const VERSION: u8 = 1;
+const WRAPPED_KEY_LENGTH: usize = 32 + 16;
+const HEADER_LENGTH: usize = 4 + 1 + 32 + 1;
+const WRAP_INFO: &[u8] = b"pngme recipient v1";
+
+/// The private half of a key pair, used to decrypt.
+pub struct Identity(StaticSecret);
+
+/// The public half of a key pair, used to encrypt for its owner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Recipient(PublicKey);
+
+impl Identity {
+    const PREFIX: &'static str = "pngme-identity:";
+
+    /// Creates a new random identity.
+    pub fn generate() -> Identity {
+        Self(StaticSecret::random_from_rng(OsRng))
+    }
+
+    pub fn recipient(&self) -> Recipient {
+        Recipient(PublicKey::from(&self.0))
+    }
+}
+
+impl Recipient {
+    const PREFIX: &'static str = "pngme-recipient:";
+}
+
+/// Formats as the prefix followed by the base64 encoded key, as stored in key files.
+impl fmt::Display for Identity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", Self::PREFIX, STANDARD.encode(self.0.as_bytes()))
+    }
+}
+
+impl fmt::Display for Recipient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}{}", Self::PREFIX, STANDARD.encode(self.0.as_bytes()))
+    }
+}
+
+impl FromStr for Identity {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self(StaticSecret::from(parse_key(s, Self::PREFIX)?)))
+    }
+}
+
+impl FromStr for Recipient {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Ok(Self(PublicKey::from(parse_key(s, Self::PREFIX)?)))
+    }
+}
+
+fn parse_key(s: &str, prefix: &str) -> Result<[u8; 32]> {
+    let encoded = match s.trim().strip_prefix(prefix) {
+        Some(encoded) => encoded,
+        None => return Err(format!("key must start with {:?}", prefix).into()),
+    };
+    match <[u8; 32]>::try_from(STANDARD.decode(encoded)?) {
+        Ok(key) => Ok(key),
+        Err(_) => Err("key must be 32 bytes".into()),
+    }
+}
+
+/// Returns true if `bytes` start like encrypted chunk data.
+pub fn is_sealed(bytes: &[u8]) -> bool {
+    bytes.starts_with(&MAGIC)
+}
+
+/// Encrypts `payload` so that any of `recipients` can decrypt it. The payload
+/// is encrypted once with a random key, and that key is wrapped for every
+/// recipient with a key derived from an ephemeral Diffie-Hellman exchange.
+/// Wrapped keys don't name their recipient; an identity finds its own by trying
+/// to unwrap each of them.
+///
+/// The chunk data holds the following fields in order:
+/// 1. The magic bytes `PENC` *(4 bytes)*
+/// 2. The format version *(1 byte)*
+/// 3. The ephemeral public key *(32 bytes)*
+/// 4. The number of recipients *(1 byte)*
+/// 5. The payload key wrapped for each recipient *(48 bytes each)*
+/// 6. The encrypted payload, authenticated together with the fields above
+///    *(remaining bytes)*
+pub fn seal(payload: &[u8], recipients: &[Recipient]) -> Result<Vec<u8>> {
+    if recipients.is_empty() || recipients.len() > 255 {
+        return Err("between 1 and 255 recipients are required".into());
+    }
+    let mut payload_key = [0u8; 32];
+    OsRng.fill_bytes(&mut payload_key);
+    // A fresh key pair per message, used for every recipient and then dropped.
+    let ephemeral = StaticSecret::random_from_rng(OsRng);
+    let ephemeral_public = PublicKey::from(&ephemeral);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.extend(ephemeral_public.as_bytes());
+    bytes.push(recipients.len() as u8);
+    for recipient in recipients {
+        let shared = ephemeral.diffie_hellman(&recipient.0);
+        let wrap_key = wrap_key(&shared, &ephemeral_public, &recipient.0)?;
+        bytes.extend(encrypt(&wrap_key, &payload_key, &[])?);
+    }
+    let ciphertext = encrypt(&payload_key, payload, &bytes)?;
+    bytes.extend(ciphertext);
+    Ok(bytes)
+}
+
+/// Decrypts data made by `seal`. Fails if `identity` is not one of its recipients.
+pub fn open(bytes: &[u8], identity: &Identity) -> Result<Vec<u8>> {
+    if !is_sealed(bytes) {
+        return Err("chunk data is not encrypted".into());
+    }
+    let header = match bytes.get(..HEADER_LENGTH) {
+        Some(header) => header,
+        None => return Err("encrypted data is truncated".into()),
+    };
+    if header[4] != VERSION {
+        return Err(format!("unsupported encryption version {}", header[4]).into());
+    }
+    let ephemeral_public = PublicKey::from(<[u8; 32]>::try_from(&header[5..37])?);
+    let count = header[37] as usize;
+    let aad_length = HEADER_LENGTH + count * WRAPPED_KEY_LENGTH;
+    if bytes.len() < aad_length {
+        return Err("encrypted data is truncated".into());
+    }
+
+    let shared = identity.0.diffie_hellman(&ephemeral_public);
+    let wrap_key = wrap_key(&shared, &ephemeral_public, &identity.recipient().0)?;
+    let payload_key: [u8; 32] = bytes[HEADER_LENGTH..aad_length]
+        .chunks(WRAPPED_KEY_LENGTH)
+        .find_map(|wrapped| decrypt(&wrap_key, wrapped, &[]).ok()?.try_into().ok())
+        .ok_or("data is not encrypted for this identity")?;
+    decrypt(&payload_key, &bytes[aad_length..], &bytes[..aad_length])
+}
+
+/// Derives the key that wraps the payload key for `recipient`, bound to both
+/// public keys of the exchange.
+fn wrap_key(
+    shared: &SharedSecret,
+    ephemeral_public: &PublicKey,
+    recipient: &PublicKey,
+) -> Result<[u8; 32]> {
+    if !shared.was_contributory() {
+        return Err("recipient key is not a valid X25519 public key".into());
+    }
+    let mut salt = ephemeral_public.to_bytes().to_vec();
+    salt.extend(recipient.as_bytes());
+    let mut key = [0u8; 32];
+    Hkdf::<Sha256>::new(Some(&salt), shared.as_bytes())
+        .expand(WRAP_INFO, &mut key)
+        .map_err(|_| "key derivation failed")?;
+    Ok(key)
+}
+
+// Every key encrypts exactly one message, so a constant nonce is never reused.
+const NONCE: [u8; 12] = [0; 12];
+
+fn encrypt(key: &[u8; 32], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .encrypt(Nonce::from_slice(&NONCE), Payload { msg, aad })
+        .map_err(|_| "encryption failed".into())
+}
+
+fn decrypt(key: &[u8; 32], msg: &[u8], aad: &[u8]) -> Result<Vec<u8>> {
+    ChaCha20Poly1305::new(Key::from_slice(key))
+        .decrypt(Nonce::from_slice(&NONCE), Payload { msg, aad })
+        .map_err(|_| "decryption failed".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_seal_open() {
+        let alice = Identity::generate();
+        let bob = Identity::generate();
+        let eve = Identity::generate();
+        let sealed = seal(b"deploy at noon", &[alice.recipient(), bob.recipient()]).unwrap();
+        assert!(is_sealed(&sealed));
+        assert_eq!(open(&sealed, &alice).unwrap(), b"deploy at noon");
+        assert_eq!(open(&sealed, &bob).unwrap(), b"deploy at noon");
+        assert!(open(&sealed, &eve).is_err());
+        assert!(seal(b"nobody", &[]).is_err());
+    }
+
+    #[test]
+    fn test_tampering() {
+        let alice = Identity::generate();
+        let sealed = seal(b"deploy at noon", &[alice.recipient()]).unwrap();
+        for idx in [4, 10, HEADER_LENGTH + 3, sealed.len() - 1] {
+            let mut tampered = sealed.clone();
+            tampered[idx] ^= 1;
+            assert!(open(&tampered, &alice).is_err());
+        }
+        assert!(open(&sealed[..HEADER_LENGTH + 10], &alice).is_err());
+        assert!(open(b"PENC", &alice).is_err());
+    }
+
+    #[test]
+    fn test_key_strings() {
+        let identity = Identity::generate();
+        let recipient = identity.recipient();
+        let parsed = Identity::from_str(&format!("{}\n", identity)).unwrap();
+        assert_eq!(parsed.recipient(), recipient);
+        assert_eq!(
+            Recipient::from_str(&recipient.to_string()).unwrap(),
+            recipient
+        );
+        assert!(Recipient::from_str(&identity.to_string()).is_err());
+        assert!(Recipient::from_str("pngme-recipient:AAAA").is_err());
+    }
+}
@@ -0,0 +1,255 @@
+use std::convert::TryFrom;
+use std::fmt;
+
+use crate::{Error, Result};
+
+/// A chunk of a RIFF file: a four character code followed by a little endian
+/// length and the data, padded to an even length.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RiffChunk {
+    fourcc: [u8; 4],
+    data: Vec<u8>,
+}
+
+impl RiffChunk {
+    pub fn new(fourcc: [u8; 4], data: Vec<u8>) -> RiffChunk {
+        Self { fourcc, data }
+    }
+
+    pub fn fourcc(&self) -> &[u8; 4] {
+        &self.fourcc
+    }
+
+    /// The four character code as a string, with invalid bytes replaced.
+    pub fn label(&self) -> String {
+        String::from_utf8_lossy(&self.fourcc).to_string()
+    }
+
+    pub fn data(&self) -> &[u8] {
+        &self.data
+    }
+
+    /// Returns this chunk as a byte sequence, including the padding byte.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = self.fourcc.to_vec();
+        bytes.extend((self.data.len() as u32).to_le_bytes());
+        bytes.extend(&self.data);
+        if self.data.len() % 2 == 1 {
+            bytes.push(0);
+        }
+        bytes
+    }
+}
+
+impl fmt::Display for RiffChunk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ({} bytes)", self.label(), self.data.len())
+    }
+}
+
+/// A RIFF container such as a WebP image.
+/// https://developers.google.com/speed/webp/docs/riff_container
+pub struct Riff {
+    form_type: [u8; 4],
+    chunks: Vec<RiffChunk>,
+}
+
+impl Riff {
+    pub const MAGIC: [u8; 4] = *b"RIFF";
+    pub const WEBP: [u8; 4] = *b"WEBP";
+
+    pub fn from_chunks(form_type: [u8; 4], chunks: Vec<RiffChunk>) -> Riff {
+        Self { form_type, chunks }
+    }
+
+    /// The form type following the RIFF header, e.g. `WEBP`.
+    pub fn form_type(&self) -> &[u8; 4] {
+        &self.form_type
+    }
+
+    pub fn chunks(&self) -> &[RiffChunk] {
+        &self.chunks
+    }
+
+    /// Returns true if `bytes` start with a RIFF header of the WebP form type.
+    pub fn is_webp(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::MAGIC) && bytes.get(8..12) == Some(&Self::WEBP[..])
+    }
+
+    /// Appends a chunk. WebP only allows chunks it doesn't know in the extended
+    /// format, so a simple WebP file gets a `VP8X` header first.
+    pub fn append_chunk(&mut self, chunk: RiffChunk) -> Result<()> {
+        if self.form_type == Self::WEBP {
+            self.extend_webp()?;
+        }
+        self.chunks.push(chunk);
+        Ok(())
+    }
+
+    /// Removes the first chunk with the given four character code.
+    pub fn remove_chunk(&mut self, fourcc: &[u8; 4]) -> Result<RiffChunk> {
+        match self.chunks.iter().position(|x| &x.fourcc == fourcc) {
+            Some(idx) => Ok(self.chunks.remove(idx)),
+            None => Err(format!("{} not found", String::from_utf8_lossy(fourcc)).into()),
+        }
+    }
+
+    /// Returns this file as a byte sequence.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let chunks: Vec<u8> = self.chunks.iter().flat_map(|x| x.as_bytes()).collect();
+        let mut bytes = Self::MAGIC.to_vec();
+        bytes.extend((chunks.len() as u32 + 4).to_le_bytes());
+        bytes.extend(self.form_type);
+        bytes.extend(chunks);
+        bytes
+    }
+
+    /// Turns a simple WebP file into the extended format by prepending a `VP8X`
+    /// chunk holding the canvas size of its bitstream. Extended files are left as is.
+    fn extend_webp(&mut self) -> Result<()> {
+        let first = match self.chunks.first() {
+            Some(chunk) => chunk,
+            None => return Err("WebP file has no image data".into()),
+        };
+        let (width, height, alpha) = match first.fourcc() {
+            b"VP8X" => return Ok(()),
+            b"VP8 " => vp8_size(first.data())?,
+            b"VP8L" => vp8l_size(first.data())?,
+            _ => return Err(format!("unexpected WebP chunk {}", first.label()).into()),
+        };
+        let mut data = vec![if alpha { 0x10 } else { 0 }, 0, 0, 0];
+        data.extend(&(width - 1).to_le_bytes()[..3]);
+        data.extend(&(height - 1).to_le_bytes()[..3]);
+        self.chunks.insert(0, RiffChunk::new(*b"VP8X", data));
+        Ok(())
+    }
+}
+
+/// Reads the frame size of a lossy key frame, which never has alpha.
+fn vp8_size(data: &[u8]) -> Result<(u32, u32, bool)> {
+    let (width, height) = match data.get(3..10) {
+        Some([0x9d, 0x01, 0x2a, w0, w1, h0, h1]) => (
+            u16::from_le_bytes([*w0, *w1]) as u32 & 0x3fff,
+            u16::from_le_bytes([*h0, *h1]) as u32 & 0x3fff,
+        ),
+        _ => return Err("invalid VP8 bitstream".into()),
+    };
+    if width == 0 || height == 0 {
+        return Err("VP8 frame has a zero width or height".into());
+    }
+    Ok((width, height, false))
+}
+
+/// Reads the image size and alpha hint of a lossless bitstream.
+fn vp8l_size(data: &[u8]) -> Result<(u32, u32, bool)> {
+    match data.get(0..5) {
+        Some([0x2f, b0, b1, b2, b3]) => {
+            let bits = u32::from_le_bytes([*b0, *b1, *b2, *b3]);
+            Ok((
+                (bits & 0x3fff) + 1,
+                ((bits >> 14) & 0x3fff) + 1,
+                bits >> 28 & 1 == 1,
+            ))
+        }
+        _ => Err("invalid VP8L bitstream".into()),
+    }
+}
+
+impl TryFrom<&[u8]> for Riff {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < 12 || !bytes.starts_with(&Self::MAGIC) {
+            return Err("invalid RIFF header".into());
+        }
+        let size = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        let end = match size.checked_add(8) {
+            Some(end) if end <= bytes.len() && size >= 4 => end,
+            _ => return Err("RIFF size exceeds the file".into()),
+        };
+        let mut form_type = [0u8; 4];
+        form_type.copy_from_slice(&bytes[8..12]);
+
+        let mut chunks = Vec::new();
+        let mut pos = 12;
+        while pos < end {
+            let header = match bytes.get(pos..pos + 8) {
+                Some(header) if pos + 8 <= end => header,
+                _ => return Err("RIFF chunk header is truncated".into()),
+            };
+            let mut fourcc = [0u8; 4];
+            fourcc.copy_from_slice(&header[..4]);
+            let len = u32::from_le_bytes([header[4], header[5], header[6], header[7]]) as usize;
+            let data_end = match (pos + 8).checked_add(len) {
+                Some(data_end) if data_end <= end => data_end,
+                _ => return Err("RIFF chunk data is truncated".into()),
+            };
+            chunks.push(RiffChunk::new(fourcc, bytes[pos + 8..data_end].to_vec()));
+            // The padding byte may be missing after the last chunk.
+            pos = data_end + len % 2;
+        }
+        Ok(Self { form_type, chunks })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testing_webp() -> Vec<u8> {
+        // A 1x1 lossless WebP.
+        let vp8l = [0x2f, 0x00, 0x00, 0x00, 0x00, 0x88, 0x88, 0x08, 0x00];
+        Riff::from_chunks(Riff::WEBP, vec![RiffChunk::new(*b"VP8L", vp8l.to_vec())]).as_bytes()
+    }
+
+    #[test]
+    fn test_riff_roundtrip() {
+        let bytes = testing_webp();
+        assert!(Riff::is_webp(&bytes));
+        let riff = Riff::try_from(&bytes[..]).unwrap();
+        assert_eq!(riff.form_type(), b"WEBP");
+        assert_eq!(riff.chunks().len(), 1);
+        assert_eq!(riff.as_bytes(), bytes);
+    }
+
+    #[test]
+    fn test_riff_append_rejects_empty_vp8() {
+        let vp8 = [0x10, 0x00, 0x00, 0x9d, 0x01, 0x2a, 0x00, 0x00, 0x01, 0x00];
+        let bytes =
+            Riff::from_chunks(Riff::WEBP, vec![RiffChunk::new(*b"VP8 ", vp8.to_vec())]).as_bytes();
+        let mut riff = Riff::try_from(&bytes[..]).unwrap();
+        assert!(riff
+            .append_chunk(RiffChunk::new(*b"ruSt", b"secret".to_vec()))
+            .is_err());
+    }
+
+    #[test]
+    fn test_riff_padding() {
+        let chunk = RiffChunk::new(*b"ruSt", b"odd".to_vec());
+        assert_eq!(chunk.as_bytes(), b"ruSt\x03\0\0\0odd\0");
+    }
+
+    #[test]
+    fn test_riff_append_extends_webp() {
+        let mut riff = Riff::try_from(&testing_webp()[..]).unwrap();
+        riff.append_chunk(RiffChunk::new(*b"ruSt", b"secret".to_vec()))
+            .unwrap();
+        riff.append_chunk(RiffChunk::new(*b"ruSt", b"again".to_vec()))
+            .unwrap();
+        let fourccs: Vec<String> = riff.chunks().iter().map(|x| x.label()).collect();
+        assert_eq!(fourccs, vec!["VP8X", "VP8L", "ruSt", "ruSt"]);
+        assert_eq!(riff.chunks()[0].data(), &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+
+        let rebuilt = Riff::try_from(&riff.as_bytes()[..]).unwrap();
+        assert_eq!(rebuilt.chunks()[3].data(), b"again");
+    }
+
+    #[test]
+    fn test_riff_truncated() {
+        let bytes = testing_webp();
+        assert!(Riff::try_from(&bytes[..bytes.len() - 2]).is_err());
+        assert!(Riff::try_from(&b"RIFF"[..]).is_err());
+        let huge = b"RIFF\x0c\x00\x00\x00WEBPVP8L\xff\xff\xff\xff";
+        assert!(Riff::try_from(&huge[..]).is_err());
+    }
+}
@@ -0,0 +1,663 @@
+use std::fmt;
+
+use serde::Serialize;
+
+use crate::container::ContainerKind;
+use crate::filter;
+use crate::png::Png;
+use crate::zlib;
+
+/// How likely a finding is to mean hidden data. `None` marks notes that
+/// don't raise the risk of a file, such as the results of clean checks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Risk {
+    None,
+    Low,
+    Medium,
+    High,
+}
+
+impl fmt::Display for Risk {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Risk::None => "none",
+            Risk::Low => "low",
+            Risk::Medium => "medium",
+            Risk::High => "high",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Something in a file that may hide data or make it dangerous to open.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Finding {
+    /// A short stable name for the check that produced this finding.
+    pub check: &'static str,
+    pub risk: Risk,
+    pub message: String,
+}
+
+impl Finding {
+    fn new(check: &'static str, risk: Risk, message: String) -> Finding {
+        Finding {
+            check,
+            risk,
+            message,
+        }
+    }
+}
+
+/// Everything `scan` found in one file. The risk of the file is the highest
+/// risk of its findings.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Report {
+    pub risk: Risk,
+    pub findings: Vec<Finding>,
+}
+
+// Magic bytes of formats commonly glued onto images.
+const SIGNATURES: &[(&[u8], &str)] = &[
+    (b"PK\x03\x04", "ZIP archive"),
+    (b"%PDF", "PDF document"),
+    (b"\x89PNG\r\n\x1a\n", "PNG image"),
+    (b"\xff\xd8\xff", "JPEG image"),
+    (b"GIF87a", "GIF image"),
+    (b"GIF89a", "GIF image"),
+    (b"Rar!\x1a\x07", "RAR archive"),
+    (b"7z\xbc\xaf\x27\x1c", "7-Zip archive"),
+    (b"\x1f\x8b", "gzip data"),
+    (b"\x7fELF", "ELF executable"),
+    (b"MZ", "Windows executable"),
+    (b"#!", "script"),
+    (b"<?php", "PHP script"),
+];
+
+// Markup that browsers render even without a matching file extension.
+const MARKUP: &[(&str, &str)] = &[
+    ("<!doctype html", "HTML document"),
+    ("<html", "HTML document"),
+    ("<script", "HTML script"),
+    ("<svg", "SVG image"),
+];
+
+/// Public ancillary chunk types registered for PNG, MNG and JNG.
+const REGISTERED_CHUNKS: &[&str] = &[
+    "bKGD", "cHRM", "cICP", "cLLI", "dSIG", "eXIf", "gAMA", "hIST", "iCCP", "iTXt", "mDCV", "oFFs",
+    "pCAL", "pHYs", "sBIT", "sCAL", "sPLT", "sRGB", "sTER", "tEXt", "tIME", "tRNS", "zTXt", "acTL",
+    "fcTL", "fdAT", "gIFg", "gIFt", "gIFx", "fRAc", "eXPI", "fPRI", "nEED", "pHYg", "mkBF", "mkBS",
+    "mkBT", "mkTS", "prVW",
+];
+
+/// Chunk types whose data is compressed or may be, so high entropy is expected.
+const COMPRESSED_CHUNKS: &[&str] = &["zTXt", "iTXt", "iCCP", "eXIf", "fdAT"];
+
+/// Chunks shorter than this are too short for a meaningful entropy.
+const MIN_ENTROPY_LENGTH: usize = 64;
+
+/// Fewer samples than this don't give meaningful LSB statistics.
+const MIN_SAMPLES: usize = 1024;
+
+/// Names the format of `bytes` if they start like a well known file. A ZIP
+/// end of central directory record anywhere counts too, since archive readers
+/// look for it from the end of the file.
+pub fn identify(bytes: &[u8]) -> Option<&'static str> {
+    if let Some((_, name)) = SIGNATURES
+        .iter()
+        .find(|(magic, _)| bytes.starts_with(magic))
+    {
+        return Some(name);
+    }
+    let start = bytes.iter().position(|x| !x.is_ascii_whitespace())?;
+    let head = String::from_utf8_lossy(&bytes[start..bytes.len().min(start + 16)]).to_lowercase();
+    if let Some((_, name)) = MARKUP.iter().find(|(tag, _)| head.starts_with(tag)) {
+        return Some(name);
+    }
+    if bytes.windows(4).any(|x| x == b"PK\x05\x06") {
+        return Some("ZIP archive");
+    }
+    None
+}
+
+/// Checks `png` for data hidden in its chunks, its image data or after its end.
+pub fn scan(png: &Png) -> Report {
+    let mut findings = Vec::new();
+    scan_chunks(png, &mut findings);
+    if png.kind() == ContainerKind::Png {
+        scan_image(png, &mut findings);
+    }
+    let risk = findings.iter().map(|x| x.risk).max().unwrap_or(Risk::None);
+    Report { risk, findings }
+}
+
+/// Describes trailing bytes as `print` and `scan` report them.
+pub fn describe_trailer(trailer: &[u8], end: &str) -> String {
+    match identify(trailer) {
+        Some(format) => format!("{} bytes after {} ({})", trailer.len(), end, format),
+        None => format!("{} bytes after {}", trailer.len(), end),
+    }
+}
+
+fn scan_chunks(png: &Png, findings: &mut Vec<Finding>) {
+    for (index, chunk) in png.chunks().iter().enumerate() {
+        let chunk_type = chunk.chunk_type();
+        if chunk_type.is_critical() {
+            continue;
+        }
+        let name = chunk_type.to_string();
+        if !chunk_type.is_public() {
+            findings.push(Finding::new(
+                "private-chunk",
+                Risk::Medium,
+                format!(
+                    "#{} {} is a private chunk ({} bytes)",
+                    index,
+                    name,
+                    chunk.data().len()
+                ),
+            ));
+        } else if !REGISTERED_CHUNKS.contains(&&name[..]) {
+            findings.push(Finding::new(
+                "unknown-chunk",
+                Risk::Low,
+                format!(
+                    "#{} {} is not a registered chunk ({} bytes)",
+                    index,
+                    name,
+                    chunk.data().len()
+                ),
+            ));
+        }
+        if chunk.data().len() >= MIN_ENTROPY_LENGTH && !COMPRESSED_CHUNKS.contains(&&name[..]) {
+            let bits = entropy(chunk.data());
+            // The highest entropy `len` bytes can reach is log2(len) bits.
+            let max = (chunk.data().len().min(256) as f64).log2();
+            if bits >= 0.9 * max {
+                findings.push(Finding::new(
+                    "high-entropy",
+                    Risk::Medium,
+                    format!(
+                        "#{} {} looks encrypted or compressed ({:.2} bits per byte)",
+                        index, name, bits
+                    ),
+                ));
+            }
+        }
+    }
+
+    let end = png.kind().end_chunk();
+    if let Some(pos) = png
+        .chunks()
+        .iter()
+        .position(|x| x.chunk_type().bytes() == end.as_bytes())
+    {
+        let after = png.chunks().len() - pos - 1;
+        if after > 0 {
+            findings.push(Finding::new(
+                "chunks-after-end",
+                Risk::Medium,
+                format!("{} chunks after {}", after, end),
+            ));
+        }
+    }
+    let trailer = png.trailer();
+    if !trailer.is_empty() {
+        // A trailer in a known format makes the file a polyglot.
+        let risk = match identify(trailer) {
+            Some(_) => Risk::High,
+            None => Risk::Medium,
+        };
+        findings.push(Finding::new(
+            "trailing-data",
+            risk,
+            describe_trailer(trailer, end),
+        ));
+    }
+}
+
+fn scan_image(png: &Png, findings: &mut Vec<Finding>) {
+    let idat = png.idat_data();
+    // Inflate no more than the header allows, so a small file cannot make
+    // the scan decompress gigabytes.
+    let ihdr = png.ihdr();
+    let limit = match &ihdr {
+        Ok(ihdr) => ihdr.raw_len(),
+        Err(_) => zlib::MAX_DECOMPRESSED_LEN,
+    };
+    let raw = match zlib::decompress_prefix(&idat, limit) {
+        Ok(None) => {
+            findings.push(Finding::new(
+                "idat-invalid",
+                Risk::Medium,
+                format!(
+                    "image data is longer than the {} bytes the header describes",
+                    limit
+                ),
+            ));
+            return;
+        }
+        Ok(Some((raw, rest))) => {
+            if !rest.is_empty() {
+                findings.push(Finding::new(
+                    "idat-trailing",
+                    Risk::High,
+                    format!(
+                        "{} bytes in IDAT after the end of the zlib stream",
+                        rest.len()
+                    ),
+                ));
+            }
+            raw
+        }
+        Err(e) => {
+            findings.push(Finding::new(
+                "idat-invalid",
+                Risk::Medium,
+                format!("image data does not decompress: {}", e),
+            ));
+            return;
+        }
+    };
+
+    let samples = match ihdr {
+        Ok(ihdr) if ihdr.bit_depth != 8 || ihdr.color_type == 3 => {
+            findings.push(Finding::new(
+                "lsb-analysis",
+                Risk::None,
+                "skipped, only 8-bit grayscale and truecolor images are analysed".to_string(),
+            ));
+            return;
+        }
+        Ok(ihdr) => match filter::unfilter(&ihdr, &raw) {
+            Ok(pixels) => color_samples(&pixels, ihdr.channels(), ihdr.color_type),
+            Err(e) => {
+                findings.push(Finding::new(
+                    "idat-invalid",
+                    Risk::Medium,
+                    format!("image data does not match IHDR: {}", e),
+                ));
+                return;
+            }
+        },
+        Err(_) => return,
+    };
+    if samples.iter().map(|x| x.len()).sum::<usize>() < MIN_SAMPLES {
+        findings.push(Finding::new(
+            "lsb-analysis",
+            Risk::None,
+            "skipped, the image is too small".to_string(),
+        ));
+        return;
+    }
+
+    let all: Vec<u8> = samples.concat();
+    if let Some(p) = lsb::chi_square(&all) {
+        // Smooth histograms fit as well as embedded ones, so on its own this is
+        // weak evidence.
+        let risk = if p >= 0.95 { Risk::Low } else { Risk::None };
+        findings.push(Finding::new(
+            "lsb-chi-square",
+            risk,
+            format!("probability of LSB embedding is {:.2}", p),
+        ));
+    }
+    if let Some(rate) = lsb::rs_estimate(&samples) {
+        let risk = if rate >= 0.3 {
+            Risk::High
+        } else if rate >= 0.1 {
+            Risk::Medium
+        } else {
+            Risk::None
+        };
+        findings.push(Finding::new(
+            "lsb-rs",
+            risk,
+            format!("RS analysis estimates {:.0}% of LSBs changed", rate * 100.0),
+        ));
+    }
+}
+
+/// Splits unfiltered 8-bit pixels into one sample sequence per color channel,
+/// leaving out alpha.
+fn color_samples(pixels: &[u8], channels: usize, color_type: u8) -> Vec<Vec<u8>> {
+    let colors = match color_type {
+        4 | 6 => channels - 1,
+        _ => channels,
+    };
+    (0..colors)
+        .map(|c| pixels.iter().skip(c).step_by(channels).copied().collect())
+        .collect()
+}
+
+/// Shannon entropy of `data` in bits per byte.
+fn entropy(data: &[u8]) -> f64 {
+    let mut counts = [0usize; 256];
+    for byte in data {
+        counts[*byte as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts
+        .iter()
+        .filter(|x| **x > 0)
+        .map(|x| {
+            let p = *x as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Statistical attacks on LSB replacement, which swaps values within the pairs
+/// 2k and 2k+1.
+mod lsb {
+    /// The chi-square attack by Westfeld and Pfitzmann. Embedding evens out the
+    /// counts within each pair of values, so a close fit to the evened out
+    /// histogram means embedding. Returns the probability of embedding.
+    pub fn chi_square(samples: &[u8]) -> Option<f64> {
+        let mut histogram = [0usize; 256];
+        for x in samples {
+            histogram[*x as usize] += 1;
+        }
+        let mut chi2 = 0.0;
+        let mut categories = 0;
+        for pair in histogram.chunks_exact(2) {
+            let expected = (pair[0] + pair[1]) as f64 / 2.0;
+            // Sparse categories make the statistic unreliable.
+            if expected < 5.0 {
+                continue;
+            }
+            chi2 += (pair[0] as f64 - expected).powi(2) / expected;
+            categories += 1;
+        }
+        if categories < 2 {
+            return None;
+        }
+        Some(gamma_q((categories - 1) as f64 / 2.0, chi2 / 2.0))
+    }
+
+    /// RS analysis by Fridrich, Goljan and Du. Groups of four neighbouring
+    /// samples are classified as regular or singular by whether flipping
+    /// makes them noisier, once with LSB flipping and once with the shifted
+    /// flipping that embedding doesn't touch. Returns the estimated fraction
+    /// of changed LSBs.
+    pub fn rs_estimate(channels: &[Vec<u8>]) -> Option<f64> {
+        let (d0, dn0) = rs_counts(channels, false)?;
+        let (d1, dn1) = rs_counts(channels, true)?;
+        let a = 2.0 * (d1 + d0);
+        let b = dn0 - dn1 - d1 - 3.0 * d0;
+        let c = d0 - dn0;
+        let x = if a.abs() < 1e-9 {
+            if b.abs() < 1e-9 {
+                return None;
+            }
+            -c / b
+        } else {
+            let discriminant = b * b - 4.0 * a * c;
+            if discriminant < 0.0 {
+                return None;
+            }
+            let roots = [
+                (-b + discriminant.sqrt()) / (2.0 * a),
+                (-b - discriminant.sqrt()) / (2.0 * a),
+            ];
+            if roots[0].abs() < roots[1].abs() {
+                roots[0]
+            } else {
+                roots[1]
+            }
+        };
+        Some((x / (x - 0.5)).clamp(0.0, 1.0))
+    }
+
+    /// Returns R - S for the mask [0, 1, 1, 0] and its negation, as fractions
+    /// of all groups. With `flipped` every LSB is flipped first.
+    fn rs_counts(channels: &[Vec<u8>], flipped: bool) -> Option<(f64, f64)> {
+        const MASK: [bool; 4] = [false, true, true, false];
+        let (mut positive, mut negative, mut groups) = (0i64, 0i64, 0usize);
+        for group in channels.iter().flat_map(|x| x.chunks_exact(4)) {
+            let group: Vec<i16> = group
+                .iter()
+                .map(|x| i16::from(if flipped { x ^ 1 } else { *x }))
+                .collect();
+            let original = smoothness(&group);
+            let apply = |f: fn(i16) -> i16| -> Vec<i16> {
+                group
+                    .iter()
+                    .zip(MASK)
+                    .map(|(x, m)| if m { f(*x) } else { *x })
+                    .collect()
+            };
+            positive += (smoothness(&apply(|x| x ^ 1)) - original).signum();
+            negative += (smoothness(&apply(|x| ((x + 1) ^ 1) - 1)) - original).signum();
+            groups += 1;
+        }
+        if groups < 256 {
+            return None;
+        }
+        Some((
+            positive as f64 / groups as f64,
+            negative as f64 / groups as f64,
+        ))
+    }
+
+    fn smoothness(group: &[i16]) -> i64 {
+        group
+            .windows(2)
+            .map(|x| i64::from((x[1] - x[0]).abs()))
+            .sum()
+    }
+
+    /// The regularized upper incomplete gamma function Q(a, x), following
+    /// Numerical Recipes.
+    pub(super) fn gamma_q(a: f64, x: f64) -> f64 {
+        if x <= 0.0 {
+            return 1.0;
+        }
+        let prefix = (a * x.ln() - x - ln_gamma(a)).exp();
+        if x < a + 1.0 {
+            // Series for the lower function P(a, x).
+            let (mut term, mut sum, mut n) = (1.0 / a, 1.0 / a, a);
+            for _ in 0..1000 {
+                n += 1.0;
+                term *= x / n;
+                sum += term;
+                if term.abs() < sum.abs() * 1e-12 {
+                    break;
+                }
+            }
+            (1.0 - sum * prefix).clamp(0.0, 1.0)
+        } else {
+            // Continued fraction for Q(a, x) by the modified Lentz method.
+            let tiny = 1e-300;
+            let mut b = x + 1.0 - a;
+            let mut c = 1.0 / tiny;
+            let mut d = 1.0 / b;
+            let mut h = d;
+            for i in 1..1000 {
+                let an = -(i as f64) * (i as f64 - a);
+                b += 2.0;
+                d = an * d + b;
+                if d.abs() < tiny {
+                    d = tiny;
+                }
+                c = b + an / c;
+                if c.abs() < tiny {
+                    c = tiny;
+                }
+                d = 1.0 / d;
+                let delta = d * c;
+                h *= delta;
+                if (delta - 1.0).abs() < 1e-12 {
+                    break;
+                }
+            }
+            (prefix * h).clamp(0.0, 1.0)
+        }
+    }
+
+    /// ln Γ(x) by the Lanczos approximation.
+    fn ln_gamma(x: f64) -> f64 {
+        const COEFFICIENTS: [f64; 6] = [
+            76.18009172947146,
+            -86.50532032941677,
+            24.01409824083091,
+            -1.231739572450155,
+            0.1208650973866179e-2,
+            -0.5395239384953e-5,
+        ];
+        let tmp = x + 5.5;
+        let tmp = tmp - (x + 0.5) * tmp.ln();
+        let mut series = 1.000000000190015;
+        for (i, c) in COEFFICIENTS.iter().enumerate() {
+            series += c / (x + 1.0 + i as f64);
+        }
+        -tmp + (2.5066282746310005 * series / x).ln()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::filter::{FilterStrategy, FilterType};
+    use crate::ihdr::Ihdr;
+    use crate::png::tests::chunk;
+
+    /// A grayscale image with smooth gradients and a little noise, like a photo.
+    fn photo_pixels(width: u32, height: u32) -> Vec<u8> {
+        let mut seed = 12345u32;
+        let mut pixels = Vec::new();
+        for y in 0..height {
+            for x in 0..width {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                let noise = ((seed >> 16) % 5) as f64;
+                let linear =
+                    100.0 + 60.0 * (x as f64 / 23.0).sin() + 40.0 * (y as f64 / 17.0).cos();
+                // Gamma encoded, as photos are.
+                pixels.push((((linear + noise) / 255.0).powf(0.45) * 255.0) as u8);
+            }
+        }
+        pixels
+    }
+
+    /// Replaces every LSB with pseudo random bits, as a full capacity embedding does.
+    fn embed(pixels: &[u8]) -> Vec<u8> {
+        let mut seed = 987654321u32;
+        pixels
+            .iter()
+            .map(|x| {
+                seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+                (x & !1) | ((seed >> 16) & 1) as u8
+            })
+            .collect()
+    }
+
+    fn testing_png(pixels: &[u8], width: u32, height: u32) -> Png {
+        let ihdr = Ihdr {
+            width,
+            height,
+            bit_depth: 8,
+            color_type: 0,
+            compression_method: 0,
+            filter_method: 0,
+            interlace_method: 0,
+        };
+        let filtered = filter::filter(&ihdr, pixels, FilterStrategy::Fixed(FilterType::None));
+        Png::from_chunks(vec![
+            chunk("IHDR", &ihdr.as_bytes()),
+            chunk("IDAT", &zlib::compress(&filtered, 6).unwrap()),
+            chunk("IEND", &[]),
+        ])
+    }
+
+    fn checks(report: &Report) -> Vec<(&'static str, Risk)> {
+        report.findings.iter().map(|x| (x.check, x.risk)).collect()
+    }
+
+    #[test]
+    fn test_identify() {
+        assert_eq!(identify(b"PK\x03\x04rest"), Some("ZIP archive"));
+        assert_eq!(identify(b"junk PK\x05\x06 end"), Some("ZIP archive"));
+        assert_eq!(identify(b"%PDF-1.7"), Some("PDF document"));
+        assert_eq!(identify(b"\n  <HTML><body>"), Some("HTML document"));
+        assert_eq!(identify(b"<Script>alert(1)"), Some("HTML script"));
+        assert_eq!(identify(b"hello"), None);
+        assert_eq!(identify(b""), None);
+    }
+
+    #[test]
+    fn test_scan_clean() {
+        let report = scan(&testing_png(&photo_pixels(128, 128), 128, 128));
+        assert!(report.risk <= Risk::Low, "{:?}", report);
+        assert_eq!(report.findings[1].check, "lsb-rs");
+        assert_eq!(report.findings[1].risk, Risk::None);
+    }
+
+    #[test]
+    fn test_scan_chunks() {
+        let mut png = testing_png(&[0; 16], 4, 4);
+        png.insert_chunk(chunk("tEXt", b"Comment\0hello"));
+        png.insert_chunk(chunk("ruSt", b"hello"));
+        png.insert_chunk(chunk("xYzW", &[0; 100]));
+        png.insert_chunk(chunk("zTXt", &(0..=255).collect::<Vec<u8>>()));
+        png.append_chunk(chunk("prVt", &(0..=255).collect::<Vec<u8>>()));
+        png.set_trailer(b"PK\x03\x04 archive".to_vec());
+        let report = scan(&png);
+        assert_eq!(report.risk, Risk::High);
+        assert_eq!(
+            checks(&report),
+            vec![
+                ("private-chunk", Risk::Medium),
+                ("unknown-chunk", Risk::Low),
+                ("private-chunk", Risk::Medium),
+                ("high-entropy", Risk::Medium),
+                ("chunks-after-end", Risk::Medium),
+                ("trailing-data", Risk::High),
+                ("lsb-analysis", Risk::None),
+            ]
+        );
+        assert_eq!(
+            report.findings[5].message,
+            "12 bytes after IEND (ZIP archive)"
+        );
+    }
+
+    #[test]
+    fn test_idat_trailing() {
+        let mut chunks = testing_png(&[0; 16], 4, 4).chunks().to_vec();
+        let mut idat = chunks[1].data().to_vec();
+        idat.extend(b"hidden");
+        chunks[1] = chunk("IDAT", &idat);
+        let report = scan(&Png::from_chunks(chunks));
+        assert_eq!(checks(&report)[0], ("idat-trailing", Risk::High));
+    }
+
+    #[test]
+    fn test_idat_too_long() {
+        let mut chunks = testing_png(&[0; 16], 4, 4).chunks().to_vec();
+        chunks[1] = chunk("IDAT", &zlib::compress(&[0; 100_000], 9).unwrap());
+        let report = scan(&Png::from_chunks(chunks));
+        assert_eq!(checks(&report), vec![("idat-invalid", Risk::Medium)]);
+        assert!(report.findings[0]
+            .message
+            .contains("longer than the 20 bytes"));
+    }
+
+    #[test]
+    fn test_lsb_embedding() {
+        let pixels = embed(&photo_pixels(128, 128));
+        let report = scan(&testing_png(&pixels, 128, 128));
+        assert_eq!(report.risk, Risk::High, "{:?}", report);
+        assert_eq!(
+            checks(&report),
+            vec![("lsb-chi-square", Risk::Low), ("lsb-rs", Risk::High)]
+        );
+    }
+
+    #[test]
+    fn test_gamma_q() {
+        // Survival function of the chi-square distribution, Q(k/2, x/2).
+        assert!((lsb::gamma_q(1.0, 1.0) - (-1.0f64).exp()).abs() < 1e-9);
+        assert!((lsb::gamma_q(5.0, 9.0) - 0.05496).abs() < 1e-4);
+        assert!((lsb::gamma_q(0.5, 1.920729) - 0.05).abs() < 1e-4);
+    }
+}
@@ -0,0 +1,541 @@
+use std::collections::BTreeMap;
+use std::fmt;
+#[cfg(not(target_arch = "wasm32"))]
+use std::fs;
+#[cfg(not(target_arch = "wasm32"))]
+use std::path::Path;
+use std::str::FromStr;
+
+use serde::Deserialize;
+
+use crate::chunk::Chunk;
+use crate::chunk_type::ChunkType;
+use crate::Result;
+
+/// Binary layouts of custom chunk types, read from a TOML file with one table
+/// per chunk type:
+///
+/// ```toml
+/// [prVd]
+/// endian = "little"
+///
+/// [[prVd.fields]]
+/// name = "id"
+/// type = "u32"
+///
+/// [[prVd.fields]]
+/// name = "kind"
+/// type = "u8"
+/// values = { raw = 0, thumbnail = 1 }
+///
+/// [[prVd.fields]]
+/// name = "note"
+/// type = "string"
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct Schema {
+    chunks: BTreeMap<String, ChunkSchema>,
+}
+
+/// The layout of one chunk type: its fields in order, with nothing between them.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ChunkSchema {
+    /// Byte order of numeric fields that don't set their own.
+    #[serde(default)]
+    pub endian: Endian,
+    pub fields: Vec<Field>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Endian {
+    #[default]
+    Big,
+    Little,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Field {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: FieldType,
+    pub endian: Option<Endian>,
+    /// Length in bytes of a `string` or `bytes` field. Without one the field
+    /// takes the rest of the data, so it must come last.
+    pub length: Option<usize>,
+    /// Names for the values of an integer field. When given, other values are
+    /// rejected by `ChunkSchema::encode`.
+    #[serde(default)]
+    pub values: BTreeMap<String, i64>,
+}
+
+/// The type of a field. Strings are UTF-8; a `cstring` ends with a NUL byte,
+/// and a fixed length `string` is padded with them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    U8,
+    U16,
+    U32,
+    U64,
+    I8,
+    I16,
+    I32,
+    I64,
+    F32,
+    F64,
+    String,
+    Cstring,
+    Bytes,
+}
+
+/// A decoded field value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Int(i128),
+    Float(f64),
+    Text(String),
+    Bytes(Vec<u8>),
+    /// An integer with a name from the field's `values`.
+    Named(String, i128),
+}
+
+impl Schema {
+    /// Parses and checks a schema.
+    pub fn from_toml(s: &str) -> Result<Schema> {
+        let schema: Schema = toml::from_str(s)?;
+        for (chunk_type, chunk_schema) in &schema.chunks {
+            ChunkType::from_str(chunk_type)?;
+            chunk_schema
+                .check()
+                .map_err(|e| format!("{}: {}", chunk_type, e))?;
+        }
+        Ok(schema)
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Schema> {
+        Self::from_toml(&fs::read_to_string(path)?)
+    }
+
+    /// Returns the layout of `chunk_type`, if the schema describes it.
+    pub fn get(&self, chunk_type: &str) -> Option<&ChunkSchema> {
+        self.chunks.get(chunk_type)
+    }
+
+    /// Decodes `chunk` if the schema describes its type.
+    pub fn decode(&self, chunk: &Chunk) -> Result<Option<Vec<(String, Value)>>> {
+        match self.get(&chunk.chunk_type().to_string()) {
+            Some(chunk_schema) => Ok(Some(chunk_schema.decode(chunk.data())?)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl ChunkSchema {
+    fn check(&self) -> Result<()> {
+        for (idx, field) in self.fields.iter().enumerate() {
+            if self.fields[..idx].iter().any(|x| x.name == field.name) {
+                return Err(format!("field {} is defined twice", field.name).into());
+            }
+            let is_sized = matches!(field.field_type, FieldType::String | FieldType::Bytes);
+            if field.length.is_some() && !is_sized {
+                return Err(format!("field {} can't have a length", field.name).into());
+            }
+            if is_sized && field.length.is_none() && idx + 1 != self.fields.len() {
+                return Err(
+                    format!("field {} needs a length or must come last", field.name).into(),
+                );
+            }
+            if !field.values.is_empty() {
+                let (min, max) = field.field_type.range().ok_or_else(|| {
+                    format!(
+                        "field {} can't have values, it is not an integer",
+                        field.name
+                    )
+                })?;
+                for (name, value) in &field.values {
+                    if !(min..=max).contains(&i128::from(*value)) {
+                        return Err(format!(
+                            "value {} = {} of field {} does not fit in {}",
+                            name, value, field.name, field.field_type
+                        )
+                        .into());
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Splits `data` into its fields. Fails unless the fields use up `data` exactly.
+    pub fn decode(&self, data: &[u8]) -> Result<Vec<(String, Value)>> {
+        let mut pos = 0;
+        let mut decoded = Vec::new();
+        for field in &self.fields {
+            let rest = &data[pos..];
+            let len = match (field.field_type.size(), field.length) {
+                (Some(size), _) | (None, Some(size)) => size,
+                (None, None) if field.field_type == FieldType::Cstring => {
+                    rest.iter()
+                        .position(|x| *x == 0)
+                        .ok_or_else(|| format!("field {} has no terminating NUL", field.name))?
+                        + 1
+                }
+                (None, None) => rest.len(),
+            };
+            let bytes = rest
+                .get(..len)
+                .ok_or_else(|| format!("data ends inside field {}", field.name))?;
+            decoded.push((field.name.clone(), field.read(bytes, self.endian)?));
+            pos += len;
+        }
+        if pos != data.len() {
+            return Err(format!("{} bytes left after the last field", data.len() - pos).into());
+        }
+        Ok(decoded)
+    }
+
+    /// Builds chunk data from a value for every field, given as text. Integer
+    /// fields also take the names of their values, and `bytes` fields take hex.
+    pub fn encode(&self, values: &[(&str, &str)]) -> Result<Vec<u8>> {
+        for (idx, (name, _)) in values.iter().enumerate() {
+            if !self.fields.iter().any(|x| x.name == *name) {
+                return Err(format!("no field named {}", name).into());
+            }
+            if values[..idx].iter().any(|(x, _)| x == name) {
+                return Err(format!("field {} is set twice", name).into());
+            }
+        }
+        let mut data = Vec::new();
+        for field in &self.fields {
+            let value = match values.iter().find(|(name, _)| *name == field.name) {
+                Some((_, value)) => value,
+                None => return Err(format!("field {} is not set", field.name).into()),
+            };
+            data.extend(field.write(value, self.endian)?);
+        }
+        Ok(data)
+    }
+}
+
+impl Field {
+    fn read(&self, bytes: &[u8], endian: Endian) -> Result<Value> {
+        let mut ordered = bytes.to_vec();
+        if self.endian.unwrap_or(endian) == Endian::Little {
+            ordered.reverse();
+        }
+        let b = &ordered[..];
+        let value = match self.field_type {
+            FieldType::U8 => Value::Int(b[0].into()),
+            FieldType::U16 => Value::Int(u16::from_be_bytes(b.try_into()?).into()),
+            FieldType::U32 => Value::Int(u32::from_be_bytes(b.try_into()?).into()),
+            FieldType::U64 => Value::Int(u64::from_be_bytes(b.try_into()?).into()),
+            FieldType::I8 => Value::Int((b[0] as i8).into()),
+            FieldType::I16 => Value::Int(i16::from_be_bytes(b.try_into()?).into()),
+            FieldType::I32 => Value::Int(i32::from_be_bytes(b.try_into()?).into()),
+            FieldType::I64 => Value::Int(i64::from_be_bytes(b.try_into()?).into()),
+            FieldType::F32 => Value::Float(f32::from_be_bytes(b.try_into()?).into()),
+            FieldType::F64 => Value::Float(f64::from_be_bytes(b.try_into()?)),
+            FieldType::String | FieldType::Cstring => {
+                let text = bytes.strip_suffix(&[0]).unwrap_or(bytes);
+                let text = match self.length {
+                    Some(_) => &bytes[..bytes.iter().rposition(|x| *x != 0).map_or(0, |x| x + 1)],
+                    None => text,
+                };
+                match String::from_utf8(text.to_vec()) {
+                    Ok(text) => Value::Text(text),
+                    Err(_) => return Err(format!("field {} is not valid UTF-8", self.name).into()),
+                }
+            }
+            FieldType::Bytes => Value::Bytes(bytes.to_vec()),
+        };
+        if let Value::Int(n) = value {
+            if let Some((name, _)) = self.values.iter().find(|(_, x)| i128::from(**x) == n) {
+                return Ok(Value::Named(name.clone(), n));
+            }
+        }
+        Ok(value)
+    }
+
+    fn write(&self, value: &str, endian: Endian) -> Result<Vec<u8>> {
+        let mut bytes = match self.field_type {
+            FieldType::F32 => (self.parse::<f32>(value)?).to_be_bytes().to_vec(),
+            FieldType::F64 => (self.parse::<f64>(value)?).to_be_bytes().to_vec(),
+            FieldType::String | FieldType::Cstring => {
+                let mut bytes = value.as_bytes().to_vec();
+                if self.field_type == FieldType::Cstring {
+                    if bytes.contains(&0) {
+                        return Err(format!("field {} can't contain NUL", self.name).into());
+                    }
+                    bytes.push(0);
+                }
+                self.pad(bytes)?
+            }
+            FieldType::Bytes => self.pad(
+                parse_hex(value)
+                    .ok_or_else(|| format!("field {}: {:?} is not hex", self.name, value))?,
+            )?,
+            _ => {
+                let n = match self.values.get(value) {
+                    Some(n) => i128::from(*n),
+                    None => self.parse::<i128>(value)?,
+                };
+                let (min, max) = self.field_type.range().unwrap_or_default();
+                if !(min..=max).contains(&n) {
+                    return Err(format!(
+                        "field {}: {} does not fit in {}",
+                        self.name, n, self.field_type
+                    )
+                    .into());
+                }
+                if !self.values.is_empty() && !self.values.values().any(|x| i128::from(*x) == n) {
+                    let names: Vec<&str> = self.values.keys().map(|x| &x[..]).collect();
+                    return Err(format!(
+                        "field {}: {} is not one of {}",
+                        self.name,
+                        value,
+                        names.join(", ")
+                    )
+                    .into());
+                }
+                let size = self.field_type.size().unwrap_or_default();
+                n.to_be_bytes()[16 - size..].to_vec()
+            }
+        };
+        if self.field_type.size().is_some() && self.endian.unwrap_or(endian) == Endian::Little {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+
+    fn parse<T: FromStr>(&self, value: &str) -> Result<T> {
+        value.parse().map_err(|_| {
+            format!(
+                "field {}: {:?} is not a {}",
+                self.name, value, self.field_type
+            )
+            .into()
+        })
+    }
+
+    /// Pads `bytes` with NULs to the fixed length of this field, if it has one.
+    fn pad(&self, mut bytes: Vec<u8>) -> Result<Vec<u8>> {
+        if let Some(length) = self.length {
+            if bytes.len() > length {
+                return Err(format!(
+                    "field {} is {} bytes long, got {}",
+                    self.name,
+                    length,
+                    bytes.len()
+                )
+                .into());
+            }
+            bytes.resize(length, 0);
+        }
+        Ok(bytes)
+    }
+}
+
+impl FieldType {
+    /// Size in bytes of numeric types.
+    fn size(&self) -> Option<usize> {
+        match self {
+            FieldType::U8 | FieldType::I8 => Some(1),
+            FieldType::U16 | FieldType::I16 => Some(2),
+            FieldType::U32 | FieldType::I32 | FieldType::F32 => Some(4),
+            FieldType::U64 | FieldType::I64 | FieldType::F64 => Some(8),
+            _ => None,
+        }
+    }
+
+    /// Smallest and largest value of integer types.
+    fn range(&self) -> Option<(i128, i128)> {
+        match self {
+            FieldType::U8 => Some((0, u8::MAX.into())),
+            FieldType::U16 => Some((0, u16::MAX.into())),
+            FieldType::U32 => Some((0, u32::MAX.into())),
+            FieldType::U64 => Some((0, u64::MAX.into())),
+            FieldType::I8 => Some((i8::MIN.into(), i8::MAX.into())),
+            FieldType::I16 => Some((i16::MIN.into(), i16::MAX.into())),
+            FieldType::I32 => Some((i32::MIN.into(), i32::MAX.into())),
+            FieldType::I64 => Some((i64::MIN.into(), i64::MAX.into())),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for FieldType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            FieldType::U8 => "u8",
+            FieldType::U16 => "u16",
+            FieldType::U32 => "u32",
+            FieldType::U64 => "u64",
+            FieldType::I8 => "i8",
+            FieldType::I16 => "i16",
+            FieldType::I32 => "i32",
+            FieldType::I64 => "i64",
+            FieldType::F32 => "f32",
+            FieldType::F64 => "f64",
+            FieldType::String => "string",
+            FieldType::Cstring => "cstring",
+            FieldType::Bytes => "bytes",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{}", n),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Text(text) => write!(f, "{:?}", text),
+            Value::Bytes(bytes) => {
+                for byte in bytes {
+                    write!(f, "{:02x}", byte)?;
+                }
+                Ok(())
+            }
+            Value::Named(name, n) => write!(f, "{} ({})", name, n),
+        }
+    }
+}
+
+fn parse_hex(s: &str) -> Option<Vec<u8>> {
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCHEMA: &str = r#"
+        [prVd]
+        endian = "little"
+
+        [[prVd.fields]]
+        name = "id"
+        type = "u32"
+
+        [[prVd.fields]]
+        name = "created"
+        type = "u64"
+        endian = "big"
+
+        [[prVd.fields]]
+        name = "kind"
+        type = "u8"
+        values = { raw = 0, thumbnail = 1 }
+
+        [[prVd.fields]]
+        name = "tag"
+        type = "string"
+        length = 4
+
+        [[prVd.fields]]
+        name = "note"
+        type = "string"
+    "#;
+
+    fn testing_values() -> Vec<(&'static str, &'static str)> {
+        vec![
+            ("id", "42"),
+            ("created", "1700000000"),
+            ("kind", "thumbnail"),
+            ("tag", "ab"),
+            ("note", "hi"),
+        ]
+    }
+
+    #[test]
+    fn test_roundtrip() {
+        let schema = Schema::from_toml(SCHEMA).unwrap();
+        let layout = schema.get("prVd").unwrap();
+        let data = layout.encode(&testing_values()).unwrap();
+        assert_eq!(&data[..4], &[42, 0, 0, 0]);
+        assert_eq!(&data[4..12], &1700000000u64.to_be_bytes());
+        assert_eq!(&data[12..], b"\x01ab\0\0hi");
+
+        let chunk = Chunk::new(ChunkType::from_str("prVd").unwrap(), data);
+        let decoded = schema.decode(&chunk).unwrap().unwrap();
+        let shown: Vec<String> = decoded
+            .iter()
+            .map(|(name, value)| format!("{}={}", name, value))
+            .collect();
+        assert_eq!(
+            shown,
+            vec![
+                "id=42",
+                "created=1700000000",
+                "kind=thumbnail (1)",
+                "tag=\"ab\"",
+                "note=\"hi\""
+            ]
+        );
+        let other = Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![]);
+        assert!(schema.decode(&other).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_encode_checks() {
+        let schema = Schema::from_toml(SCHEMA).unwrap();
+        let layout = schema.get("prVd").unwrap();
+        let with = |name: &'static str, value: &'static str| {
+            let mut values = testing_values();
+            values.retain(|(x, _)| *x != name);
+            values.push((name, value));
+            layout.encode(&values)
+        };
+        assert!(with("id", "-1").is_err());
+        assert!(with("id", "4294967296").is_err());
+        assert!(with("id", "0x10").is_err());
+        assert!(with("kind", "2").is_err());
+        assert!(with("kind", "1").is_ok());
+        assert!(with("tag", "toolong").is_err());
+        assert!(with("unknown", "1").is_err());
+        assert!(layout.encode(&testing_values()[1..]).is_err());
+    }
+
+    #[test]
+    fn test_decode_checks() {
+        let layout = Schema::from_toml(SCHEMA)
+            .unwrap()
+            .get("prVd")
+            .unwrap()
+            .clone();
+        let data = layout.encode(&testing_values()).unwrap();
+        assert!(layout.decode(&data[..10]).is_err());
+
+        let schema =
+            Schema::from_toml("[ruSt]\nfields = [{ name = \"n\", type = \"u16\" }]").unwrap();
+        let layout = schema.get("ruSt").unwrap();
+        assert_eq!(layout.decode(&[1, 2]).unwrap()[0].1, Value::Int(258));
+        assert!(layout.decode(&[1, 2, 3]).is_err());
+    }
+
+    #[test]
+    fn test_invalid_schemas() {
+        let field = |s: &str| Schema::from_toml(&format!("[ruSt]\nfields = [{}]", s));
+        assert!(field(r#"{ name = "a", type = "u128" }"#).is_err());
+        assert!(field(r#"{ name = "a", type = "u8", values = { big = 256 } }"#).is_err());
+        assert!(field(r#"{ name = "a", type = "string", values = { x = 1 } }"#).is_err());
+        assert!(field(r#"{ name = "a", type = "u8", length = 2 }"#).is_err());
+        assert!(field(r#"{ name = "a", type = "bytes" }, { name = "b", type = "u8" }"#).is_err());
+        assert!(field(r#"{ name = "a", type = "u8" }, { name = "a", type = "u8" }"#).is_err());
+        assert!(Schema::from_toml("[ru]\nfields = []").is_err());
+        assert!(
+            field(r#"{ name = "a", type = "cstring" }, { name = "b", type = "bytes" }"#).is_ok()
+        );
+    }
+}
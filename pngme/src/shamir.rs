@@ -0,0 +1,200 @@
+use std::convert::TryFrom;
+
+use rand_core::{OsRng, RngCore};
+
+use crate::gf256::{inverse, mul};
+use crate::{Error, Result};
+
+/// One of several shares of a secret split with Shamir's scheme over GF(2^8).
+/// Any `threshold` shares of the same secret rebuild it, fewer reveal nothing.
+///
+/// The chunk data holds the following fields in order:
+/// 1. The magic bytes `PSHR` *(4 bytes)*
+/// 2. The format version *(1 byte)*
+/// 3. A random id shared by all shares of one secret *(8 bytes)*
+/// 4. The threshold *(1 byte)*
+/// 5. The x coordinate of this share, never 0 *(1 byte)*
+/// 6. The y coordinates, one per byte of the secret *(remaining bytes)*
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Share {
+    pub id: [u8; 8],
+    pub threshold: u8,
+    pub x: u8,
+    pub y: Vec<u8>,
+}
+
+impl Share {
+    const MAGIC: [u8; 4] = *b"PSHR";
+    const VERSION: u8 = 1;
+    const HEADER_LENGTH: usize = 4 + 1 + 8 + 1 + 1;
+
+    /// Returns this share as the chunk data described above.
+    pub fn as_bytes(&self) -> Vec<u8> {
+        let mut bytes = Self::MAGIC.to_vec();
+        bytes.push(Self::VERSION);
+        bytes.extend(self.id);
+        bytes.push(self.threshold);
+        bytes.push(self.x);
+        bytes.extend(&self.y);
+        bytes
+    }
+
+    /// Returns true if `bytes` start like a share.
+    pub fn is_share(bytes: &[u8]) -> bool {
+        bytes.starts_with(&Self::MAGIC)
+    }
+}
+
+impl TryFrom<&[u8]> for Share {
+    type Error = Error;
+
+    fn try_from(bytes: &[u8]) -> Result<Self> {
+        if !Self::is_share(bytes) {
+            return Err("chunk data is not a secret share".into());
+        }
+        if bytes.len() < Self::HEADER_LENGTH {
+            return Err("secret share is truncated".into());
+        }
+        if bytes[4] != Self::VERSION {
+            return Err(format!("unsupported share version {}", bytes[4]).into());
+        }
+        let share = Self {
+            id: bytes[5..13].try_into()?,
+            threshold: bytes[13],
+            x: bytes[14],
+            y: bytes[Self::HEADER_LENGTH..].to_vec(),
+        };
+        if share.x == 0 || share.threshold == 0 {
+            return Err("secret share is invalid".into());
+        }
+        Ok(share)
+    }
+}
+
+/// Splits `secret` into `count` shares, any `threshold` of which rebuild it.
+pub fn split(secret: &[u8], count: u8, threshold: u8) -> Result<Vec<Share>> {
+    if threshold < 2 || threshold > count {
+        return Err(format!(
+            "threshold must be between 2 and the number of shares, got {} of {}",
+            threshold, count
+        )
+        .into());
+    }
+    let mut id = [0u8; 8];
+    OsRng.fill_bytes(&mut id);
+    let mut shares: Vec<Share> = (1..=count)
+        .map(|x| Share {
+            id,
+            threshold,
+            x,
+            y: Vec::with_capacity(secret.len()),
+        })
+        .collect();
+    // Each byte is the constant term of its own random polynomial of degree
+    // `threshold - 1`, and every share holds that polynomial evaluated at `x`.
+    let mut coefficients = vec![0u8; threshold as usize];
+    for byte in secret {
+        coefficients[0] = *byte;
+        OsRng.fill_bytes(&mut coefficients[1..]);
+        for share in &mut shares {
+            let y = coefficients
+                .iter()
+                .rev()
+                .fold(0, |acc, c| mul(acc, share.x) ^ c);
+            share.y.push(y);
+        }
+    }
+    Ok(shares)
+}
+
+/// Rebuilds the secret from shares made by one call to `split`.
+pub fn combine(shares: &[Share]) -> Result<Vec<u8>> {
+    let first = match shares.first() {
+        Some(first) => first,
+        None => return Err("no secret shares given".into()),
+    };
+    let mut xs: Vec<u8> = Vec::new();
+    let mut used: Vec<&Share> = Vec::new();
+    for share in shares {
+        if share.id != first.id || share.threshold != first.threshold {
+            return Err("secret shares belong to different secrets".into());
+        }
+        if share.y.len() != first.y.len() {
+            return Err("secret shares have different lengths".into());
+        }
+        if !xs.contains(&share.x) {
+            xs.push(share.x);
+            used.push(share);
+        }
+    }
+    if used.len() < first.threshold as usize {
+        return Err(format!(
+            "{} of {} required secret shares given",
+            used.len(),
+            first.threshold
+        )
+        .into());
+    }
+    used.truncate(first.threshold as usize);
+
+    // Lagrange interpolation at x = 0, where subtraction is xor.
+    let weights: Vec<u8> = used
+        .iter()
+        .map(|share| {
+            used.iter()
+                .filter(|other| other.x != share.x)
+                .fold(1, |acc, other| {
+                    mul(acc, mul(other.x, inverse(other.x ^ share.x)))
+                })
+        })
+        .collect();
+    Ok((0..first.y.len())
+        .map(|i| {
+            used.iter()
+                .zip(&weights)
+                .fold(0, |acc, (share, weight)| acc ^ mul(share.y[i], *weight))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_combine() {
+        let secret = b"launch code 0000";
+        let shares = split(secret, 5, 3).unwrap();
+        assert_eq!(shares.len(), 5);
+        for share in &shares {
+            assert_ne!(&share.y[..], &secret[..]);
+        }
+        for picked in [[0, 1, 2], [0, 2, 4], [4, 3, 1]] {
+            let subset: Vec<Share> = picked.iter().map(|i| shares[*i].clone()).collect();
+            assert_eq!(combine(&subset).unwrap(), secret);
+        }
+        assert_eq!(combine(&shares).unwrap(), secret);
+
+        let too_few = [shares[0].clone(), shares[3].clone(), shares[0].clone()];
+        assert!(combine(&too_few).is_err());
+        assert!(combine(&[]).is_err());
+    }
+
+    #[test]
+    fn test_mixed_secrets() {
+        let a = split(b"first", 3, 2).unwrap();
+        let b = split(b"other", 3, 2).unwrap();
+        assert!(combine(&[a[0].clone(), b[1].clone()]).is_err());
+    }
+
+    #[test]
+    fn test_share_bytes() {
+        let share = split(b"secret", 2, 2).unwrap().remove(1);
+        let bytes = share.as_bytes();
+        assert!(Share::is_share(&bytes));
+        assert_eq!(Share::try_from(&bytes[..]).unwrap(), share);
+        assert!(Share::try_from(&bytes[..10]).is_err());
+        assert!(split(b"secret", 3, 1).is_err());
+        assert!(split(b"secret", 3, 4).is_err());
+    }
+}
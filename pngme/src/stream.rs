@@ -0,0 +1,340 @@
+use std::convert::TryFrom;
+
+use futures_util::stream::{self, Stream};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use crate::chunk::{Chunk, ChunkRef};
+use crate::container::ContainerKind;
+use crate::png::{ChunkRefs, Limits, Png};
+use crate::Result;
+
+impl Png {
+    /// Reads a `Png` from `reader` without blocking the runtime.
+    pub async fn from_async_reader<R: AsyncRead + Unpin>(reader: R) -> Result<Png> {
+        Self::from_async_reader_with_limits(reader, &Limits::default()).await
+    }
+
+    /// Like `from_async_reader`, rejecting chunks and files that exceed `limits`
+    /// as soon as their length arrives.
+    pub async fn from_async_reader_with_limits<R: AsyncRead + Unpin>(
+        mut reader: R,
+        limits: &Limits,
+    ) -> Result<Png> {
+        let kind = read_header(&mut reader).await?;
+        let mut chunks = Vec::new();
+        let mut trailer = Vec::new();
+        let mut total = 0;
+        while let Some(chunk) = read_chunk(&mut reader, limits, chunks.len(), &mut total)
+            .await?
+            .map(verify_crc)
+            .transpose()?
+        {
+            let is_end = chunk.chunk_type().bytes() == kind.end_chunk().as_bytes();
+            chunks.push(chunk);
+            if is_end {
+                let (rest, rest_trailer) = read_after_end(&mut reader, limits, total).await?;
+                chunks.extend(rest);
+                trailer = rest_trailer;
+                break;
+            }
+        }
+        let mut png = Png::from_chunks_with_kind(kind, chunks);
+        png.set_trailer(trailer);
+        Ok(png)
+    }
+
+    /// Writes the whole file to `writer` and flushes it.
+    pub async fn write_to_async<W: AsyncWrite + Unpin>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(self.header()).await?;
+        for chunk in self.chunks() {
+            writer.write_all(&chunk.as_bytes()).await?;
+        }
+        writer.write_all(self.trailer()).await?;
+        writer.flush().await?;
+        Ok(())
+    }
+}
+
+/// Reads and checks the 8 byte signature at the start of `reader`.
+pub async fn read_header<R: AsyncRead + Unpin>(reader: &mut R) -> Result<ContainerKind> {
+    let mut header = [0; 8];
+    reader.read_exact(&mut header).await?;
+    ContainerKind::try_from(header)
+}
+
+/// Yields the chunks of `reader` one by one as their bytes arrive. The header
+/// must already have been read, see `read_header`. Like `PngRef::chunks` the
+/// stream ends after the first error, or when fewer bytes remain than a chunk
+/// length takes. Past the end chunk of `kind`, bytes that don't frame as a
+/// chunk end the stream without an error, but a chunk that fails its CRC
+/// does not.
+pub fn chunks<R: AsyncRead + Unpin>(
+    reader: R,
+    kind: ContainerKind,
+    limits: Limits,
+) -> impl Stream<Item = Result<Chunk>> {
+    stream::unfold(Some((reader, 0, 0, false)), move |state| async move {
+        let (mut reader, index, mut total, seen_end) = state?;
+        match read_chunk(&mut reader, &limits, index, &mut total).await {
+            Ok(Some(chunk)) if chunk.crc() != chunk.calculate_crc() => {
+                Some((Err("provided crc is not valid".into()), None))
+            }
+            Ok(Some(chunk)) => {
+                let seen_end =
+                    seen_end || chunk.chunk_type().bytes() == kind.end_chunk().as_bytes();
+                Some((Ok(chunk), Some((reader, index + 1, total, seen_end))))
+            }
+            Ok(None) => None,
+            Err(_) if seen_end => None,
+            Err(e) => Some((Err(e), None)),
+        }
+    })
+}
+
+/// Copies a file from `reader` to `writer` chunk by chunk, dropping every chunk
+/// for which `keep` returns false. The trailer is copied unchanged. Returns how
+/// many chunks were dropped.
+pub async fn filter_chunks<R, W, F>(
+    mut reader: R,
+    mut writer: W,
+    limits: &Limits,
+    mut keep: F,
+) -> Result<usize>
+where
+    R: AsyncRead + Unpin,
+    W: AsyncWrite + Unpin,
+    F: FnMut(&Chunk) -> bool,
+{
+    let kind = read_header(&mut reader).await?;
+    writer.write_all(&kind.signature()).await?;
+    let mut index = 0;
+    let mut total = 0;
+    let mut dropped = 0;
+    let mut trailer = Vec::new();
+    while let Some(chunk) = read_chunk(&mut reader, limits, index, &mut total)
+        .await?
+        .map(verify_crc)
+        .transpose()?
+    {
+        let is_end = chunk.chunk_type().bytes() == kind.end_chunk().as_bytes();
+        let mut batch = vec![chunk];
+        if is_end {
+            let (rest, rest_trailer) = read_after_end(&mut reader, limits, total).await?;
+            batch.extend(rest);
+            trailer = rest_trailer;
+        }
+        for chunk in batch {
+            if keep(&chunk) {
+                writer.write_all(&chunk.as_bytes()).await?;
+            } else {
+                dropped += 1;
+            }
+        }
+        if is_end {
+            break;
+        }
+        index += 1;
+    }
+    writer.write_all(&trailer).await?;
+    writer.flush().await?;
+    Ok(dropped)
+}
+
+/// Reads everything after the end chunk and splits it into chunks and the
+/// trailer, like `PngRef::chunks`. The trailer counts towards the total limit.
+async fn read_after_end<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    limits: &Limits,
+    total: u64,
+) -> Result<(Vec<Chunk>, Vec<u8>)> {
+    let budget = limits.max_total_size.saturating_sub(total);
+    let mut rest = Vec::new();
+    (&mut *reader)
+        .take(budget.saturating_add(1))
+        .read_to_end(&mut rest)
+        .await?;
+    if rest.len() as u64 > budget {
+        return Err(format!(
+            "chunk data exceeds the limit of {} bytes",
+            limits.max_total_size
+        )
+        .into());
+    }
+    let mut refs = ChunkRefs::after_end(&rest, *limits);
+    let chunks = (&mut refs)
+        .map(|x| x.map(|chunk| chunk.to_chunk()))
+        .collect::<Result<Vec<Chunk>>>()?;
+    Ok((chunks, refs.remainder().to_vec()))
+}
+
+/// Reads chunk #`index`, or `None` once fewer than 4 bytes are left. Only the
+/// framing is checked, see `verify_crc`. The data buffer only grows with the
+/// bytes that actually arrive, so a bogus length cannot make it allocate up front.
+async fn read_chunk<R: AsyncRead + Unpin>(
+    reader: &mut R,
+    limits: &Limits,
+    index: usize,
+    total: &mut u64,
+) -> Result<Option<Chunk>> {
+    let mut len_bytes = [0; 4];
+    let mut filled = 0;
+    while filled < len_bytes.len() {
+        match reader.read(&mut len_bytes[filled..]).await? {
+            0 => return Ok(None),
+            n => filled += n,
+        }
+    }
+    let len = u32::from_be_bytes(len_bytes);
+    limits.check(index, len, total)?;
+
+    let mut bytes = len_bytes.to_vec();
+    let rest = len as u64 + 8;
+    let read = (&mut *reader).take(rest).read_to_end(&mut bytes).await?;
+    if (read as u64) < rest {
+        return Err(format!("chunk #{} is truncated", index).into());
+    }
+    Ok(Some(ChunkRef::parse(&bytes, false)?.to_chunk()))
+}
+
+/// Passes on `chunk` if its stored CRC matches its type and data.
+fn verify_crc(chunk: Chunk) -> Result<Chunk> {
+    match chunk.crc() == chunk.calculate_crc() {
+        true => Ok(chunk),
+        false => Err("provided crc is not valid".into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk_type::ChunkType;
+    use crate::png::tests::minimal_png;
+    use futures_util::StreamExt;
+    use std::str::FromStr;
+
+    fn testing_png() -> Png {
+        let mut png = minimal_png();
+        png.insert_chunk(Chunk::new(
+            ChunkType::from_str("tEXt").unwrap(),
+            b"Title\0Dice".to_vec(),
+        ));
+        png.insert_chunk(Chunk::new(
+            ChunkType::from_str("IDAT").unwrap(),
+            vec![1, 2, 3],
+        ));
+        png
+    }
+
+    fn assert_send<T: Send>(_: &T) {}
+
+    #[tokio::test]
+    async fn test_async_roundtrip() {
+        let png = testing_png();
+        let mut bytes = Vec::new();
+        png.write_to_async(&mut bytes).await.unwrap();
+        assert_eq!(bytes, png.as_bytes());
+
+        let read = Png::from_async_reader(&bytes[..]).await.unwrap();
+        assert_eq!(read.as_bytes(), bytes);
+        assert!(Png::from_async_reader(&bytes[1..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_async_limits() {
+        let bytes = testing_png().as_bytes();
+        let limits = Limits {
+            max_chunk_size: 12,
+            ..Limits::default()
+        };
+        assert!(Png::from_async_reader_with_limits(&bytes[..], &limits)
+            .await
+            .is_err());
+        assert!(Png::from_async_reader(&bytes[..bytes.len() - 2])
+            .await
+            .is_err());
+
+        let mut huge = ContainerKind::Png.signature().to_vec();
+        huge.extend_from_slice(&[0x7f, 0xff, 0xff, 0xff]);
+        huge.extend_from_slice(b"IDAT");
+        assert!(Png::from_async_reader(&huge[..]).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_chunk_stream() {
+        let bytes = testing_png().as_bytes();
+        let mut reader = &bytes[..];
+        assert_eq!(read_header(&mut reader).await.unwrap(), ContainerKind::Png);
+        let stream = chunks(reader, ContainerKind::Png, Limits::default());
+        assert_send(&stream);
+        let types: Vec<String> = stream
+            .map(|x| x.unwrap().chunk_type().to_string())
+            .collect()
+            .await;
+        assert_eq!(types, vec!["IHDR", "tEXt", "IDAT", "IEND"]);
+
+        let mut corrupt = bytes.clone();
+        corrupt[8 + 25 + 10] ^= 1;
+        let mut reader = &corrupt[..];
+        read_header(&mut reader).await.unwrap();
+        let results: Vec<bool> = chunks(reader, ContainerKind::Png, Limits::default())
+            .map(|x| x.is_ok())
+            .collect()
+            .await;
+        assert_eq!(results, vec![true, false]);
+    }
+
+    #[tokio::test]
+    async fn test_async_trailer() {
+        let mut png = testing_png();
+        png.append_chunk(Chunk::new(ChunkType::from_str("ruSt").unwrap(), vec![1]));
+        png.set_trailer(b"PK\x03\x04 hidden".to_vec());
+        let bytes = png.as_bytes();
+        let read = Png::from_async_reader(&bytes[..]).await.unwrap();
+        assert_eq!(read.chunks().len(), 5);
+        assert_eq!(read.trailer(), png.trailer());
+
+        let mut reader = &bytes[..];
+        read_header(&mut reader).await.unwrap();
+        let results: Vec<bool> = chunks(reader, ContainerKind::Png, Limits::default())
+            .map(|x| x.is_ok())
+            .collect()
+            .await;
+        assert_eq!(results, vec![true; 5]);
+
+        let mut corrupt = bytes.clone();
+        let offset = corrupt.len() - png.trailer().len() - 5;
+        corrupt[offset] ^= 1;
+        assert!(Png::from_async_reader(&corrupt[..]).await.is_err());
+        let mut reader = &corrupt[..];
+        read_header(&mut reader).await.unwrap();
+        let results: Vec<bool> = chunks(reader, ContainerKind::Png, Limits::default())
+            .map(|x| x.is_ok())
+            .collect()
+            .await;
+        assert_eq!(results, vec![true, true, true, true, false]);
+
+        let mut out = Vec::new();
+        filter_chunks(&bytes[..], &mut out, &Limits::default(), |x| {
+            x.chunk_type().is_critical()
+        })
+        .await
+        .unwrap();
+        assert!(out.ends_with(png.trailer()));
+        assert_eq!(Png::try_from(&out[..]).unwrap().chunks().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_filter_chunks() {
+        let bytes = testing_png().as_bytes();
+        let mut out = Vec::new();
+        let dropped = filter_chunks(&bytes[..], &mut out, &Limits::default(), |x| {
+            x.chunk_type().is_critical()
+        })
+        .await
+        .unwrap();
+        assert_eq!(dropped, 1);
+        let png = Png::try_from(&out[..]).unwrap();
+        assert!(png.chunk_by_type("tEXt").is_none());
+        assert_eq!(png.chunks().len(), 3);
+    }
+}
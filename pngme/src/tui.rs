@@ -0,0 +1,277 @@
+use std::str::FromStr;
+
+use pngme::chunk_type::ChunkType;
+use pngme::editor::Editor;
+use pngme::hex;
+use pngme::known::KnownChunk;
+use pngme::message::Message;
+use pngme::png::Png;
+use pngme::Result;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Style, Stylize};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::{DefaultTerminal, Frame};
+
+const HELP: &str = "↑↓ select  shift+↑↓ move  d delete  e edit text  a add  s save  q quit";
+
+/// What the keyboard is currently driving: the chunk list or a prompt.
+enum Mode {
+    Normal,
+    EditText(String),
+    AddType(String),
+    AddData(String, String),
+    ConfirmQuit,
+}
+
+struct App {
+    editor: Editor,
+    path: String,
+    provenance: bool,
+    mode: Mode,
+    status: String,
+    scroll: u16,
+    quit: bool,
+}
+
+/// Opens `path` in the chunk editor until the user quits. With `provenance`,
+/// every save is recorded in the history chunk.
+pub fn run(path: &str, provenance: bool) -> Result<()> {
+    let mut app = App {
+        editor: Editor::new(Png::from_file(path)?),
+        path: path.to_string(),
+        provenance,
+        mode: Mode::Normal,
+        status: HELP.to_string(),
+        scroll: 0,
+        quit: false,
+    };
+    let mut terminal = ratatui::init();
+    let result = app.run(&mut terminal);
+    ratatui::restore();
+    result
+}
+
+impl App {
+    fn save(&mut self) -> Result<()> {
+        if self.provenance {
+            self.editor.record_history(&Png::from_file(&self.path)?)?;
+        }
+        self.editor.save(&self.path)
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> Result<()> {
+        while !self.quit {
+            terminal.draw(|frame| self.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press {
+                    self.handle_key(key);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn handle_key(&mut self, key: KeyEvent) {
+        let mode = std::mem::replace(&mut self.mode, Mode::Normal);
+        self.mode = match mode {
+            Mode::Normal => return self.handle_normal_key(key),
+            Mode::ConfirmQuit => {
+                self.quit = key.code == KeyCode::Char('y');
+                self.status = HELP.to_string();
+                Mode::Normal
+            }
+            Mode::EditText(text) => match edit_line(text, key) {
+                Prompt::Editing(text) => Mode::EditText(text),
+                Prompt::Cancelled => Mode::Normal,
+                Prompt::Done(text) => {
+                    let result = self.editor.set_text(&text);
+                    self.report(result, "text updated");
+                    Mode::Normal
+                }
+            },
+            Mode::AddType(chunk_type) => match edit_line(chunk_type, key) {
+                Prompt::Editing(chunk_type) => Mode::AddType(chunk_type),
+                Prompt::Cancelled => Mode::Normal,
+                Prompt::Done(chunk_type) => match ChunkType::from_str(&chunk_type) {
+                    Ok(_) => Mode::AddData(chunk_type, String::new()),
+                    Err(e) => {
+                        self.status = format!("error: {}", e);
+                        Mode::Normal
+                    }
+                },
+            },
+            Mode::AddData(chunk_type, data) => match edit_line(data, key) {
+                Prompt::Editing(data) => Mode::AddData(chunk_type, data),
+                Prompt::Cancelled => Mode::Normal,
+                Prompt::Done(data) => {
+                    let result = self.editor.add(&chunk_type, data.as_bytes());
+                    self.report(result, &format!("added {}", chunk_type));
+                    self.scroll = 0;
+                    Mode::Normal
+                }
+            },
+        };
+    }
+
+    fn handle_normal_key(&mut self, key: KeyEvent) {
+        let shift = key.modifiers.contains(KeyModifiers::SHIFT);
+        match key.code {
+            KeyCode::Up if shift => self.editor.move_up(),
+            KeyCode::Down if shift => self.editor.move_down(),
+            KeyCode::Char('K') => self.editor.move_up(),
+            KeyCode::Char('J') => self.editor.move_down(),
+            KeyCode::Up | KeyCode::Char('k') => {
+                self.editor.select_previous();
+                self.scroll = 0;
+            }
+            KeyCode::Down | KeyCode::Char('j') => {
+                self.editor.select_next();
+                self.scroll = 0;
+            }
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_add(10),
+            KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(10),
+            KeyCode::Char('d') | KeyCode::Delete => {
+                if let Some(chunk) = self.editor.delete() {
+                    self.status = format!("deleted {}", chunk.chunk_type());
+                    self.scroll = 0;
+                }
+            }
+            KeyCode::Char('e') => match self.editor.text() {
+                Some(text) => self.mode = Mode::EditText(text),
+                None => self.status = "only tEXt, zTXt and iTXt chunks can be edited".to_string(),
+            },
+            KeyCode::Char('a') => self.mode = Mode::AddType(String::new()),
+            KeyCode::Char('s') => {
+                let result = self.save();
+                self.report(result, &format!("saved {}", self.path));
+            }
+            KeyCode::Char('q') | KeyCode::Esc => {
+                if self.editor.is_modified() {
+                    self.mode = Mode::ConfirmQuit;
+                } else {
+                    self.quit = true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn report(&mut self, result: Result<()>, success: &str) {
+        self.status = match result {
+            Ok(()) => success.to_string(),
+            Err(e) => format!("error: {}", e),
+        };
+    }
+
+    fn draw(&self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(0), Constraint::Length(1)]).areas(frame.area());
+        let [list_area, detail] =
+            Layout::horizontal([Constraint::Length(26), Constraint::Min(0)]).areas(main);
+        let [decoded_area, hex_area] =
+            Layout::vertical([Constraint::Length(8), Constraint::Min(0)]).areas(detail);
+
+        let png = self.editor.png();
+        let items: Vec<ListItem> = png
+            .chunks()
+            .iter()
+            .enumerate()
+            .map(|(i, x)| ListItem::new(format!("{:>3} {} {:>10}", i, x.chunk_type(), x.length())))
+            .collect();
+        let title = format!(
+            "{}{}",
+            self.path,
+            if self.editor.is_modified() { " *" } else { "" }
+        );
+        let list = List::new(items)
+            .block(Block::bordered().title(title))
+            .highlight_style(Style::new().reversed());
+        let mut state = ListState::default().with_selected(Some(self.editor.selected()));
+        frame.render_stateful_widget(list, list_area, &mut state);
+
+        let (decoded, dump) = match self.editor.selected_chunk() {
+            Some(chunk) => {
+                let chunk_type = chunk.chunk_type();
+                let mut lines = vec![
+                    Line::from(format!(
+                        "{} ({}, {}, {})",
+                        chunk_type,
+                        if chunk_type.is_critical() {
+                            "critical"
+                        } else {
+                            "ancillary"
+                        },
+                        if chunk_type.is_public() {
+                            "public"
+                        } else {
+                            "private"
+                        },
+                        if chunk_type.is_safe_to_copy() {
+                            "safe to copy"
+                        } else {
+                            "unsafe to copy"
+                        }
+                    )),
+                    Line::from(format!("CRC {:08x}", chunk.crc())),
+                ];
+                let description = match (KnownChunk::decode(chunk), Message::try_from(chunk.data()))
+                {
+                    (Ok(Some(known)), _) => known.to_string(),
+                    (Err(e), _) => format!("invalid: {}", e),
+                    (_, Ok(message)) => message.to_string(),
+                    _ => String::from_utf8_lossy(chunk.data()).to_string(),
+                };
+                lines.push(Line::from(description));
+                let offset = png.chunk_offset(self.editor.selected()).unwrap_or(0);
+                (lines, hex::dump(&chunk.as_bytes(), offset))
+            }
+            None => (vec![Line::from("no chunks")], String::new()),
+        };
+        frame.render_widget(
+            Paragraph::new(decoded)
+                .wrap(Wrap { trim: false })
+                .block(Block::bordered().title("Decoded")),
+            decoded_area,
+        );
+        frame.render_widget(
+            Paragraph::new(dump)
+                .scroll((self.scroll, 0))
+                .block(Block::bordered().title("Hex")),
+            hex_area,
+        );
+
+        let line = match &self.mode {
+            Mode::Normal => self.status.clone(),
+            Mode::ConfirmQuit => "discard unsaved changes? (y/n)".to_string(),
+            Mode::EditText(text) => format!("text: {}█", text),
+            Mode::AddType(chunk_type) => format!("chunk type: {}█", chunk_type),
+            Mode::AddData(chunk_type, data) => format!("{} data: {}█", chunk_type, data),
+        };
+        frame.render_widget(Paragraph::new(line), status);
+    }
+}
+
+enum Prompt {
+    Editing(String),
+    Cancelled,
+    Done(String),
+}
+
+/// Applies `key` to a single line prompt holding `line`.
+fn edit_line(mut line: String, key: KeyEvent) -> Prompt {
+    match key.code {
+        KeyCode::Enter => Prompt::Done(line),
+        KeyCode::Esc => Prompt::Cancelled,
+        KeyCode::Backspace => {
+            line.pop();
+            Prompt::Editing(line)
+        }
+        KeyCode::Char(c) => {
+            line.push(c);
+            Prompt::Editing(line)
+        }
+        _ => Prompt::Editing(line),
+    }
+}
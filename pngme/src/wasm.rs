@@ -0,0 +1,179 @@
+use std::convert::TryFrom;
+
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+use crate::carrier::Carrier;
+use crate::ecc;
+use crate::known::KnownChunk;
+use crate::message::Message;
+use crate::png::Png;
+use crate::recipient;
+
+/// A parsed file, exported to JavaScript as `Png`. It only works on byte
+/// arrays, so nothing is read from or written to disk.
+#[wasm_bindgen(js_name = Png)]
+pub struct WasmPng {
+    png: Png,
+}
+
+/// What an inspection page shows for one chunk.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChunkSummary {
+    pub index: usize,
+    #[serde(rename = "type")]
+    pub chunk_type: String,
+    pub length: usize,
+    /// The contents of chunks defined by the PNG spec, as `inspect` shows them.
+    pub decoded: Option<String>,
+    /// A message embedded with a label.
+    pub message: Option<MessageSummary>,
+    /// True if the chunk holds a message encrypted for recipients.
+    pub encrypted: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MessageSummary {
+    pub label: String,
+    pub content_type: String,
+    pub created: u64,
+    pub expires: Option<u64>,
+    /// The payload, with invalid UTF-8 replaced.
+    pub text: String,
+}
+
+#[wasm_bindgen]
+extern "C" {
+    /// Milliseconds since the unix epoch, used for `message::now`.
+    #[wasm_bindgen(js_namespace = Date, js_name = now)]
+    pub(crate) fn date_now() -> f64;
+}
+
+#[wasm_bindgen(js_class = Png)]
+impl WasmPng {
+    /// Parses a PNG, MNG or JNG file.
+    pub fn parse(bytes: &[u8]) -> Result<WasmPng, JsError> {
+        match Png::try_from(bytes) {
+            Ok(png) => Ok(WasmPng { png }),
+            Err(e) => Err(JsError::new(&e.to_string())),
+        }
+    }
+
+    /// Describes every chunk as a JSON array of `ChunkSummary` objects.
+    pub fn list(&self) -> Result<String, JsError> {
+        serde_json::to_string(&summarize(&self.png)).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Returns the data of the first chunk of type `chunk_type`.
+    pub fn extract(&self, chunk_type: &str) -> Result<Vec<u8>, JsError> {
+        Carrier::extract(&self.png, chunk_type).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Adds a chunk of type `chunk_type` holding `data` where the spec allows
+    /// it, before the end chunk, as `encode` does.
+    pub fn embed(&mut self, chunk_type: &str, data: &[u8]) -> Result<(), JsError> {
+        Carrier::embed(&mut self.png, chunk_type, data).map_err(|e| JsError::new(&e.to_string()))
+    }
+
+    /// Returns the whole file.
+    #[wasm_bindgen(js_name = toBytes)]
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.png.as_bytes()
+    }
+}
+
+/// Summarizes the chunks of `png` for display. Known chunks are decoded, and
+/// messages are unwrapped from error correction where it is intact.
+pub fn summarize(png: &Png) -> Vec<ChunkSummary> {
+    png.chunks()
+        .iter()
+        .enumerate()
+        .map(|(index, chunk)| {
+            let data = match ecc::is_protected(chunk.data()) {
+                true => ecc::recover(chunk.data()).map(|x| x.data).ok(),
+                false => Some(chunk.data().to_vec()),
+            }
+            .unwrap_or_default();
+            ChunkSummary {
+                index,
+                chunk_type: chunk.chunk_type().to_string(),
+                length: chunk.data().len(),
+                decoded: KnownChunk::decode(chunk)
+                    .ok()
+                    .flatten()
+                    .map(|x| x.to_string()),
+                message: Message::try_from(&data[..])
+                    .ok()
+                    .map(|message| MessageSummary {
+                        text: String::from_utf8_lossy(&message.payload).into_owned(),
+                        label: message.label,
+                        content_type: message.content_type,
+                        created: message.created,
+                        expires: message.expires,
+                    }),
+                encrypted: recipient::is_sealed(&data),
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::Chunk;
+    use crate::chunk_type::ChunkType;
+    use crate::png::tests::minimal_png;
+    use std::str::FromStr;
+
+    pub(super) fn testing_png() -> Png {
+        let mut message = Message::new("greeting", "text/plain", b"hello".to_vec());
+        message.created = 1700000000;
+        let mut png = minimal_png();
+        png.append_chunk(Chunk::new(
+            ChunkType::from_str("ruSt").unwrap(),
+            message.as_bytes().unwrap(),
+        ));
+        png
+    }
+
+    #[test]
+    fn test_summarize() {
+        let summaries = summarize(&testing_png());
+        assert_eq!(summaries.len(), 3);
+        assert_eq!(summaries[0].chunk_type, "IHDR");
+        assert!(summaries[0].decoded.is_some());
+        assert!(summaries[0].message.is_none());
+        let message = summaries[2].message.as_ref().unwrap();
+        assert_eq!(message.label, "greeting");
+        assert_eq!(message.text, "hello");
+        assert!(!summaries[2].encrypted);
+    }
+}
+
+#[cfg(all(test, target_arch = "wasm32"))]
+mod wasm_tests {
+    use super::tests::testing_png;
+    use super::*;
+    use wasm_bindgen_test::wasm_bindgen_test;
+
+    #[wasm_bindgen_test]
+    fn test_parse_list() {
+        let png = WasmPng::parse(&testing_png().as_bytes()).unwrap();
+        let list: serde_json::Value = serde_json::from_str(&png.list().unwrap()).unwrap();
+        assert_eq!(list[2]["type"], "ruSt");
+        assert_eq!(list[2]["message"]["text"], "hello");
+        assert!(WasmPng::parse(b"not a png").is_err());
+    }
+
+    #[wasm_bindgen_test]
+    fn test_embed_extract() {
+        let mut png = WasmPng::parse(&testing_png().as_bytes()).unwrap();
+        png.embed("teSt", b"from the browser").unwrap();
+        let png = WasmPng::parse(&png.to_bytes()).unwrap();
+        assert_eq!(png.extract("teSt").unwrap(), b"from the browser");
+        let list: serde_json::Value = serde_json::from_str(&png.list().unwrap()).unwrap();
+        assert_eq!(list[1]["type"], "teSt");
+        assert_eq!(list[2]["type"], "IEND");
+        assert!(png.extract("noNe").is_err());
+    }
+}
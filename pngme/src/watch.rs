@@ -0,0 +1,161 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+use sha2::{Digest, Sha256};
+
+use crate::{Error, Result};
+
+/// The pngme commands `watch` runs on every file, parsed from an `--exec`
+/// string such as `"exif strip {} ; encode {} trCk tracked"`. Commands are
+/// separated by a lone `;`, `{}` stands for the file and a leading `pngme`
+/// may be left out. Words are split like a POSIX shell does.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Pipeline {
+    commands: Vec<Vec<String>>,
+}
+
+impl Pipeline {
+    /// The arguments of each command with `{}` replaced by `path`.
+    pub fn commands_for(&self, path: &str) -> Vec<Vec<String>> {
+        self.commands
+            .iter()
+            .map(|command| command.iter().map(|x| x.replace("{}", path)).collect())
+            .collect()
+    }
+}
+
+impl FromStr for Pipeline {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let words = shlex::split(s).ok_or("unbalanced quotes in pipeline")?;
+        let mut commands = Vec::new();
+        for command in words.split(|x| x == ";") {
+            let command = match command.first().map(|x| &x[..]) {
+                Some("pngme") => &command[1..],
+                _ => command,
+            };
+            if command.is_empty() {
+                return Err("empty command in pipeline".into());
+            }
+            if !command.iter().any(|x| x.contains("{}")) {
+                return Err(
+                    format!("{:?} does not use {{}} for the file", command.join(" ")).into(),
+                );
+            }
+            commands.push(command.to_vec());
+        }
+        Ok(Self { commands })
+    }
+}
+
+/// Holds back files that are still being written. A file is ready once no
+/// event arrived for it for `delay`.
+#[derive(Debug)]
+pub struct Debouncer {
+    delay: Duration,
+    pending: BTreeMap<PathBuf, Instant>,
+}
+
+impl Debouncer {
+    pub fn new(delay: Duration) -> Debouncer {
+        Self {
+            delay,
+            pending: BTreeMap::new(),
+        }
+    }
+
+    /// Notes an event for `path` at `at`, restarting its wait.
+    pub fn touch(&mut self, path: PathBuf, at: Instant) {
+        self.pending.insert(path, at);
+    }
+
+    /// Removes and returns the files that have been quiet since `delay` before `now`.
+    pub fn ready(&mut self, now: Instant) -> Vec<PathBuf> {
+        let ready: Vec<PathBuf> = self
+            .pending
+            .iter()
+            .filter(|(_, &at)| now.saturating_duration_since(at) >= self.delay)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in &ready {
+            self.pending.remove(path);
+        }
+        ready
+    }
+}
+
+/// Remembers the contents of handled files by hash, so that a file is only
+/// handled again when someone else changes it, not after `watch` itself
+/// rewrote it.
+#[derive(Debug, Default)]
+pub struct Seen {
+    hashes: HashMap<PathBuf, [u8; 32]>,
+}
+
+impl Seen {
+    /// Returns true if `path` holding `bytes` has not been handled yet.
+    pub fn is_new(&self, path: &Path, bytes: &[u8]) -> bool {
+        self.hashes.get(path) != Some(&Sha256::digest(bytes).into())
+    }
+
+    /// Records that `path` now holds `bytes`.
+    pub fn remember(&mut self, path: &Path, bytes: &[u8]) {
+        self.hashes
+            .insert(path.to_path_buf(), Sha256::digest(bytes).into());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pipeline() {
+        let pipeline =
+            Pipeline::from_str("pngme exif strip {} ; encode {} trCk 'design team'").unwrap();
+        assert_eq!(
+            pipeline.commands_for("in/a b.png"),
+            vec![
+                vec!["exif", "strip", "in/a b.png"],
+                vec!["encode", "in/a b.png", "trCk", "design team"],
+            ]
+        );
+        assert!(Pipeline::from_str("encode {} trCk 'open").is_err());
+        assert!(Pipeline::from_str("exif strip {} ;").is_err());
+        assert!(Pipeline::from_str("remove trCk").is_err());
+    }
+
+    #[test]
+    fn test_debouncer() {
+        let start = Instant::now();
+        let second = Duration::from_secs(1);
+        let mut debouncer = Debouncer::new(second);
+        debouncer.touch(PathBuf::from("a.png"), start);
+        debouncer.touch(PathBuf::from("b.png"), start);
+        debouncer.touch(PathBuf::from("a.png"), start + second);
+        assert!(debouncer.ready(start).is_empty());
+        assert_eq!(
+            debouncer.ready(start + second),
+            vec![PathBuf::from("b.png")]
+        );
+        assert_eq!(
+            debouncer.ready(start + second * 2),
+            vec![PathBuf::from("a.png")]
+        );
+        assert!(debouncer.ready(start + second * 3).is_empty());
+    }
+
+    #[test]
+    fn test_seen() {
+        let path = Path::new("a.png");
+        let mut seen = Seen::default();
+        assert!(seen.is_new(path, b"export"));
+        seen.remember(path, b"processed");
+        assert!(!seen.is_new(path, b"processed"));
+        assert!(seen.is_new(path, b"export"));
+        assert!(seen.is_new(Path::new("b.png"), b"processed"));
+    }
+}
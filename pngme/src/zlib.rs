@@ -0,0 +1,92 @@
+use std::io::{Read, Write};
+
+use flate2::bufread;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use crate::Result;
+
+/// The most `decompress` inflates, so that a small crafted stream cannot
+/// exhaust memory. Image data is bounded by its header instead, see
+/// `decompress_with_limit`.
+pub const MAX_DECOMPRESSED_LEN: u64 = 64 << 20;
+
+/// Inflates a complete zlib stream, as stored in `zTXt` or `iCCP` chunks,
+/// of at most `MAX_DECOMPRESSED_LEN` bytes.
+pub fn decompress(data: &[u8]) -> Result<Vec<u8>> {
+    decompress_with_limit(data, MAX_DECOMPRESSED_LEN)
+}
+
+/// Inflates a complete zlib stream, failing once it grows past `limit` bytes.
+pub fn decompress_with_limit(data: &[u8], limit: u64) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    ZlibDecoder::new(data)
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut out)?;
+    if out.len() as u64 > limit {
+        return Err(format!("data inflates to more than {} bytes", limit).into());
+    }
+    Ok(out)
+}
+
+/// Inflates the zlib stream at the start of `data` and returns it together
+/// with whatever follows the end of the stream. Returns `None` as soon as the
+/// stream grows past `limit` bytes.
+pub fn decompress_prefix(data: &[u8], limit: u64) -> Result<Option<(Vec<u8>, &[u8])>> {
+    let mut decoder = bufread::ZlibDecoder::new(data);
+    let mut out = Vec::new();
+    (&mut decoder)
+        .take(limit.saturating_add(1))
+        .read_to_end(&mut out)?;
+    if out.len() as u64 > limit {
+        return Ok(None);
+    }
+    Ok(Some((out, decoder.into_inner())))
+}
+
+/// Deflates `data` into a zlib stream using the given compression `level` (0-9).
+pub fn compress(data: &[u8], level: u32) -> Result<Vec<u8>> {
+    let mut encoder = ZlibEncoder::new(Vec::new(), Compression::new(level));
+    encoder.write_all(data)?;
+    Ok(encoder.finish()?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zlib_roundtrip() {
+        let data = b"This is where your secret message will be!".repeat(10);
+        let compressed = compress(&data, 9).unwrap();
+        assert!(compressed.len() < data.len());
+        assert_eq!(decompress(&compressed).unwrap(), data);
+    }
+
+    #[test]
+    fn test_decompress_prefix() {
+        let mut compressed = compress(b"pixels", 6).unwrap();
+        assert_eq!(decompress_prefix(&compressed, 6).unwrap().unwrap().1, b"");
+        compressed.extend(b"appended");
+        let (data, rest) = decompress_prefix(&compressed, 6).unwrap().unwrap();
+        assert_eq!(data, b"pixels");
+        assert_eq!(rest, b"appended");
+        assert_eq!(decompress_prefix(&compressed, 5).unwrap(), None);
+    }
+
+    #[test]
+    fn test_decompress_limit() {
+        let compressed = compress(&[0; 1000], 9).unwrap();
+        assert_eq!(
+            decompress_with_limit(&compressed, 1000).unwrap().len(),
+            1000
+        );
+        assert!(decompress_with_limit(&compressed, 999).is_err());
+    }
+
+    #[test]
+    fn test_decompress_invalid() {
+        assert!(decompress(&[1, 2, 3, 4]).is_err());
+    }
+}